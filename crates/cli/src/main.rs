@@ -0,0 +1,343 @@
+// 📦🦋 charted TestKit: testing library for Axum services with testcontainers support
+// Copyright (c) 2024 Noelware, LLC. <team@noelware.org>
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! `charted-testkit` runs [scenario files][charted_testkit::scenario] against an already running
+//! service, which is useful for smoke testing a service after it was deployed.
+//!
+//! ```shell
+//! $ charted-testkit --base-url http://localhost:3651 --var username=noel --format junit --output report.xml smoke/
+//! ```
+
+mod report;
+
+use charted_testkit::{
+    scenario::{self, Scenario},
+    TestContext,
+};
+use clap::Parser;
+use report::{Format, Outcome, Report, Status};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::Instant,
+};
+
+/// Runs charted TestKit scenario files against a running service.
+///
+/// Exits with `0` if every scenario passed, `1` if a scenario failed and `2` if a scenario
+/// couldn't be run at all (i.e, it has a syntax error or uses a variable that isn't defined).
+#[derive(Debug, Parser)]
+#[command(name = "charted-testkit", version, about, long_about)]
+struct Args {
+    /// Base URL of the service, i.e. `http://localhost:3651/api/v1`
+    #[arg(long, short = 'u', env = "TESTKIT_BASE_URL", value_parser = parse_base_url)]
+    base_url: String,
+
+    /// Scenario files to run. Directories are searched for `.http` files.
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+
+    /// Defines a variable. `VALUE` is parsed as JSON if it is valid JSON, otherwise it is a string.
+    #[arg(long = "var", short = 'v', value_name = "NAME=VALUE", value_parser = parse_var)]
+    vars: Vec<(String, Value)>,
+
+    /// Environment variables that start with `PREFIX` define a variable with the prefix stripped,
+    /// i.e. `TESTKIT_VAR_token` defines `{{token}}`. Variables from `--var` take precedence.
+    #[arg(long, value_name = "PREFIX", default_value = "TESTKIT_VAR_")]
+    env_prefix: String,
+
+    /// Format of the report.
+    #[arg(long, short, value_enum, default_value_t = Format::Text)]
+    format: Format,
+
+    /// Writes the report to a file rather than to standard output.
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+
+    /// Stops running scenarios once a scenario didn't pass.
+    #[arg(long)]
+    fail_fast: bool,
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let files = match collect_files(&args.files) {
+        Ok(files) => files,
+        Err(err) => {
+            eprintln!("error: {err}");
+            return ExitCode::from(2);
+        }
+    };
+
+    let mut variables = env_variables(std::env::vars(), &args.env_prefix);
+    variables.extend(args.vars.iter().cloned());
+
+    let report = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("failed to build tokio runtime")
+        .block_on(run(&args.base_url, &files, &variables, args.fail_fast));
+
+    let rendered = report.render(args.format);
+    match args.output {
+        Some(ref path) => {
+            if let Err(err) = std::fs::write(path, rendered) {
+                eprintln!("error: failed to write report to `{}`: {err}", path.display());
+                return ExitCode::from(2);
+            }
+        }
+
+        None => print!("{rendered}"),
+    }
+
+    ExitCode::from(report.exit_code())
+}
+
+/// Runs every scenario in `files` with a fresh [`TestContext`] and cookie jar, in order.
+async fn run(base_url: &str, files: &[PathBuf], variables: &HashMap<String, Value>, fail_fast: bool) -> Report {
+    let mut outcomes = Vec::with_capacity(files.len());
+    for file in files {
+        let started = Instant::now();
+        let status = run_scenario(base_url, file, variables).await;
+        let outcome = Outcome {
+            file: file.display().to_string(),
+            duration: started.elapsed(),
+            status,
+        };
+
+        eprintln!(
+            "scenario {} ... {} ({}ms)",
+            outcome.file,
+            match outcome.status {
+                Status::Passed => "ok",
+                Status::Failed { .. } => "FAILED",
+                Status::Errored { .. } => "ERROR",
+            },
+            outcome.duration.as_millis()
+        );
+
+        let stop = fail_fast && !matches!(outcome.status, Status::Passed);
+        outcomes.push(outcome);
+
+        if stop {
+            break;
+        }
+    }
+
+    Report {
+        base_url: base_url.to_owned(),
+        outcomes,
+    }
+}
+
+async fn run_scenario(base_url: &str, file: &Path, variables: &HashMap<String, Value>) -> Status {
+    let scenario = match Scenario::from_file(file) {
+        Ok(scenario) => variables.iter().fold(scenario, |scenario, (name, value)| {
+            scenario.variable(name, value.clone())
+        }),
+
+        Err(err) => {
+            return Status::Errored {
+                message: err.to_string(),
+            }
+        }
+    };
+
+    let ctx = TestContext::from_base_url(base_url).cookie_jar(true);
+    match scenario.run(&ctx).await {
+        Ok(_) => Status::Passed,
+        Err(err @ scenario::Error::Invalid { .. }) => Status::Errored {
+            message: err.to_string(),
+        },
+
+        Err(err) => Status::Failed {
+            line: err.line(),
+            message: err.to_string(),
+        },
+    }
+}
+
+/// Expands directories in `paths` into the `.http` files they contain, sorted by name.
+fn collect_files(paths: &[PathBuf]) -> Result<Vec<PathBuf>, String> {
+    let mut files = Vec::new();
+    for path in paths {
+        if !path.is_dir() {
+            files.push(path.clone());
+            continue;
+        }
+
+        let mut entries = std::fs::read_dir(path)
+            .and_then(|entries| {
+                entries
+                    .map(|entry| entry.map(|entry| entry.path()))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .map_err(|err| format!("failed to read directory `{}`: {err}", path.display()))?;
+
+        entries.retain(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "http"));
+        entries.sort();
+
+        if entries.is_empty() {
+            return Err(format!(
+                "directory `{}` doesn't contain any `.http` files",
+                path.display()
+            ));
+        }
+
+        files.extend(entries);
+    }
+
+    Ok(files)
+}
+
+/// Collects the variables of every environment variable in `vars` that starts with `prefix`.
+fn env_variables<I: IntoIterator<Item = (String, String)>>(vars: I, prefix: &str) -> HashMap<String, Value> {
+    vars.into_iter()
+        .filter_map(|(name, value)| {
+            let name = name.strip_prefix(prefix).filter(|name| !name.is_empty())?;
+            Some((name.to_owned(), parse_value(&value)))
+        })
+        .collect()
+}
+
+fn parse_base_url(url: &str) -> Result<String, String> {
+    let uri = url.parse::<http::Uri>().map_err(|err| err.to_string())?;
+    if uri.scheme_str() != Some("http") || uri.authority().is_none() {
+        return Err("expected an absolute `http://` URL".into());
+    }
+
+    Ok(url.to_owned())
+}
+
+fn parse_var(var: &str) -> Result<(String, Value), String> {
+    match var.split_once('=') {
+        Some((name, value)) if !name.is_empty() => Ok((name.to_owned(), parse_value(value))),
+        _ => Err("expected `NAME=VALUE`".into()),
+    }
+}
+
+fn parse_value(value: &str) -> Value {
+    serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{collect_files, env_variables, parse_base_url, parse_var, run, Status};
+    use axum::{http::StatusCode, routing, Router};
+    use charted_testkit::TestContext;
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+
+    #[test]
+    fn variables() {
+        assert_eq!(parse_var("id=1").unwrap(), ("id".into(), json!(1)));
+        assert_eq!(parse_var("name=noel").unwrap(), ("name".into(), json!("noel")));
+        assert_eq!(parse_var("query=a=b").unwrap(), ("query".into(), json!("a=b")));
+        assert!(parse_var("=noel").is_err());
+        assert!(parse_var("noel").is_err());
+
+        let vars = env_variables(
+            [
+                ("TESTKIT_VAR_token".into(), "weow".into()),
+                ("TESTKIT_VAR_".into(), "ignored".into()),
+                ("HOME".into(), "/home/noel".into()),
+            ],
+            "TESTKIT_VAR_",
+        );
+
+        assert_eq!(vars, HashMap::from([("token".to_owned(), json!("weow"))]));
+    }
+
+    #[test]
+    fn base_urls() {
+        assert!(parse_base_url("http://localhost:3651/api/v1").is_ok());
+        assert!(parse_base_url("https://charts.noelware.org").is_err());
+        assert!(parse_base_url("/api/v1").is_err());
+    }
+
+    #[test]
+    fn directories_are_expanded() {
+        let dir = std::env::temp_dir().join(format!("charted-testkit-cli-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["b.http", "a.http", "README.md"] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+
+        let files = collect_files(&[dir.clone(), "smoke.http".into()]).unwrap();
+        assert_eq!(files, [dir.join("a.http"), dir.join("b.http"), "smoke.http".into()]);
+
+        std::fs::remove_file(dir.join("a.http")).unwrap();
+        std::fs::remove_file(dir.join("b.http")).unwrap();
+        assert!(collect_files(&[dir.clone()]).is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    #[cfg_attr(
+        windows,
+        ignore = "fails on Windows: hyper_util::client::legacy::Error(Connect, ConnectError(\"tcp connect error\", Os { code: 10049, kind: AddrNotAvailable, message: \"The requested address is not valid in its context.\" })))"
+    )]
+    async fn scenarios() {
+        let mut server = TestContext::default();
+        server
+            .serve(
+                Router::new().nest(
+                    "/api",
+                    Router::new()
+                        .route("/health", routing::get(|| async { "ok" }))
+                        .route("/teapot", routing::get(|| async { StatusCode::IM_A_TEAPOT })),
+                ),
+            )
+            .await;
+
+        let dir = std::env::temp_dir().join(format!("charted-testkit-cli-scenarios-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("health.http"),
+            "GET /health\n\nHTTP 200\n[Asserts]\nbody == \"{{expected}}\"\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("teapot.http"), "GET /teapot\n\nHTTP 200\n").unwrap();
+        std::fs::write(dir.join("undefined.http"), "GET /{{missing}}\n\nHTTP 200\n").unwrap();
+
+        let files = collect_files(&[dir.clone()]).unwrap();
+        let variables = HashMap::from([("expected".to_owned(), Value::from("ok"))]);
+        let base_url = format!("http://{}/api", server.server_addr().unwrap());
+
+        let report = run(&base_url, &files, &variables, false).await;
+        assert_eq!(report.passed(), 1);
+        assert_eq!(report.failed(), 1);
+        assert_eq!(report.errored(), 1);
+        assert!(matches!(
+            report.outcomes[1].status,
+            Status::Failed { line: Some(1), .. }
+        ));
+
+        let report = run(&base_url, &files[1..], &variables, true).await;
+        assert_eq!(report.outcomes.len(), 1);
+        assert_eq!(report.exit_code(), 1);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,344 @@
+// 📦🦋 charted TestKit: testing library for Axum services with testcontainers support
+// Copyright (c) 2024 Noelware, LLC. <team@noelware.org>
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Reports of a run, which can be rendered as text, JUnit XML or JSON.
+
+use serde_json::{json, Value};
+use std::{fmt::Write, time::Duration};
+
+/// Outcome of running a single scenario file.
+#[derive(Debug)]
+pub struct Outcome {
+    pub file: String,
+    pub duration: Duration,
+    pub status: Status,
+}
+
+#[derive(Debug)]
+pub enum Status {
+    /// Every request of the scenario matched its expected response.
+    Passed,
+
+    /// A request couldn't be sent or its response didn't match what was expected.
+    Failed { line: Option<usize>, message: String },
+
+    /// The scenario couldn't be read or parsed, or uses a variable that isn't defined.
+    Errored { message: String },
+}
+
+impl Status {
+    fn name(&self) -> &'static str {
+        match self {
+            Status::Passed => "passed",
+            Status::Failed { .. } => "failed",
+            Status::Errored { .. } => "errored",
+        }
+    }
+}
+
+/// Format that a [`Report`] is rendered as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    Text,
+    Junit,
+    Json,
+}
+
+/// All outcomes of a run.
+#[derive(Debug)]
+pub struct Report {
+    pub base_url: String,
+    pub outcomes: Vec<Outcome>,
+}
+
+impl Report {
+    pub fn passed(&self) -> usize {
+        self.count(|status| matches!(status, Status::Passed))
+    }
+
+    pub fn failed(&self) -> usize {
+        self.count(|status| matches!(status, Status::Failed { .. }))
+    }
+
+    pub fn errored(&self) -> usize {
+        self.count(|status| matches!(status, Status::Errored { .. }))
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.outcomes.iter().map(|outcome| outcome.duration).sum()
+    }
+
+    /// Exit code of the run: `0` if every scenario passed, `2` if a scenario couldn't be run at
+    /// all and `1` if a scenario failed.
+    pub fn exit_code(&self) -> u8 {
+        match (self.errored(), self.failed()) {
+            (0, 0) => 0,
+            (0, _) => 1,
+            _ => 2,
+        }
+    }
+
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Text => self.text(),
+            Format::Junit => self.junit(),
+            Format::Json => {
+                let mut json = serde_json::to_string_pretty(&self.json()).expect("report to be serializable");
+                json.push('\n');
+
+                json
+            }
+        }
+    }
+
+    fn count<F: Fn(&Status) -> bool>(&self, f: F) -> usize {
+        self.outcomes.iter().filter(|outcome| f(&outcome.status)).count()
+    }
+
+    fn text(&self) -> String {
+        let mut buf = String::new();
+        for outcome in &self.outcomes {
+            match &outcome.status {
+                Status::Passed => continue,
+                Status::Failed { message, .. } => {
+                    let _ = writeln!(buf, "---- {} failed ----\n{message}\n", outcome.file);
+                }
+
+                Status::Errored { message } => {
+                    let _ = writeln!(buf, "---- {} errored ----\n{message}\n", outcome.file);
+                }
+            }
+        }
+
+        let _ = writeln!(
+            buf,
+            "{} passed; {} failed; {} errored; finished in {:.2}s",
+            self.passed(),
+            self.failed(),
+            self.errored(),
+            self.duration().as_secs_f64()
+        );
+
+        buf
+    }
+
+    fn junit(&self) -> String {
+        let mut buf = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let counts = format!(
+            "tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{:.3}\"",
+            self.outcomes.len(),
+            self.failed(),
+            self.errored(),
+            self.duration().as_secs_f64()
+        );
+
+        let _ = writeln!(buf, "<testsuites name=\"charted-testkit\" {counts}>");
+        let _ = writeln!(buf, "  <testsuite name=\"{}\" {counts}>", escape(&self.base_url));
+
+        for outcome in &self.outcomes {
+            let _ = write!(
+                buf,
+                "    <testcase name=\"{}\" classname=\"charted-testkit\" time=\"{:.3}\"",
+                escape(&outcome.file),
+                outcome.duration.as_secs_f64()
+            );
+
+            let (element, message) = match &outcome.status {
+                Status::Passed => {
+                    buf.push_str("/>\n");
+                    continue;
+                }
+
+                Status::Failed { message, .. } => ("failure", message),
+                Status::Errored { message } => ("error", message),
+            };
+
+            let summary = message.lines().next().unwrap_or_default();
+            let _ = writeln!(
+                buf,
+                ">\n      <{element} message=\"{}\">{}</{element}>\n    </testcase>",
+                escape(summary),
+                escape(message)
+            );
+        }
+
+        buf.push_str("  </testsuite>\n</testsuites>\n");
+        buf
+    }
+
+    fn json(&self) -> Value {
+        let scenarios = self
+            .outcomes
+            .iter()
+            .map(|outcome| {
+                let mut value = json!({
+                    "file": outcome.file,
+                    "status": outcome.status.name(),
+                    "duration_ms": outcome.duration.as_millis() as u64,
+                });
+
+                match &outcome.status {
+                    Status::Passed => {}
+                    Status::Failed { line, message } => {
+                        value["line"] = json!(line);
+                        value["message"] = json!(message);
+                    }
+
+                    Status::Errored { message } => {
+                        value["message"] = json!(message);
+                    }
+                }
+
+                value
+            })
+            .collect::<Vec<_>>();
+
+        json!({
+            "base_url": self.base_url,
+            "passed": self.passed(),
+            "failed": self.failed(),
+            "errored": self.errored(),
+            "duration_ms": self.duration().as_millis() as u64,
+            "scenarios": scenarios,
+        })
+    }
+}
+
+/// Escapes `text` so that it can be used as XML text or in an attribute value. Characters that
+/// aren't allowed in XML 1.0 at all are replaced with `U+FFFD`.
+fn escape(text: &str) -> String {
+    let mut buf = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => buf.push_str("&amp;"),
+            '<' => buf.push_str("&lt;"),
+            '>' => buf.push_str("&gt;"),
+            '"' => buf.push_str("&quot;"),
+            '\'' => buf.push_str("&apos;"),
+            '\t' | '\n' | '\r' => buf.push(ch),
+            ch if ch < ' ' || ch == '\u{fffe}' || ch == '\u{ffff}' => buf.push('\u{fffd}'),
+            ch => buf.push(ch),
+        }
+    }
+
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape, Format, Outcome, Report, Status};
+    use serde_json::json;
+    use std::time::Duration;
+
+    fn report() -> Report {
+        Report {
+            base_url: "http://localhost:3651".into(),
+            outcomes: vec![
+                Outcome {
+                    file: "smoke/health.http".into(),
+                    duration: Duration::from_millis(12),
+                    status: Status::Passed,
+                },
+                Outcome {
+                    file: "smoke/login.http".into(),
+                    duration: Duration::from_millis(30),
+                    status: Status::Failed {
+                        line: Some(4),
+                        message: "request on line 4 failed:\nexpected <200>, got \"401\"".into(),
+                    },
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn exit_codes() {
+        let mut report = report();
+        assert_eq!(report.exit_code(), 1);
+
+        report.outcomes.push(Outcome {
+            file: "smoke/broken.http".into(),
+            duration: Duration::ZERO,
+            status: Status::Errored {
+                message: "line 1: expected a request line".into(),
+            },
+        });
+
+        assert_eq!(report.exit_code(), 2);
+
+        report
+            .outcomes
+            .retain(|outcome| matches!(outcome.status, Status::Passed));
+        assert_eq!(report.exit_code(), 0);
+    }
+
+    #[test]
+    fn junit() {
+        assert_eq!(
+            report().render(Format::Junit),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites name="charted-testkit" tests="2" failures="1" errors="0" time="0.042">
+  <testsuite name="http://localhost:3651" tests="2" failures="1" errors="0" time="0.042">
+    <testcase name="smoke/health.http" classname="charted-testkit" time="0.012"/>
+    <testcase name="smoke/login.http" classname="charted-testkit" time="0.030">
+      <failure message="request on line 4 failed:">request on line 4 failed:
+expected &lt;200&gt;, got &quot;401&quot;</failure>
+    </testcase>
+  </testsuite>
+</testsuites>
+"#
+        );
+    }
+
+    #[test]
+    fn json() {
+        let value = serde_json::from_str::<serde_json::Value>(&report().render(Format::Json)).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "base_url": "http://localhost:3651",
+                "passed": 1,
+                "failed": 1,
+                "errored": 0,
+                "duration_ms": 42,
+                "scenarios": [
+                    { "file": "smoke/health.http", "status": "passed", "duration_ms": 12 },
+                    {
+                        "file": "smoke/login.http",
+                        "status": "failed",
+                        "duration_ms": 30,
+                        "line": 4,
+                        "message": "request on line 4 failed:\nexpected <200>, got \"401\"",
+                    },
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn escaping() {
+        assert_eq!(
+            escape("a < b && 'c' > \"d\""),
+            "a &lt; b &amp;&amp; &apos;c&apos; &gt; &quot;d&quot;"
+        );
+        assert_eq!(escape("binary\u{0}body\n"), "binary\u{fffd}body\n");
+    }
+}
@@ -51,22 +51,27 @@ macro_rules! err {
 
 mod kw {
     syn::custom_keyword!(containers);
+    syn::custom_keyword!(context);
     syn::custom_keyword!(teardown);
     syn::custom_keyword!(router);
     syn::custom_keyword!(setup);
+    syn::custom_keyword!(soft);
 }
 
+#[derive(Clone)]
 pub(crate) enum PathOrExpr {
     Path(Path),
     Callable(ExprCall),
 }
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct Attr {
     pub containers: Vec<PathOrExpr>,
+    pub context: Option<Path>,
     pub teardown: Option<Path>,
     pub router: Option<Path>,
     pub setup: Option<Path>,
+    pub soft: bool,
 }
 
 impl Parse for Attr {
@@ -151,6 +156,39 @@ impl Parse for Attr {
                 me.setup = Some(parse_literal_or_path(input)?);
                 comma_if_not_empty(input)?;
 
+                continue;
+            } else if lookahead.peek(kw::context) {
+                if me.context.is_some() {
+                    return Err(err!(Span::call_site(), "context is already defined"));
+                }
+
+                // context
+                // context = "path_to_context"
+                // context = path_to_context_also
+                input.parse::<kw::context>()?;
+                if !input.peek(Token![=]) {
+                    me.context = Some(PathSegment::from(Ident::new("context", Span::call_site())).into());
+                    comma_if_not_empty(input)?;
+
+                    continue;
+                }
+
+                input.parse::<Token![=]>()?;
+
+                me.context = Some(parse_literal_or_path(input)?);
+                comma_if_not_empty(input)?;
+
+                continue;
+            } else if lookahead.peek(kw::soft) {
+                if me.soft {
+                    return Err(err!(Span::call_site(), "soft is already defined"));
+                }
+
+                // soft
+                input.parse::<kw::soft>()?;
+                me.soft = true;
+                comma_if_not_empty(input)?;
+
                 continue;
             } else if lookahead.peek(kw::router) {
                 if me.router.is_some() {
@@ -168,6 +206,8 @@ impl Parse for Attr {
                     continue;
                 }
 
+                input.parse::<Token![=]>()?;
+
                 me.router = Some(parse_literal_or_path(input)?);
                 comma_if_not_empty(input)?;
 
@@ -67,8 +67,27 @@ pub fn test(body: ItemFn, attrs: Attr) -> TokenStream {
         });),
     });
 
+    let context = match attrs.context {
+        Some(ref path) => quote!(#path()),
+        None => quote!(::charted_testkit::TestContext::default()),
+    };
+
+    let (soft, finish_soft) = match attrs.soft {
+        true => (
+            quote!(let __soft = ::charted_testkit::soft::SoftAssertions::new();),
+            quote!(__soft.finish();),
+        ),
+
+        false => (quote!(), quote!()),
+    };
+
     let serve = match attrs.router {
-        Some(ref router) => quote!(ctx.serve(#router()).await;),
+        Some(ref router) => quote! {
+            match ::charted_testkit::TestContext::external_base_url() {
+                Some(url) => ctx.set_base_url(url),
+                None => ctx.serve(#router()).await,
+            }
+        },
         None => quote!(),
     };
 
@@ -87,16 +106,19 @@ pub fn test(body: ItemFn, attrs: Attr) -> TokenStream {
 
             rt.block_on(async {
                 // Create our TestContext
-                let mut ctx = ::charted_testkit::TestContext::default();
+                let mut ctx = #context;
 
                 #setup
                 #(#containers)*
                 #serve
 
                 let __fn_ptr: fn(_) -> _ = #name;
+
+                #soft
                 let res = __fn_ptr(&ctx).await;
 
                 #teardown
+                #finish_soft
                 res
             })
         }
@@ -21,6 +21,7 @@
 
 mod attr;
 mod expand;
+mod scenarios;
 
 use proc_macro::TokenStream;
 use syn::{parse_macro_input, ItemFn};
@@ -32,6 +33,20 @@ use syn::{parse_macro_input, ItemFn};
 ///   test to set it up
 /// * teardown functions, where a `fn(&TestContext) -> Result<(), Box<dyn ::std::error::Error>>` is called when
 ///   a test is done being executed
+/// * context functions, where a `fn() -> TestContext` is called to construct the [`TestContext`] instead of
+///   using `TestContext::default()`. Builder methods like `cookie_jar`, `artifacts_dir` or `allow_http2` take
+///   the context by value, so they can't be called from a setup function, which only receives a reference
+/// * `soft`, which records every failed TestKit assertion and reports them together when the test ends
+///   instead of failing on the first one
+///
+/// If the `TESTKIT_BASE_URL` environment variable is set, the router isn't served and requests are sent to that URL
+/// instead, so that the same tests can run against a separately started binary:
+///
+/// ```shell
+/// $ TESTKIT_BASE_URL=http://localhost:3651 cargo test
+/// ```
+///
+/// [`TestContext`]: https://docs.rs/charted-testkit/*/charted_testkit/struct.TestContext.html
 #[proc_macro_attribute]
 pub fn test(attrs: TokenStream, item: TokenStream) -> TokenStream {
     let body = parse_macro_input!(item as ItemFn);
@@ -42,3 +57,27 @@ pub fn test(attrs: TokenStream, item: TokenStream) -> TokenStream {
 
     expand::test(body, attrs).into()
 }
+
+/// Generates a test for every [scenario] file that matches a pattern, like `tests/scenarios/*.http`. The pattern
+/// is relative to the crate's `Cargo.toml`, and only its file name can contain `*` and `?` wildcards. Each test is
+/// named after its file (`create-repository.http` becomes `create_repository`) in a module that is named after the
+/// directory (`scenarios`), and accepts the same options as [`macro@test`], which are usually used to set the router
+/// that the scenarios run against:
+///
+/// ```ignore
+/// fn router() -> axum::Router {
+///     // ...
+/// }
+///
+/// charted_testkit::include_scenarios!("tests/scenarios/*.http", router);
+/// ```
+///
+/// Changes to the scenario files rebuild the tests, but files that are added or removed are only picked up once
+/// the file that invokes this macro is rebuilt.
+///
+/// [scenario]: https://docs.rs/charted-testkit/*/charted_testkit/scenario/index.html
+#[proc_macro]
+pub fn include_scenarios(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as scenarios::Input);
+    scenarios::expand(input).into()
+}
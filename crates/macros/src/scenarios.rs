@@ -0,0 +1,169 @@
+// 📦🦋 charted TestKit: testing library for Axum services with testcontainers support
+// Copyright (c) 2024 Noelware, LLC. <team@noelware.org>
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::attr::Attr;
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use std::path::{Path, PathBuf};
+use syn::{
+    parse::{Parse, ParseStream},
+    parse_quote, Ident, ItemFn, LitStr, Result, Token,
+};
+
+pub struct Input {
+    pattern: LitStr,
+    attrs: Attr,
+}
+
+impl Parse for Input {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let pattern = input.parse()?;
+        if !input.is_empty() {
+            input.parse::<Token![,]>()?;
+        }
+
+        Ok(Input {
+            pattern,
+            attrs: input.parse()?,
+        })
+    }
+}
+
+pub fn expand(input: Input) -> TokenStream {
+    let span = input.pattern.span();
+    let files = match find(&input.pattern.value()) {
+        Ok(files) if files.is_empty() => {
+            return syn::Error::new(span, "pattern didn't match any scenario files").into_compile_error()
+        }
+
+        Ok(files) => files,
+        Err(message) => return syn::Error::new(span, message).into_compile_error(),
+    };
+
+    let mut names = Vec::with_capacity(files.len());
+    let mut tests = Vec::with_capacity(files.len());
+    for (path, display) in files {
+        let name = test_name(&path);
+        if names.contains(&name) {
+            return syn::Error::new(
+                span,
+                format!("multiple scenario files would generate a test named `{name}`"),
+            )
+            .into_compile_error();
+        }
+
+        let ident = Ident::new(&name, Span::call_site());
+        let absolute = path.to_string_lossy().into_owned();
+        let item: ItemFn = parse_quote! {
+            async fn #ident(ctx: &::charted_testkit::TestContext) {
+                ::charted_testkit::scenario::__run(::core::include_str!(#absolute), #display, ctx).await;
+            }
+        };
+
+        names.push(name);
+        tests.push(crate::expand::test(item, input.attrs.clone()));
+    }
+
+    // tests are generated in a module that is named after the directory of the scenario files, so
+    // that they don't clash with other items
+    let module = Ident::new(&module_name(&input.pattern.value()), Span::call_site());
+    quote! {
+        mod #module {
+            #[allow(unused_imports)]
+            use super::*;
+
+            #(#tests)*
+        }
+    }
+}
+
+fn module_name(pattern: &str) -> String {
+    let dir = pattern.rsplit_once('/').map(|(dir, _)| dir).unwrap_or_default();
+    match dir.rsplit('/').next().filter(|name| !name.is_empty()) {
+        Some(name) => sanitize(name),
+        None => String::from("scenarios"),
+    }
+}
+
+/// Finds every file that matches `pattern`, relative to the crate that invokes the macro. Only
+/// the file name can contain wildcards (`*` and `?`).
+fn find(pattern: &str) -> std::result::Result<Vec<(PathBuf, String)>, String> {
+    let root = std::env::var_os("CARGO_MANIFEST_DIR")
+        .map(PathBuf::from)
+        .ok_or_else(|| String::from("`CARGO_MANIFEST_DIR` is not set"))?;
+
+    let (dir, file) = pattern.rsplit_once('/').unwrap_or(("", pattern));
+    if dir.contains(['*', '?']) {
+        return Err(String::from("only the file name of the pattern can contain wildcards"));
+    }
+
+    let dir = root.join(dir);
+    let entries = std::fs::read_dir(&dir).map_err(|e| format!("failed to read directory `{}`: {e}", dir.display()))?;
+
+    let mut files = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("failed to read directory `{}`: {e}", dir.display()))?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if entry.path().is_file() && matches(file.as_bytes(), name.as_bytes()) {
+            let display = Path::new(pattern).with_file_name(&name).to_string_lossy().into_owned();
+
+            files.push((entry.path(), display));
+        }
+    }
+
+    // tests are generated in a stable order
+    files.sort();
+    Ok(files)
+}
+
+/// Checks whenever if `name` matches a pattern with `*` and `?` wildcards.
+fn matches(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some(b'*'), _) => matches(&pattern[1..], name) || (!name.is_empty() && matches(pattern, &name[1..])),
+        (Some(b'?'), Some(_)) => matches(&pattern[1..], &name[1..]),
+        (Some(a), Some(b)) if a == b => matches(&pattern[1..], &name[1..]),
+        _ => false,
+    }
+}
+
+/// Turns the stem of a scenario file into the name of its test, i.e. `create-repository.http`
+/// into `create_repository`.
+fn test_name(path: &Path) -> String {
+    sanitize(&path.file_stem().unwrap_or_default().to_string_lossy())
+}
+
+fn sanitize(name: &str) -> String {
+    let mut name = name
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | '0'..='9' | '_' => c,
+            'A'..='Z' => c.to_ascii_lowercase(),
+            _ => '_',
+        })
+        .collect::<String>();
+
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.insert(0, '_');
+    }
+
+    name
+}
@@ -0,0 +1,80 @@
+// 📦🦋 charted TestKit: testing library for Axum services with testcontainers support
+// Copyright (c) 2024 Noelware, LLC. <team@noelware.org>
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Runs tests in external target mode, where `TESTKIT_BASE_URL` points at a server that
+//! was started outside of the test rather than the router being served.
+
+use axum::{body::Bytes, http::Method, routing, Router};
+use charted_testkit::{assert_successful, consume_body, TestContext};
+use charted_testkit_macros::test;
+use std::{net::SocketAddr, sync::OnceLock};
+
+/// The router is never built in external target mode.
+fn router() -> Router {
+    panic!("router shouldn't be served when `TESTKIT_BASE_URL` is set");
+}
+
+/// Starts the "separately started binary" once and points `TESTKIT_BASE_URL` at it before
+/// the test macro decides which mode to use.
+fn context() -> TestContext {
+    static SERVER: OnceLock<SocketAddr> = OnceLock::new();
+
+    let addr = SERVER.get_or_init(|| {
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let rt = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .expect("failed to create Tokio runtime?!");
+
+            rt.block_on(async move {
+                let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+                tx.send(listener.local_addr().unwrap()).unwrap();
+
+                let router = Router::new().route("/api/v1/hello", routing::get(|| async { "Hello from outside!" }));
+                axum::serve(listener, router).await.unwrap();
+            });
+        });
+
+        rx.recv().unwrap()
+    });
+
+    std::env::set_var("TESTKIT_BASE_URL", format!("http://{addr}/api/v1"));
+    TestContext::default()
+}
+
+#[test(context, router)]
+#[cfg_attr(
+    windows,
+    ignore = "fails on Windows: hyper_util::client::legacy::Error(Connect, ConnectError(\"tcp connect error\", Os { code: 10049, kind: AddrNotAvailable, message: \"The requested address is not valid in its context.\" })))"
+)]
+async fn external(ctx: &TestContext) {
+    assert!(ctx.server_addr().is_none());
+    assert_eq!(ctx.base_path().as_deref(), Some("/api/v1"));
+
+    let res = ctx
+        .request("/hello", Method::GET, None::<Bytes>, |_| {})
+        .await
+        .expect("unable to send request");
+
+    assert_successful!(res);
+    assert_eq!(consume_body!(res), Bytes::from_static(b"Hello from outside!"));
+}
@@ -0,0 +1,51 @@
+// 📦🦋 charted TestKit: testing library for Axum services with testcontainers support
+// Copyright (c) 2024 Noelware, LLC. <team@noelware.org>
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use axum::{
+    http::{header, HeaderMap},
+    routing, Router,
+};
+
+async fn hello() -> &'static str {
+    "Hello, world?"
+}
+
+async fn login() -> [(header::HeaderName, &'static str); 1] {
+    [(header::SET_COOKIE, "session=weow")]
+}
+
+async fn me(headers: HeaderMap) -> String {
+    headers
+        .get(header::COOKIE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_owned()
+}
+
+fn router() -> Router {
+    Router::new()
+        .route("/", routing::get(hello))
+        .route("/login", routing::post(login))
+        .route("/me", routing::get(me))
+}
+
+// generates the `scenarios::hello` and `scenarios::login_flow` tests
+charted_testkit_macros::include_scenarios!("tests/scenarios/*.http", router = router);
@@ -26,6 +26,11 @@ fn ui() {
     cases.compile_fail("./tests/ui/invalid_container.rs");
     cases.compile_fail("./tests/ui/invalid_teardown.rs");
     cases.compile_fail("./tests/ui/invalid_setup.rs");
+    cases.compile_fail("./tests/ui/invalid_scenarios.rs");
+    cases.pass("./tests/ui/setup_and_router.rs");
 
-    cases.pass("./tests/ui/container_as_callable.rs");
+    // `containers` can only be expanded if the `testcontainers` feature is enabled
+    if cfg!(feature = "testcontainers") {
+        cases.pass("./tests/ui/container_as_callable.rs");
+    }
 }
@@ -19,12 +19,14 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
+use charted_testkit::TestContext;
+
 async fn mycontainer() -> String {
     String::from("weow")
 }
 
 #[charted_testkit_macros::test(containers = [mycontainer()])]
-fn __testcase(ctx: &TestContext) -> Result<(), ()> {
+async fn __testcase(_ctx: &TestContext) -> Result<(), ()> {
     Ok(())
 }
 
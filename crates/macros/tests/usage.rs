@@ -20,7 +20,7 @@
 // SOFTWARE.
 
 use axum::{body::Bytes, http::Method};
-use charted_testkit::{assert_successful, consume_body, TestContext};
+use charted_testkit::{assert_has_header, assert_status_code, assert_successful, consume_body, TestContext};
 use charted_testkit_macros::test;
 
 async fn setup(_ctx: &TestContext) {
@@ -31,8 +31,18 @@ async fn hello() -> &'static str {
     "Hello, world?"
 }
 
+async fn login() -> [(axum::http::header::HeaderName, &'static str); 1] {
+    [(axum::http::header::SET_COOKIE, "session=weow")]
+}
+
 fn router() -> axum::Router {
-    axum::Router::new().route("/", axum::routing::get(hello))
+    axum::Router::new()
+        .route("/", axum::routing::get(hello))
+        .route("/login", axum::routing::post(login))
+}
+
+fn context() -> TestContext {
+    TestContext::default().cookie_jar(true)
 }
 
 #[test(setup, router)]
@@ -51,3 +61,35 @@ async fn usage(ctx: &TestContext) {
     let body = consume_body!(res);
     assert_eq!(body, Bytes::from_static(b"Hello, world?"));
 }
+
+#[test(context, router)]
+#[cfg_attr(
+    windows,
+    ignore = "fails on Windows: hyper_util::client::legacy::Error(Connect, ConnectError(\"tcp connect error\", Os { code: 10049, kind: AddrNotAvailable, message: \"The requested address is not valid in its context.\" })))"
+)]
+async fn custom_context(ctx: &TestContext) {
+    let res = ctx
+        .request("/login", Method::POST, None::<axum::body::Bytes>, |_| {})
+        .await
+        .expect("unable to send request");
+
+    assert_successful!(res);
+    assert!(ctx.cookies().and_then(|jar| jar.get("session")).is_some());
+}
+
+#[test(soft, router)]
+#[should_panic(expected = "2 soft assertions failed")]
+#[cfg_attr(
+    windows,
+    ignore = "fails on Windows: hyper_util::client::legacy::Error(Connect, ConnectError(\"tcp connect error\", Os { code: 10049, kind: AddrNotAvailable, message: \"The requested address is not valid in its context.\" })))"
+)]
+async fn soft_assertions(ctx: &TestContext) {
+    let res = ctx
+        .request("/", Method::GET, None::<axum::body::Bytes>, |_| {})
+        .await
+        .expect("unable to send request");
+
+    assert_status_code!(res, axum::http::StatusCode::CREATED);
+    assert_has_header!(res, SET_COOKIE);
+    assert_successful!(res);
+}
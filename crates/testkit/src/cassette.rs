@@ -0,0 +1,830 @@
+// 📦🦋 charted TestKit: testing library for Axum services with testcontainers support
+// Copyright (c) 2024 Noelware, LLC. <team@noelware.org>
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Record/replay of outbound HTTP calls with cassette files, so that tests of services that
+//! call third-party APIs are hermetic.
+//!
+//! A [`Cassette`] starts a local proxy that the service under test sends its requests to instead
+//! of the third-party API. In [record mode][Mode::Record], the proxy forwards every request to the
+//! upstream and writes each request and response into the cassette file once it is dropped. In
+//! [replay mode][Mode::Replay], the proxy responds with the recorded responses and the test fails
+//! if a request doesn't match any recorded request.
+//!
+//! The mode is read from the `TESTKIT_CASSETTE_MODE` environment variable (`record`, `replay` or
+//! `auto`), unless it is set with [`Cassette::mode`]. By default, it is [`Mode::Auto`], which
+//! records the cassette if it doesn't exist yet and replays it otherwise, so CI should set
+//! `TESTKIT_CASSETTE_MODE=replay` to never reach the upstream.
+//!
+//! ## Example
+//! ```rust,no_run
+//! # use charted_testkit::cassette::Cassette;
+//! #
+//! # #[tokio::main]
+//! # async fn main() {
+//! let proxy = Cassette::new("tests/cassettes/github.json")
+//!     .upstream("http://localhost:8080")
+//!     .start()
+//!     .await
+//!     .expect("failed to load cassette");
+//!
+//! // point the service under test at `proxy.url()` instead of the upstream...
+//! # }
+//! ```
+
+use crate::TestContext;
+use axum::{
+    body::{Body, Bytes},
+    extract::Request,
+    http::{header, HeaderMap, HeaderName, HeaderValue, Method, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    Router,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use http_body_util::{BodyExt, Full};
+use hyper_util::{
+    client::legacy::{connect::HttpConnector, Client},
+    rt::TokioExecutor,
+};
+use serde_json::{json, Map, Value};
+use std::{
+    fmt::{Debug, Display, Write},
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+pub use crate::exchange::REDACTED;
+
+/// Error type for loading and saving a [`Cassette`].
+#[derive(Debug)]
+pub enum Error {
+    /// The cassette couldn't be read or written.
+    Io(std::io::Error),
+
+    /// The cassette isn't a valid cassette file.
+    Parse(String),
+
+    /// The cassette must be recorded but no upstream was set with [`Cassette::upstream`].
+    MissingUpstream,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "failed to read or write cassette: {err}"),
+            Error::Parse(message) => write!(f, "failed to parse cassette: {message}"),
+            Error::MissingUpstream => f.write_str("cassette must be recorded, but no upstream was set"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Whenever if a [`Cassette`] forwards requests and records them, or replays them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Forwards every request to the upstream and overwrites the cassette with them.
+    Record,
+
+    /// Responds with the recorded responses, and fails on requests that weren't recorded.
+    Replay,
+
+    /// Records the cassette if it doesn't exist, and replays it otherwise.
+    Auto,
+}
+
+impl Mode {
+    /// Reads the mode from the `TESTKIT_CASSETTE_MODE` environment variable, if it is set.
+    ///
+    /// ## Panics
+    /// This will panic if the environment variable isn't a valid mode.
+    pub fn from_env() -> Option<Mode> {
+        let value = std::env::var("TESTKIT_CASSETTE_MODE")
+            .ok()
+            .filter(|value| !value.is_empty())?;
+        Some(
+            value
+                .parse()
+                .unwrap_or_else(|e| panic!("invalid `TESTKIT_CASSETTE_MODE`: {e}")),
+        )
+    }
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "record" => Ok(Mode::Record),
+            "replay" => Ok(Mode::Replay),
+            "auto" => Ok(Mode::Auto),
+            _ => Err(format!("expected `record`, `replay` or `auto`, got `{s}`")),
+        }
+    }
+}
+
+/// A request and the response that the upstream responded with.
+#[derive(Debug, Clone)]
+struct Interaction {
+    method: Method,
+    uri: String,
+    request_headers: HeaderMap,
+    request_body: Bytes,
+    status: StatusCode,
+    response_headers: HeaderMap,
+    response_body: Bytes,
+}
+
+impl Interaction {
+    fn to_json(&self) -> Value {
+        let mut request = json!({
+            "method": self.method.as_str(),
+            "uri": self.uri,
+            "headers": headers_to_json(&self.request_headers),
+        });
+
+        body_to_json(&mut request, &self.request_body);
+
+        let mut response = json!({
+            "status": self.status.as_u16(),
+            "headers": headers_to_json(&self.response_headers),
+        });
+
+        body_to_json(&mut response, &self.response_body);
+        json!({ "request": request, "response": response })
+    }
+
+    fn from_json(value: &Value) -> Result<Interaction, String> {
+        let request = value.get("request").ok_or("missing `request`")?;
+        let response = value.get("response").ok_or("missing `response`")?;
+
+        let method = request
+            .get("method")
+            .and_then(Value::as_str)
+            .ok_or("missing `request.method`")?;
+
+        let status = response
+            .get("status")
+            .and_then(Value::as_u64)
+            .ok_or("missing `response.status`")?;
+
+        Ok(Interaction {
+            method: method.parse().map_err(|_| format!("invalid method `{method}`"))?,
+            uri: request
+                .get("uri")
+                .and_then(Value::as_str)
+                .ok_or("missing `request.uri`")?
+                .to_owned(),
+
+            request_headers: headers_from_json(request.get("headers"))?,
+            request_body: body_from_json(request)?,
+            status: u16::try_from(status)
+                .ok()
+                .and_then(|status| StatusCode::from_u16(status).ok())
+                .ok_or_else(|| format!("invalid status code {status}"))?,
+
+            response_headers: headers_from_json(response.get("headers"))?,
+            response_body: body_from_json(response)?,
+        })
+    }
+
+    /// Whenever if `req` is the same request as this interaction. Only the headers in
+    /// `match_headers` are compared.
+    fn matches(&self, req: &Interaction, match_headers: &[HeaderName]) -> bool {
+        self.method == req.method
+            && self.uri == req.uri
+            && self.request_body == req.request_body
+            && match_headers.iter().all(|name| {
+                self.request_headers
+                    .get_all(name)
+                    .iter()
+                    .eq(req.request_headers.get_all(name).iter())
+            })
+    }
+}
+
+fn headers_to_json(headers: &HeaderMap) -> Value {
+    let mut map = Map::new();
+    for name in headers.keys() {
+        let values = headers
+            .get_all(name)
+            .iter()
+            .map(|value| Value::from(String::from_utf8_lossy(value.as_bytes())))
+            .collect();
+
+        map.insert(name.to_string(), Value::Array(values));
+    }
+
+    Value::Object(map)
+}
+
+fn headers_from_json(value: Option<&Value>) -> Result<HeaderMap, String> {
+    let mut headers = HeaderMap::new();
+    let Some(value) = value else {
+        return Ok(headers);
+    };
+
+    let map = value.as_object().ok_or("headers must be an object")?;
+    for (name, values) in map {
+        let name = HeaderName::from_str(name).map_err(|_| format!("invalid header name `{name}`"))?;
+        for value in values.as_array().ok_or("header values must be an array")? {
+            let value = value
+                .as_str()
+                .and_then(|value| HeaderValue::from_str(value).ok())
+                .ok_or_else(|| format!("invalid value of header `{name}`"))?;
+
+            headers.append(name.clone(), value);
+        }
+    }
+
+    Ok(headers)
+}
+
+fn body_to_json(object: &mut Value, body: &Bytes) {
+    match std::str::from_utf8(body) {
+        Ok(text) => object["body"] = Value::from(text),
+        Err(_) => {
+            object["body"] = Value::from(STANDARD.encode(body));
+            object["encoding"] = Value::from("base64");
+        }
+    }
+}
+
+fn body_from_json(object: &Value) -> Result<Bytes, String> {
+    let body = object.get("body").and_then(Value::as_str).unwrap_or_default();
+    match object.get("encoding").and_then(Value::as_str) {
+        Some("base64") => STANDARD
+            .decode(body)
+            .map(Bytes::from)
+            .map_err(|err| format!("invalid base64 body: {err}")),
+
+        Some(encoding) => Err(format!("unsupported body encoding `{encoding}`")),
+        None => Ok(Bytes::copy_from_slice(body.as_bytes())),
+    }
+}
+
+/// Headers that only apply to a single connection, which aren't forwarded or replayed.
+fn is_hop_by_hop(name: &HeaderName) -> bool {
+    [
+        header::CONNECTION,
+        header::HOST,
+        header::CONTENT_LENGTH,
+        header::TRANSFER_ENCODING,
+        header::TE,
+        header::TRAILER,
+        header::UPGRADE,
+        header::PROXY_AUTHORIZATION,
+    ]
+    .contains(name)
+        || name == "keep-alive"
+}
+
+fn without_hop_by_hop(headers: &HeaderMap) -> HeaderMap {
+    let mut copy = HeaderMap::with_capacity(headers.len());
+    for (name, value) in headers {
+        if !is_hop_by_hop(name) {
+            copy.append(name, value.clone());
+        }
+    }
+
+    copy
+}
+
+/// Builder for a [`CassetteServer`].
+#[derive(Debug, Clone)]
+pub struct Cassette {
+    path: PathBuf,
+    upstream: Option<String>,
+    mode: Option<Mode>,
+    match_headers: Vec<HeaderName>,
+    redact_headers: Vec<HeaderName>,
+}
+
+impl Cassette {
+    /// Creates a [`Cassette`] that is recorded to, or replayed from, the file at `path`.
+    pub fn new<P: Into<PathBuf>>(path: P) -> Cassette {
+        Cassette {
+            path: path.into(),
+            upstream: None,
+            mode: None,
+            match_headers: Vec::new(),
+            redact_headers: vec![header::AUTHORIZATION, header::COOKIE],
+        }
+    }
+
+    /// Sets the base URL (i.e, `http://localhost:8080`) that requests are forwarded to when the
+    /// cassette is recorded.
+    pub fn upstream<U: Into<String>>(mut self, url: U) -> Self {
+        self.upstream = Some(url.into());
+        self
+    }
+
+    /// Sets the mode, which overrides the `TESTKIT_CASSETTE_MODE` environment variable.
+    pub fn mode(mut self, mode: Mode) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    /// Also compares the values of the `name` header when a request is matched against the
+    /// recorded requests, which are otherwise only compared by method, URI and body.
+    ///
+    /// ## Panics
+    /// This will panic if `name` isn't a valid header name.
+    pub fn match_header<K>(mut self, name: K) -> Self
+    where
+        K: TryInto<HeaderName>,
+        K::Error: Debug,
+    {
+        self.match_headers
+            .push(name.try_into().expect("header name to be valid"));
+        self
+    }
+
+    /// Records the `name` request header as [`REDACTED`] so that secrets don't end up in the
+    /// cassette. The `Authorization` and `Cookie` headers are always redacted.
+    ///
+    /// ## Panics
+    /// This will panic if `name` isn't a valid header name.
+    pub fn redact_header<K>(mut self, name: K) -> Self
+    where
+        K: TryInto<HeaderName>,
+        K::Error: Debug,
+    {
+        self.redact_headers
+            .push(name.try_into().expect("header name to be valid"));
+        self
+    }
+
+    /// Loads the cassette (unless it is recorded) and starts the proxy on an ephemeral port.
+    pub async fn start(self) -> Result<CassetteServer, Error> {
+        let mode = match self.mode.or_else(Mode::from_env).unwrap_or(Mode::Auto) {
+            Mode::Auto if self.path.exists() => Mode::Replay,
+            Mode::Auto => Mode::Record,
+            mode => mode,
+        };
+
+        let (interactions, upstream) = match mode {
+            Mode::Replay => (load(&self.path)?, None),
+            _ => {
+                let upstream = self.upstream.ok_or(Error::MissingUpstream)?;
+                (Vec::new(), Some(Upstream::new(upstream)))
+            }
+        };
+
+        let state = Arc::new(State {
+            mode,
+            upstream,
+            match_headers: self.match_headers,
+            redact_headers: self.redact_headers,
+            interactions: Mutex::new(
+                interactions
+                    .into_iter()
+                    .map(|interaction| (interaction, false))
+                    .collect(),
+            ),
+            problems: Mutex::default(),
+        });
+
+        let handler = state.clone();
+        let mut ctx = TestContext::default();
+        ctx.serve(Router::new().fallback(move |req: Request<Body>| {
+            let state = handler.clone();
+            async move { state.handle(req).await }
+        }))
+        .await;
+
+        Ok(CassetteServer {
+            ctx,
+            path: self.path,
+            state,
+        })
+    }
+}
+
+fn load(path: &Path) -> Result<Vec<Interaction>, Error> {
+    let contents = std::fs::read_to_string(path).map_err(Error::Io)?;
+    let value = serde_json::from_str::<Value>(&contents).map_err(|err| Error::Parse(err.to_string()))?;
+
+    value
+        .get("interactions")
+        .and_then(Value::as_array)
+        .ok_or_else(|| Error::Parse("missing `interactions` array".into()))?
+        .iter()
+        .enumerate()
+        .map(|(i, interaction)| {
+            Interaction::from_json(interaction).map_err(|message| Error::Parse(format!("interaction #{i}: {message}")))
+        })
+        .collect()
+}
+
+/// Where requests are forwarded to in record mode. This uses a bare client rather than a
+/// [`TestContext`], so that forwarded requests (and their credentials) never end up in the
+/// test's history.
+struct Upstream {
+    url: String,
+    client: Client<HttpConnector, Full<Bytes>>,
+}
+
+impl Upstream {
+    fn new(url: String) -> Upstream {
+        Upstream {
+            url: url.trim_end_matches('/').to_owned(),
+            client: Client::builder(TokioExecutor::new()).build_http(),
+        }
+    }
+
+    async fn forward(&self, interaction: &Interaction, headers: HeaderMap) -> Result<Response<Bytes>, String> {
+        let uri = format!("{}{}", self.url, interaction.uri)
+            .parse::<Uri>()
+            .map_err(|err| err.to_string())?;
+
+        let mut req = axum::http::Request::new(Full::new(interaction.request_body.clone()));
+        *req.method_mut() = interaction.method.clone();
+        *req.uri_mut() = uri;
+        *req.headers_mut() = headers;
+
+        let (parts, body) = self
+            .client
+            .request(req)
+            .await
+            .map_err(|err| err.to_string())?
+            .into_parts();
+        let body = body
+            .collect()
+            .await
+            .map_err(|err| format!("failed to receive body: {err}"))?;
+
+        Ok(Response::from_parts(parts, body.to_bytes()))
+    }
+}
+
+struct State {
+    mode: Mode,
+    upstream: Option<Upstream>,
+    match_headers: Vec<HeaderName>,
+    redact_headers: Vec<HeaderName>,
+
+    /// Recorded interactions and whenever if they were replayed already.
+    interactions: Mutex<Vec<(Interaction, bool)>>,
+    problems: Mutex<Vec<String>>,
+}
+
+impl State {
+    async fn handle(&self, req: Request) -> Response {
+        let (parts, body) = req.into_parts();
+        let body = match axum::body::to_bytes(body, usize::MAX).await {
+            Ok(body) => body,
+            Err(err) => return (StatusCode::BAD_REQUEST, format!("failed to read body: {err}")).into_response(),
+        };
+
+        let forwarded = without_hop_by_hop(&parts.headers);
+        let mut request_headers = forwarded.clone();
+        for name in &self.redact_headers {
+            if request_headers.contains_key(name) {
+                request_headers.insert(name.clone(), HeaderValue::from_static(REDACTED));
+            }
+        }
+
+        let interaction = Interaction {
+            method: parts.method,
+            uri: parts
+                .uri
+                .path_and_query()
+                .map(|uri| uri.as_str().to_owned())
+                .unwrap_or_else(|| "/".into()),
+
+            request_headers,
+            request_body: body,
+            status: StatusCode::OK,
+            response_headers: HeaderMap::new(),
+            response_body: Bytes::new(),
+        };
+
+        match self.mode {
+            Mode::Replay => self.replay(interaction),
+            _ => self.record(interaction, forwarded).await,
+        }
+    }
+
+    fn replay(&self, req: Interaction) -> Response {
+        let mut interactions = lock(&self.interactions);
+        let Some((interaction, replayed)) = interactions
+            .iter_mut()
+            .find(|(interaction, replayed)| !*replayed && interaction.matches(&req, &self.match_headers))
+        else {
+            let message = format!("no recorded interaction matched `{} {}`", req.method, req.uri);
+            lock(&self.problems).push(message.clone());
+
+            return (StatusCode::BAD_GATEWAY, message).into_response();
+        };
+
+        *replayed = true;
+        respond(interaction)
+    }
+
+    async fn record(&self, mut interaction: Interaction, headers: HeaderMap) -> Response {
+        let upstream = self.upstream.as_ref().expect("upstream to be set in record mode");
+        let res = match upstream.forward(&interaction, headers).await {
+            Ok(res) => res,
+            Err(err) => {
+                let message = format!("failed to forward `{} {}`: {err}", interaction.method, interaction.uri);
+                lock(&self.problems).push(message.clone());
+
+                return (StatusCode::BAD_GATEWAY, message).into_response();
+            }
+        };
+
+        let (parts, body) = res.into_parts();
+        interaction.status = parts.status;
+        interaction.response_headers = without_hop_by_hop(&parts.headers);
+        interaction.response_body = body;
+
+        let res = respond(&interaction);
+        lock(&self.interactions).push((interaction, true));
+
+        res
+    }
+}
+
+fn respond(interaction: &Interaction) -> Response {
+    let mut res = Response::new(Body::from(interaction.response_body.clone()));
+    *res.status_mut() = interaction.status;
+    *res.headers_mut() = interaction.response_headers.clone();
+
+    res
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// A proxy that was started with [`Cassette::start`]. Once it is dropped, the cassette is written
+/// if it was recorded, and the test fails if a request couldn't be replayed or forwarded.
+pub struct CassetteServer {
+    ctx: TestContext,
+    path: PathBuf,
+    state: Arc<State>,
+}
+
+impl Debug for CassetteServer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CassetteServer")
+            .field("addr", &self.addr())
+            .field("path", &self.path)
+            .field("mode", &self.state.mode)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CassetteServer {
+    /// Returns the address that the proxy listens on.
+    pub fn addr(&self) -> SocketAddr {
+        *self.ctx.server_addr().expect("cassette proxy to be served")
+    }
+
+    /// Returns the URL of the proxy (i.e, `http://127.0.0.1:34567`), which the service under test
+    /// should send its requests to instead of the upstream.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr())
+    }
+
+    /// Returns whenever if the cassette is being recorded or replayed; never [`Mode::Auto`].
+    pub fn mode(&self) -> Mode {
+        self.state.mode
+    }
+
+    /// Writes the recorded interactions into the cassette file. This does nothing when the
+    /// cassette is replayed.
+    pub fn save(&self) -> Result<(), Error> {
+        if self.state.mode != Mode::Record {
+            return Ok(());
+        }
+
+        let interactions = lock(&self.state.interactions)
+            .iter()
+            .map(|(interaction, _)| interaction.to_json())
+            .collect::<Vec<_>>();
+
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent).map_err(Error::Io)?;
+        }
+
+        let mut contents = serde_json::to_string_pretty(&json!({ "interactions": interactions }))
+            .expect("cassette to be serializable");
+
+        contents.push('\n');
+        std::fs::write(&self.path, contents).map_err(Error::Io)
+    }
+
+    /// Fails the test if a request couldn't be replayed or forwarded, and resets the problems.
+    #[track_caller]
+    pub fn verify(&self) {
+        let problems = std::mem::take(&mut *lock(&self.state.problems));
+        if problems.is_empty() {
+            return;
+        }
+
+        let mut message = format!("cassette `{}` wasn't used as expected:", self.path.display());
+        for problem in problems {
+            let _ = write!(message, "\n    - {problem}");
+        }
+
+        crate::soft::__fail(message);
+    }
+}
+
+impl Drop for CassetteServer {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            return;
+        }
+
+        if let Err(err) = self.save() {
+            crate::soft::__fail(format!("failed to save cassette `{}`: {err}", self.path.display()));
+        }
+
+        self.verify();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cassette, Mode, REDACTED};
+    use crate::{consume_body, TestContext};
+    use axum::{
+        body::Bytes,
+        http::{header, HeaderMap, Method, StatusCode},
+        routing, Router,
+    };
+    use serde_json::Value;
+    use std::path::PathBuf;
+
+    fn cassette_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("charted-testkit-cassette-{name}-{}.json", std::process::id()))
+    }
+
+    async fn send(
+        url: String,
+        method: Method,
+        uri: &'static str,
+        body: &'static str,
+    ) -> (StatusCode, HeaderMap, Bytes) {
+        let ctx = TestContext::from_base_url(url);
+        let res = ctx
+            .request(uri, method, Bytes::from_static(body.as_bytes()), |req| {
+                req.headers_mut()
+                    .insert(header::AUTHORIZATION, "Bearer ghp_secret".parse().unwrap());
+            })
+            .await
+            .expect("unable to send request");
+
+        let (status, headers) = (res.status(), res.headers().clone());
+        (status, headers, consume_body!(res))
+    }
+
+    #[tokio::test]
+    #[cfg_attr(
+        windows,
+        ignore = "fails on Windows: hyper_util::client::legacy::Error(Connect, ConnectError(\"tcp connect error\", Os { code: 10049, kind: AddrNotAvailable, message: \"The requested address is not valid in its context.\" })))"
+    )]
+    async fn record_then_replay() {
+        let path = cassette_path("roundtrip");
+        let _ = std::fs::remove_file(&path);
+
+        let mut upstream = TestContext::default();
+        upstream
+            .serve(
+                Router::new()
+                    .route(
+                        "/repos/charted-dev/testkit",
+                        routing::get(|| async { ([("x-ratelimit-remaining", "59")], "testkit") }),
+                    )
+                    .route(
+                        "/markdown",
+                        routing::post(|body: String| async move { format!("<p>{body}</p>") }),
+                    ),
+            )
+            .await;
+
+        {
+            let proxy = Cassette::new(&path)
+                .upstream(format!("http://{}", upstream.server_addr().unwrap()))
+                .mode(Mode::Auto)
+                .start()
+                .await
+                .expect("failed to start cassette");
+
+            assert_eq!(proxy.mode(), Mode::Record);
+
+            let (status, headers, body) = send(proxy.url(), Method::GET, "/repos/charted-dev/testkit", "").await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(headers.get("x-ratelimit-remaining").unwrap(), "59");
+            assert_eq!(body, Bytes::from_static(b"testkit"));
+
+            let (_, _, body) = send(proxy.url(), Method::POST, "/markdown", "hi").await;
+            assert_eq!(body, Bytes::from_static(b"<p>hi</p>"));
+        }
+
+        let cassette = serde_json::from_str::<Value>(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        let interactions = cassette["interactions"].as_array().unwrap();
+        assert_eq!(interactions.len(), 2);
+        assert_eq!(interactions[0]["request"]["headers"]["authorization"][0], REDACTED);
+        assert_eq!(interactions[1]["request"]["body"], "hi");
+        assert_eq!(interactions[1]["response"]["body"], "<p>hi</p>");
+
+        // the upstream isn't needed anymore once the cassette was recorded
+        drop(upstream);
+
+        let proxy = Cassette::new(&path)
+            .mode(Mode::Auto)
+            .start()
+            .await
+            .expect("failed to start cassette");
+
+        assert_eq!(proxy.mode(), Mode::Replay);
+
+        let (_, _, body) = send(proxy.url(), Method::POST, "/markdown", "hi").await;
+        assert_eq!(body, Bytes::from_static(b"<p>hi</p>"));
+
+        let (status, headers, body) = send(proxy.url(), Method::GET, "/repos/charted-dev/testkit", "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers.get("x-ratelimit-remaining").unwrap(), "59");
+        assert_eq!(body, Bytes::from_static(b"testkit"));
+
+        drop(proxy);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    #[cfg_attr(
+        windows,
+        ignore = "fails on Windows: hyper_util::client::legacy::Error(Connect, ConnectError(\"tcp connect error\", Os { code: 10049, kind: AddrNotAvailable, message: \"The requested address is not valid in its context.\" })))"
+    )]
+    #[should_panic(expected = "wasn't used as expected:\n    - no recorded interaction matched `POST /markdown`")]
+    async fn replay_fails_on_unmatched_requests() {
+        let path = cassette_path("unmatched");
+        std::fs::write(
+            &path,
+            r#"{ "interactions": [{
+                "request": { "method": "POST", "uri": "/markdown", "headers": {}, "body": "hi" },
+                "response": { "status": 200, "headers": {}, "body": "<p>hi</p>" }
+            }] }"#,
+        )
+        .unwrap();
+
+        let proxy = Cassette::new(&path)
+            .mode(Mode::Replay)
+            .start()
+            .await
+            .expect("failed to start cassette");
+
+        std::fs::remove_file(&path).unwrap();
+
+        let (status, _, _) = send(proxy.url(), Method::POST, "/markdown", "hello").await;
+        assert_eq!(status, StatusCode::BAD_GATEWAY);
+    }
+
+    #[tokio::test]
+    async fn recording_requires_an_upstream() {
+        let err = Cassette::new(cassette_path("missing-upstream"))
+            .mode(Mode::Record)
+            .start()
+            .await
+            .expect_err("recording without an upstream");
+
+        assert_eq!(err.to_string(), "cassette must be recorded, but no upstream was set");
+    }
+
+    #[test]
+    fn modes() {
+        assert_eq!("replay".parse::<Mode>(), Ok(Mode::Replay));
+        assert_eq!("RECORD".parse::<Mode>(), Ok(Mode::Record));
+        assert!("once".parse::<Mode>().is_err());
+    }
+}
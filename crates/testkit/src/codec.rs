@@ -0,0 +1,626 @@
+// 📦🦋 charted TestKit: testing library for Axum services with testcontainers support
+// Copyright (c) 2024 Noelware, LLC. <team@noelware.org>
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Request encoders and response decoders for the body formats that TestKit supports.
+//!
+//! Each format is gated behind its own crate feature:
+//!
+//! | Format        | Feature    | `Content-Type`           |
+//! | :------------ | :--------- | :----------------------- |
+//! | JSON          | `json`     | `application/json`       |
+//! | MessagePack   | `msgpack`  | `application/msgpack`    |
+//! | CBOR          | `cbor`     | `application/cbor`       |
+//! | XML           | `xml`      | `application/xml`        |
+//! | Protobuf      | `protobuf` | `application/protobuf`   |
+//!
+//! Encoding a request body will set the `Content-Type` header, and decoding a response body will
+//! validate that the response's `Content-Type` header matches the format before decoding it.
+
+use axum::{
+    body::Bytes,
+    http::{header, HeaderMap, Request, Response},
+};
+use http_body_util::{BodyExt, Full};
+use hyper::body::Body as HttpBody;
+use std::{fmt::Display, future::Future};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Error type for encoding and decoding bodies.
+#[derive(Debug)]
+pub enum Error {
+    /// Value couldn't be encoded into the given content type.
+    Encode {
+        content_type: &'static str,
+        message: String,
+    },
+
+    /// Response didn't have a `Content-Type` header.
+    MissingContentType { expected: &'static str, body: Bytes },
+
+    /// Response had a `Content-Type` header that the codec doesn't know how to decode.
+    ContentTypeMismatch {
+        expected: &'static str,
+        actual: String,
+        body: Bytes,
+    },
+
+    /// The response body couldn't be consumed.
+    Body(BoxError),
+
+    /// The response body couldn't be decoded.
+    Decode {
+        content_type: &'static str,
+        message: String,
+        body: Bytes,
+    },
+}
+
+impl Error {
+    /// Returns the raw body that couldn't be decoded, if there was one.
+    pub fn body(&self) -> Option<&Bytes> {
+        match self {
+            Error::MissingContentType { body, .. }
+            | Error::ContentTypeMismatch { body, .. }
+            | Error::Decode { body, .. } => Some(body),
+
+            _ => None,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Encode { content_type, message } => {
+                write!(f, "failed to encode body as `{content_type}`: {message}")
+            }
+
+            Error::MissingContentType { expected, body } => write!(
+                f,
+                "expected `Content-Type` to be `{expected}`, but response had no `Content-Type` header\n\nraw body:\n{}",
+                crate::util::display_body(body)
+            ),
+
+            Error::ContentTypeMismatch { expected, actual, body } => write!(
+                f,
+                "expected `Content-Type` to be `{expected}`, received `{actual}` instead\n\nraw body:\n{}",
+                crate::util::display_body(body)
+            ),
+
+            Error::Body(err) => write!(f, "failed to consume response body: {err}"),
+            Error::Decode {
+                content_type,
+                message,
+                body,
+            } => write!(
+                f,
+                "failed to decode body as `{content_type}`: {message}\n\nraw body:\n{}",
+                crate::util::display_body(body)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Body(err) => Some(&**err),
+            _ => None,
+        }
+    }
+}
+
+fn validate_content_type(
+    headers: &HeaderMap,
+    expected: &'static str,
+    accepts: fn(&str) -> bool,
+    body: &Bytes,
+) -> Result<(), Error> {
+    match crate::util::content_type_essence(headers) {
+        Some(essence) if accepts(&essence) => Ok(()),
+        Some(_) => Err(Error::ContentTypeMismatch {
+            expected,
+            actual: headers
+                .get(header::CONTENT_TYPE)
+                .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+                .unwrap_or_default(),
+            body: body.clone(),
+        }),
+
+        None => Err(Error::MissingContentType {
+            expected,
+            body: body.clone(),
+        }),
+    }
+}
+
+/// Represents a body format that can encode and decode [`serde`] types.
+#[cfg(feature = "serde")]
+pub trait Codec {
+    /// `Content-Type` that is sent when a request body is encoded with this codec.
+    const CONTENT_TYPE: &'static str;
+
+    /// Checks whenever if the essence of a response's `Content-Type` header (i.e, `application/json`)
+    /// can be decoded with this codec.
+    fn accepts(essence: &str) -> bool {
+        essence == Self::CONTENT_TYPE
+    }
+
+    /// Encodes `value` into a body.
+    fn encode<T: serde::Serialize + ?Sized>(value: &T) -> Result<Bytes, Error>;
+
+    /// Decodes a raw body into `T`.
+    fn decode<T: serde::de::DeserializeOwned>(body: &[u8]) -> Result<T, Error>;
+}
+
+/// [`Codec`] for JSON bodies, `application/*+json` are also accepted.
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, Copy)]
+pub struct Json;
+
+#[cfg(feature = "json")]
+impl Codec for Json {
+    const CONTENT_TYPE: &'static str = "application/json";
+
+    fn accepts(essence: &str) -> bool {
+        essence == Self::CONTENT_TYPE || (essence.starts_with("application/") && essence.ends_with("+json"))
+    }
+
+    fn encode<T: serde::Serialize + ?Sized>(value: &T) -> Result<Bytes, Error> {
+        serde_json::to_vec(value).map(Bytes::from).map_err(|e| Error::Encode {
+            content_type: Self::CONTENT_TYPE,
+            message: e.to_string(),
+        })
+    }
+
+    fn decode<T: serde::de::DeserializeOwned>(body: &[u8]) -> Result<T, Error> {
+        serde_json::from_slice(body).map_err(|e| Error::Decode {
+            content_type: Self::CONTENT_TYPE,
+            message: e.to_string(),
+            body: Bytes::copy_from_slice(body),
+        })
+    }
+}
+
+/// [`Codec`] for MessagePack bodies. Structs are encoded as maps rather than arrays.
+#[cfg(feature = "msgpack")]
+#[derive(Debug, Clone, Copy)]
+pub struct MessagePack;
+
+#[cfg(feature = "msgpack")]
+impl Codec for MessagePack {
+    const CONTENT_TYPE: &'static str = "application/msgpack";
+
+    fn accepts(essence: &str) -> bool {
+        matches!(
+            essence,
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack"
+        )
+    }
+
+    fn encode<T: serde::Serialize + ?Sized>(value: &T) -> Result<Bytes, Error> {
+        rmp_serde::to_vec_named(value)
+            .map(Bytes::from)
+            .map_err(|e| Error::Encode {
+                content_type: Self::CONTENT_TYPE,
+                message: e.to_string(),
+            })
+    }
+
+    fn decode<T: serde::de::DeserializeOwned>(body: &[u8]) -> Result<T, Error> {
+        rmp_serde::from_slice(body).map_err(|e| Error::Decode {
+            content_type: Self::CONTENT_TYPE,
+            message: e.to_string(),
+            body: Bytes::copy_from_slice(body),
+        })
+    }
+}
+
+/// [`Codec`] for CBOR bodies.
+#[cfg(feature = "cbor")]
+#[derive(Debug, Clone, Copy)]
+pub struct Cbor;
+
+#[cfg(feature = "cbor")]
+impl Codec for Cbor {
+    const CONTENT_TYPE: &'static str = "application/cbor";
+
+    fn encode<T: serde::Serialize + ?Sized>(value: &T) -> Result<Bytes, Error> {
+        let mut buf = Vec::new();
+        ciborium::into_writer(value, &mut buf).map_err(|e| Error::Encode {
+            content_type: Self::CONTENT_TYPE,
+            message: e.to_string(),
+        })?;
+
+        Ok(Bytes::from(buf))
+    }
+
+    fn decode<T: serde::de::DeserializeOwned>(body: &[u8]) -> Result<T, Error> {
+        ciborium::from_reader(body).map_err(|e| Error::Decode {
+            content_type: Self::CONTENT_TYPE,
+            message: e.to_string(),
+            body: Bytes::copy_from_slice(body),
+        })
+    }
+}
+
+/// [`Codec`] for XML bodies, `text/xml` and `application/*+xml` are also accepted.
+#[cfg(feature = "xml")]
+#[derive(Debug, Clone, Copy)]
+pub struct Xml;
+
+#[cfg(feature = "xml")]
+impl Codec for Xml {
+    const CONTENT_TYPE: &'static str = "application/xml";
+
+    fn accepts(essence: &str) -> bool {
+        essence == Self::CONTENT_TYPE
+            || essence == "text/xml"
+            || (essence.starts_with("application/") && essence.ends_with("+xml"))
+    }
+
+    fn encode<T: serde::Serialize + ?Sized>(value: &T) -> Result<Bytes, Error> {
+        quick_xml::se::to_string(value)
+            .map(Bytes::from)
+            .map_err(|e| Error::Encode {
+                content_type: Self::CONTENT_TYPE,
+                message: e.to_string(),
+            })
+    }
+
+    fn decode<T: serde::de::DeserializeOwned>(body: &[u8]) -> Result<T, Error> {
+        let decode_error = |message: String| Error::Decode {
+            content_type: Self::CONTENT_TYPE,
+            message,
+            body: Bytes::copy_from_slice(body),
+        };
+
+        let text = std::str::from_utf8(body).map_err(|e| decode_error(e.to_string()))?;
+        quick_xml::de::from_str(text).map_err(|e| decode_error(e.to_string()))
+    }
+}
+
+/// Protobuf bodies, which are encoded and decoded with [`prost`] rather than [`serde`].
+#[cfg(feature = "protobuf")]
+#[derive(Debug, Clone, Copy)]
+pub struct Protobuf;
+
+#[cfg(feature = "protobuf")]
+impl Protobuf {
+    /// `Content-Type` that is sent when a request body is encoded as Protobuf.
+    pub const CONTENT_TYPE: &'static str = "application/protobuf";
+
+    /// Checks whenever if the essence of a response's `Content-Type` header is a Protobuf body.
+    pub fn accepts(essence: &str) -> bool {
+        matches!(
+            essence,
+            "application/protobuf" | "application/x-protobuf" | "application/vnd.google.protobuf"
+        )
+    }
+
+    /// Encodes a [`prost::Message`] into a body.
+    pub fn encode<M: prost::Message>(message: &M) -> Bytes {
+        Bytes::from(message.encode_to_vec())
+    }
+
+    /// Decodes a raw body into a [`prost::Message`].
+    pub fn decode<M: prost::Message + Default>(body: &[u8]) -> Result<M, Error> {
+        M::decode(body).map_err(|e| Error::Decode {
+            content_type: Self::CONTENT_TYPE,
+            message: e.to_string(),
+            body: Bytes::copy_from_slice(body),
+        })
+    }
+}
+
+/// Extension trait for [requests][Request] to encode their body with a codec, which can be
+/// used in the `build` function of [`TestContext::request`][crate::TestContext::request].
+///
+/// All methods will set the `Content-Type` header and panic if the value couldn't be encoded.
+///
+/// ## Example
+/// ```rust
+/// # use axum::{body::Bytes, http::{Request, header}};
+/// # use http_body_util::Full;
+/// use charted_testkit::codec::RequestExt;
+///
+/// let mut req = Request::new(Full::<Bytes>::default());
+/// req.json(&serde_json::json!({"hello": "world"}));
+///
+/// assert_eq!(req.headers().get(header::CONTENT_TYPE).unwrap(), "application/json");
+/// ```
+pub trait RequestExt {
+    /// Encodes `value` with the codec `C` as the request body.
+    #[cfg(feature = "serde")]
+    fn encode_body<C: Codec, T: serde::Serialize + ?Sized>(&mut self, value: &T);
+
+    /// Encodes `value` as a JSON request body.
+    #[cfg(feature = "json")]
+    fn json<T: serde::Serialize + ?Sized>(&mut self, value: &T) {
+        self.encode_body::<Json, T>(value);
+    }
+
+    /// Encodes `value` as a MessagePack request body.
+    #[cfg(feature = "msgpack")]
+    fn msgpack<T: serde::Serialize + ?Sized>(&mut self, value: &T) {
+        self.encode_body::<MessagePack, T>(value);
+    }
+
+    /// Encodes `value` as a CBOR request body.
+    #[cfg(feature = "cbor")]
+    fn cbor<T: serde::Serialize + ?Sized>(&mut self, value: &T) {
+        self.encode_body::<Cbor, T>(value);
+    }
+
+    /// Encodes `value` as a XML request body.
+    #[cfg(feature = "xml")]
+    fn xml<T: serde::Serialize + ?Sized>(&mut self, value: &T) {
+        self.encode_body::<Xml, T>(value);
+    }
+
+    /// Encodes a [`prost::Message`] as a Protobuf request body.
+    #[cfg(feature = "protobuf")]
+    fn protobuf<M: prost::Message>(&mut self, message: &M);
+}
+
+impl RequestExt for Request<Full<Bytes>> {
+    #[cfg(feature = "serde")]
+    fn encode_body<C: Codec, T: serde::Serialize + ?Sized>(&mut self, value: &T) {
+        let body = C::encode(value).unwrap_or_else(|e| panic!("{e}"));
+        set_body(self, C::CONTENT_TYPE, body);
+    }
+
+    #[cfg(feature = "protobuf")]
+    fn protobuf<M: prost::Message>(&mut self, message: &M) {
+        set_body(self, Protobuf::CONTENT_TYPE, Protobuf::encode(message));
+    }
+}
+
+fn set_body(req: &mut Request<Full<Bytes>>, content_type: &'static str, body: Bytes) {
+    req.headers_mut()
+        .insert(header::CONTENT_TYPE, header::HeaderValue::from_static(content_type));
+
+    *req.body_mut() = Full::new(body);
+}
+
+/// Extension trait for [responses][Response] to consume and decode their body with a codec. The
+/// response's `Content-Type` header is validated before the body is decoded.
+///
+/// ## Example
+/// ```rust
+/// # use axum::http::{Response, header};
+/// use charted_testkit::codec::ResponseExt;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let res = Response::builder()
+///     .header(header::CONTENT_TYPE, "application/json")
+///     .body(String::from(r#"{"hello":"world"}"#))
+///     .unwrap();
+///
+/// let body: serde_json::Value = res.json().await.expect("to decode as JSON");
+/// assert_eq!(body, serde_json::json!({"hello": "world"}));
+/// # }
+/// ```
+pub trait ResponseExt {
+    /// Consumes the response body and decodes it with the codec `C`.
+    #[cfg(feature = "serde")]
+    fn decode<C: Codec, T: serde::de::DeserializeOwned>(self) -> impl Future<Output = Result<T, Error>> + Send;
+
+    /// Consumes the response body and decodes it as JSON.
+    #[cfg(feature = "json")]
+    fn json<T: serde::de::DeserializeOwned>(self) -> impl Future<Output = Result<T, Error>> + Send
+    where
+        Self: Sized,
+    {
+        self.decode::<Json, T>()
+    }
+
+    /// Consumes the response body and decodes it as MessagePack.
+    #[cfg(feature = "msgpack")]
+    fn msgpack<T: serde::de::DeserializeOwned>(self) -> impl Future<Output = Result<T, Error>> + Send
+    where
+        Self: Sized,
+    {
+        self.decode::<MessagePack, T>()
+    }
+
+    /// Consumes the response body and decodes it as CBOR.
+    #[cfg(feature = "cbor")]
+    fn cbor<T: serde::de::DeserializeOwned>(self) -> impl Future<Output = Result<T, Error>> + Send
+    where
+        Self: Sized,
+    {
+        self.decode::<Cbor, T>()
+    }
+
+    /// Consumes the response body and decodes it as XML.
+    #[cfg(feature = "xml")]
+    fn xml<T: serde::de::DeserializeOwned>(self) -> impl Future<Output = Result<T, Error>> + Send
+    where
+        Self: Sized,
+    {
+        self.decode::<Xml, T>()
+    }
+
+    /// Consumes the response body and decodes it as a Protobuf message.
+    #[cfg(feature = "protobuf")]
+    fn protobuf<M: prost::Message + Default>(self) -> impl Future<Output = Result<M, Error>> + Send;
+}
+
+impl<B> ResponseExt for Response<B>
+where
+    B: HttpBody + Send,
+    B::Data: Send,
+    B::Error: Into<BoxError>,
+{
+    #[cfg(feature = "serde")]
+    async fn decode<C: Codec, T: serde::de::DeserializeOwned>(self) -> Result<T, Error> {
+        let (headers, body) = collect(self).await?;
+        validate_content_type(&headers, C::CONTENT_TYPE, C::accepts, &body)?;
+
+        C::decode(&body)
+    }
+
+    #[cfg(feature = "protobuf")]
+    async fn protobuf<M: prost::Message + Default>(self) -> Result<M, Error> {
+        let (headers, body) = collect(self).await?;
+        validate_content_type(&headers, Protobuf::CONTENT_TYPE, Protobuf::accepts, &body)?;
+
+        Protobuf::decode(&body)
+    }
+}
+
+#[allow(dead_code)] // only unused if no codec features are enabled
+async fn collect<B>(res: Response<B>) -> Result<(HeaderMap, Bytes), Error>
+where
+    B: HttpBody,
+    B::Error: Into<BoxError>,
+{
+    let (parts, body) = res.into_parts();
+    let body = body.collect().await.map_err(|e| Error::Body(e.into()))?.to_bytes();
+
+    Ok((parts.headers, body))
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "json")]
+    use super::Error;
+    use super::{RequestExt, ResponseExt};
+    use axum::{
+        body::Bytes,
+        http::{header, Request, Response},
+    };
+    use http_body_util::Full;
+
+    #[cfg(feature = "serde")]
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Repository {
+        name: String,
+        stars: u64,
+    }
+
+    #[cfg(feature = "serde")]
+    fn repository() -> Repository {
+        Repository {
+            name: String::from("charted"),
+            stars: 42,
+        }
+    }
+
+    #[cfg(feature = "json")]
+    #[tokio::test]
+    async fn json_roundtrip() {
+        let mut req = Request::new(Full::<Bytes>::default());
+        req.json(&repository());
+
+        let content_type = req.headers().get(header::CONTENT_TYPE).cloned().unwrap();
+        assert_eq!(content_type, "application/json");
+
+        let res = Response::builder()
+            .header(header::CONTENT_TYPE, "application/json; charset=utf-8")
+            .body(req.into_body())
+            .unwrap();
+
+        assert_eq!(res.json::<Repository>().await.unwrap(), repository());
+    }
+
+    #[cfg(feature = "json")]
+    #[tokio::test]
+    async fn json_content_type_mismatch() {
+        let res = Response::builder()
+            .header(header::CONTENT_TYPE, "text/plain")
+            .body(String::from("Hello, world!"))
+            .unwrap();
+
+        let err = res.json::<Repository>().await.unwrap_err();
+        assert!(matches!(err, Error::ContentTypeMismatch { .. }));
+        assert!(err.to_string().contains("Hello, world!"));
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[tokio::test]
+    async fn msgpack_roundtrip() {
+        let mut req = Request::new(Full::<Bytes>::default());
+        req.msgpack(&repository());
+
+        let res = Response::builder()
+            .header(header::CONTENT_TYPE, "application/x-msgpack")
+            .body(req.into_body())
+            .unwrap();
+
+        assert_eq!(res.msgpack::<Repository>().await.unwrap(), repository());
+    }
+
+    #[cfg(feature = "cbor")]
+    #[tokio::test]
+    async fn cbor_roundtrip() {
+        let mut req = Request::new(Full::<Bytes>::default());
+        req.cbor(&repository());
+
+        let res = Response::builder()
+            .header(header::CONTENT_TYPE, "application/cbor")
+            .body(req.into_body())
+            .unwrap();
+
+        assert_eq!(res.cbor::<Repository>().await.unwrap(), repository());
+    }
+
+    #[cfg(feature = "xml")]
+    #[tokio::test]
+    async fn xml_roundtrip() {
+        let mut req = Request::new(Full::<Bytes>::default());
+        req.xml(&repository());
+
+        let res = Response::builder()
+            .header(header::CONTENT_TYPE, "text/xml")
+            .body(req.into_body())
+            .unwrap();
+
+        assert_eq!(res.xml::<Repository>().await.unwrap(), repository());
+    }
+
+    #[cfg(feature = "protobuf")]
+    #[tokio::test]
+    async fn protobuf_roundtrip() {
+        #[derive(Clone, PartialEq, prost::Message)]
+        struct Ping {
+            #[prost(string, tag = "1")]
+            message: String,
+        }
+
+        let ping = Ping {
+            message: String::from("pong"),
+        };
+
+        let mut req = Request::new(Full::<Bytes>::default());
+        req.protobuf(&ping);
+
+        let res = Response::builder()
+            .header(header::CONTENT_TYPE, "application/x-protobuf")
+            .body(req.into_body())
+            .unwrap();
+
+        assert_eq!(res.protobuf::<Ping>().await.unwrap(), ping);
+    }
+}
@@ -0,0 +1,207 @@
+// 📦🦋 charted TestKit: testing library for Axum services with testcontainers support
+// Copyright (c) 2024 Noelware, LLC. <team@noelware.org>
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Compression negotiation and transparent response decompression.
+//!
+//! [`TestContext::set_accept_encoding`][crate::TestContext::set_accept_encoding] sends the
+//! `Accept-Encoding` header on every request, and [`TestContext::set_auto_decompress`][crate::TestContext::set_auto_decompress]
+//! decompresses response bodies before they are returned. Every response will have a [`WireEncoding`]
+//! extension with the encodings that the body was sent with, so compression can still be asserted
+//! with [`assert_content_encoding!`][crate::assert_content_encoding] and [`assert_not_compressed!`][crate::assert_not_compressed]
+//! after it was decompressed.
+//!
+//! Each encoding can only be decompressed if its crate feature is enabled:
+//!
+//! | Encoding  | Feature                 |
+//! | :-------- | :---------------------- |
+//! | `gzip`    | `decompression-gzip`    |
+//! | `deflate` | `decompression-deflate` |
+//! | `br`      | `decompression-br`      |
+//! | `zstd`    | `decompression-zstd`    |
+
+use crate::Error;
+use axum::{
+    body::{Body, Bytes},
+    http::{header, HeaderMap, Response},
+};
+use http_body_util::BodyExt;
+use hyper::body::Incoming;
+
+/// The `Content-Encoding` that a response body was sent with over the wire.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WireEncoding {
+    encodings: Vec<String>,
+    decompressed: bool,
+}
+
+impl WireEncoding {
+    /// Returns the wire encoding of a response. If the response doesn't have a [`WireEncoding`]
+    /// extension, then it is read from the `Content-Encoding` header.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use axum::http::{Response, header};
+    /// use charted_testkit::compression::WireEncoding;
+    ///
+    /// let res = Response::builder().header(header::CONTENT_ENCODING, "br").body(()).unwrap();
+    /// assert_eq!(WireEncoding::of(&res).encodings(), ["br"]);
+    /// ```
+    pub fn of<B>(res: &Response<B>) -> WireEncoding {
+        match res.extensions().get::<WireEncoding>() {
+            Some(encoding) => encoding.clone(),
+            None => WireEncoding {
+                encodings: parse_encodings(res.headers()),
+                decompressed: false,
+            },
+        }
+    }
+
+    /// Returns every encoding that was applied to the body, in the order that they were applied.
+    pub fn encodings(&self) -> &[String] {
+        &self.encodings
+    }
+
+    /// Checks whenever if the body was compressed with `encoding`.
+    pub fn is(&self, encoding: &str) -> bool {
+        self.encodings.iter().any(|e| e.eq_ignore_ascii_case(encoding))
+    }
+
+    /// Checks whenever if the body was compressed at all.
+    pub fn is_compressed(&self) -> bool {
+        self.encodings.iter().any(|e| !e.eq_ignore_ascii_case("identity"))
+    }
+
+    /// Checks whenever if the body was decompressed by TestKit.
+    pub fn was_decompressed(&self) -> bool {
+        self.decompressed
+    }
+}
+
+fn parse_encodings(headers: &HeaderMap) -> Vec<String> {
+    headers
+        .get_all(header::CONTENT_ENCODING)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|value| value.trim().to_ascii_lowercase())
+        .filter(|value| !value.is_empty())
+        .collect()
+}
+
+/// Converts a response from the client into a response with a [`Body`], decompressing
+/// the body if `decompress` is true and recording the [`WireEncoding`].
+pub(crate) async fn finish(res: Response<Incoming>, decompress: bool) -> Result<Response<Body>, Error> {
+    let encodings = parse_encodings(res.headers());
+    let should_decompress = decompress
+        && !encodings.is_empty()
+        && encodings
+            .iter()
+            .all(|encoding| encoding == "identity" || is_supported(encoding));
+
+    if !should_decompress {
+        let mut res = res.map(Body::new);
+        res.extensions_mut().insert(WireEncoding {
+            encodings,
+            decompressed: false,
+        });
+
+        return Ok(res);
+    }
+
+    let (mut parts, body) = res.into_parts();
+    let mut body = body
+        .collect()
+        .await
+        .map_err(|e| Error::Body(axum::Error::new(e)))?
+        .to_bytes();
+
+    // encodings are listed in the order that they were applied
+    for encoding in encodings.iter().rev() {
+        body = decode(encoding, body)?;
+    }
+
+    parts.headers.remove(header::CONTENT_ENCODING);
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.extensions.insert(WireEncoding {
+        encodings,
+        decompressed: true,
+    });
+
+    Ok(Response::from_parts(parts, Body::from(body)))
+}
+
+fn is_supported(encoding: &str) -> bool {
+    (cfg!(feature = "decompression-gzip") && matches!(encoding, "gzip" | "x-gzip"))
+        || (cfg!(feature = "decompression-deflate") && encoding == "deflate")
+        || (cfg!(feature = "decompression-br") && encoding == "br")
+        || (cfg!(feature = "decompression-zstd") && encoding == "zstd")
+}
+
+#[cfg(not(feature = "__decompression"))]
+fn decode(_: &str, body: Bytes) -> Result<Bytes, Error> {
+    Ok(body)
+}
+
+#[cfg(feature = "__decompression")]
+fn decode(encoding: &str, body: Bytes) -> Result<Bytes, Error> {
+    use std::io::Read;
+
+    let error = |e: std::io::Error| Error::Decompression {
+        encoding: encoding.to_owned(),
+        message: e.to_string(),
+    };
+
+    let mut buf = Vec::new();
+    match encoding {
+        #[cfg(feature = "decompression-gzip")]
+        "gzip" | "x-gzip" => {
+            flate2::read::MultiGzDecoder::new(&body[..])
+                .read_to_end(&mut buf)
+                .map_err(error)?;
+        }
+
+        // `deflate` in HTTP is the zlib format rather than a raw deflate stream
+        #[cfg(feature = "decompression-deflate")]
+        "deflate" => {
+            flate2::read::ZlibDecoder::new(&body[..])
+                .read_to_end(&mut buf)
+                .map_err(error)?;
+        }
+
+        #[cfg(feature = "decompression-br")]
+        "br" => {
+            brotli_decompressor::Decompressor::new(&body[..], 4096)
+                .read_to_end(&mut buf)
+                .map_err(error)?;
+        }
+
+        #[cfg(feature = "decompression-zstd")]
+        "zstd" => {
+            zstd::stream::read::Decoder::new(&body[..])
+                .and_then(|mut decoder| decoder.read_to_end(&mut buf))
+                .map_err(error)?;
+        }
+
+        _ => return Ok(body),
+    }
+
+    Ok(Bytes::from(buf))
+}
@@ -0,0 +1,383 @@
+// 📦🦋 charted TestKit: testing library for Axum services with testcontainers support
+// Copyright (c) 2024 Noelware, LLC. <team@noelware.org>
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Persistent cookie jar that can be shared across requests.
+//!
+//! Cookies are stored from `Set-Cookie` headers and sent back with the `Cookie` header
+//! following the rules of [RFC 6265]: the `Domain`, `Path`, `Expires`/`Max-Age` and
+//! `Secure` attributes are all respected. Since the ephemeral server is always served over
+//! plain HTTP, `Secure` cookies are sent to loopback hosts like browsers do.
+//!
+//! [RFC 6265]: https://datatracker.ietf.org/doc/html/rfc6265
+
+pub use cookie::Cookie;
+
+use axum::http::{header, HeaderMap, HeaderValue, Uri};
+use cookie::time::OffsetDateTime;
+use std::{
+    net::IpAddr,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+#[derive(Debug, Clone)]
+struct Entry {
+    cookie: Cookie<'static>,
+
+    /// `None` if the cookie was inserted manually without a `Domain`, which
+    /// means that it'll be sent to any host.
+    domain: Option<String>,
+    host_only: bool,
+    path: String,
+    expires: Option<OffsetDateTime>,
+    secure: bool,
+}
+
+impl Entry {
+    fn is_expired(&self, now: OffsetDateTime) -> bool {
+        self.expires.map(|expires| expires <= now).unwrap_or(false)
+    }
+
+    fn matches(&self, uri: &Uri, now: OffsetDateTime) -> bool {
+        if self.is_expired(now) {
+            return false;
+        }
+
+        let host = uri.host().unwrap_or_default().to_ascii_lowercase();
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if let Some(ref domain) = self.domain {
+            let matches = match self.host_only {
+                true => host == domain,
+                false => domain_matches(host, domain),
+            };
+
+            if !matches {
+                return false;
+            }
+        }
+
+        if self.secure && uri.scheme_str() != Some("https") && !is_loopback(host) {
+            return false;
+        }
+
+        path_matches(uri.path(), &self.path)
+    }
+}
+
+/// A cookie jar that stores cookies from responses and sends them on subsequent requests.
+///
+/// [`CookieJar`] is cheap to clone and all clones share the same cookies.
+#[derive(Debug, Clone, Default)]
+pub struct CookieJar {
+    entries: Arc<Mutex<Vec<Entry>>>,
+}
+
+impl CookieJar {
+    /// Returns a non-expired cookie by its name.
+    pub fn get(&self, name: &str) -> Option<Cookie<'static>> {
+        let now = OffsetDateTime::now_utc();
+        self.lock()
+            .iter()
+            .find(|entry| entry.cookie.name() == name && !entry.is_expired(now))
+            .map(|entry| entry.cookie.clone())
+    }
+
+    /// Returns all the cookies that haven't expired yet.
+    pub fn cookies(&self) -> Vec<Cookie<'static>> {
+        let now = OffsetDateTime::now_utc();
+        self.lock()
+            .iter()
+            .filter(|entry| !entry.is_expired(now))
+            .map(|entry| entry.cookie.clone())
+            .collect()
+    }
+
+    /// Inserts a cookie into the jar, replacing any cookie that has the same name, domain and path.
+    ///
+    /// Cookies without a `Domain` attribute are sent to any host and cookies without a `Path`
+    /// attribute are sent to every path.
+    ///
+    /// ## Example
+    /// ```rust
+    /// use charted_testkit::cookies::{Cookie, CookieJar};
+    ///
+    /// let jar = CookieJar::default();
+    /// jar.insert(Cookie::new("session", "weow"));
+    ///
+    /// assert_eq!(jar.get("session").unwrap().value(), "weow");
+    /// ```
+    pub fn insert(&self, cookie: Cookie<'static>) {
+        let path = match cookie.path() {
+            Some(path) if path.starts_with('/') => path.to_owned(),
+            _ => String::from("/"),
+        };
+
+        let entry = Entry {
+            domain: cookie.domain().map(str::to_ascii_lowercase),
+            host_only: false,
+            expires: expiry_of(&cookie),
+            secure: cookie.secure().unwrap_or(false),
+            cookie,
+            path,
+        };
+
+        self.put(entry);
+    }
+
+    /// Removes all cookies with the given name.
+    pub fn remove(&self, name: &str) {
+        self.lock().retain(|entry| entry.cookie.name() != name);
+    }
+
+    /// Removes all cookies from this jar.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    /// Stores all the cookies from the `Set-Cookie` headers of a response to a request that
+    /// was sent to `uri`. Invalid cookies, or cookies that the request's host isn't allowed to
+    /// set are ignored.
+    pub fn store(&self, uri: &Uri, headers: &HeaderMap) {
+        let host = uri.host().unwrap_or_default().to_ascii_lowercase();
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+
+        for value in headers.get_all(header::SET_COOKIE) {
+            let Ok(cookie) = value
+                .to_str()
+                .map_err(|_| ())
+                .and_then(|value| Cookie::parse(value.to_owned()).map_err(|_| ()))
+            else {
+                continue;
+            };
+
+            let (domain, host_only) = match cookie.domain() {
+                Some(domain) => {
+                    let domain = domain.to_ascii_lowercase();
+                    if !domain_matches(host, &domain) {
+                        continue;
+                    }
+
+                    (domain, false)
+                }
+
+                None => (host.to_owned(), true),
+            };
+
+            let path = match cookie.path() {
+                Some(path) if path.starts_with('/') => path.to_owned(),
+                _ => default_path(uri.path()),
+            };
+
+            let entry = Entry {
+                domain: Some(domain),
+                expires: expiry_of(&cookie),
+                secure: cookie.secure().unwrap_or(false),
+                cookie,
+                host_only,
+                path,
+            };
+
+            // a cookie that has already expired is how servers delete cookies
+            if entry.is_expired(OffsetDateTime::now_utc()) {
+                self.lock().retain(|e| !same_cookie(e, &entry));
+                continue;
+            }
+
+            self.put(entry);
+        }
+    }
+
+    /// Returns the value of the `Cookie` header that should be sent to `uri`, or `None` if
+    /// there are no cookies to send.
+    pub fn header_for(&self, uri: &Uri) -> Option<HeaderValue> {
+        let now = OffsetDateTime::now_utc();
+        let mut entries = self.lock();
+        entries.retain(|entry| !entry.is_expired(now));
+
+        let mut matched = entries
+            .iter()
+            .filter(|entry| entry.matches(uri, now))
+            .collect::<Vec<_>>();
+        if matched.is_empty() {
+            return None;
+        }
+
+        // RFC 6265 § 5.4: cookies with longer paths are listed first
+        matched.sort_by(|a, b| b.path.len().cmp(&a.path.len()));
+
+        let value = matched
+            .iter()
+            .map(|entry| format!("{}={}", entry.cookie.name(), entry.cookie.value()))
+            .collect::<Vec<_>>()
+            .join("; ");
+
+        HeaderValue::from_str(&value).ok()
+    }
+
+    fn put(&self, entry: Entry) {
+        let mut entries = self.lock();
+        match entries.iter_mut().find(|e| same_cookie(e, &entry)) {
+            Some(existing) => *existing = entry,
+            None => entries.push(entry),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<Entry>> {
+        // a poisoned lock only means that another test assertion panicked while holding it
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn same_cookie(a: &Entry, b: &Entry) -> bool {
+    a.cookie.name() == b.cookie.name() && a.domain == b.domain && a.path == b.path
+}
+
+fn expiry_of(cookie: &Cookie<'_>) -> Option<OffsetDateTime> {
+    // `Max-Age` has precedence over `Expires`
+    match cookie.max_age() {
+        Some(max_age) => Some(OffsetDateTime::now_utc() + max_age),
+        None => cookie.expires_datetime(),
+    }
+}
+
+fn is_loopback(host: &str) -> bool {
+    host == "localhost" || host.parse::<IpAddr>().map(|ip| ip.is_loopback()).unwrap_or(false)
+}
+
+/// RFC 6265 § 5.1.3
+fn domain_matches(host: &str, domain: &str) -> bool {
+    if host == domain {
+        return true;
+    }
+
+    host.parse::<IpAddr>().is_err()
+        && host.ends_with(domain)
+        && host.as_bytes().get(host.len() - domain.len() - 1) == Some(&b'.')
+}
+
+/// RFC 6265 § 5.1.4
+fn default_path(path: &str) -> String {
+    match path.rfind('/') {
+        Some(0) | None => String::from("/"),
+        Some(idx) => path[..idx].to_owned(),
+    }
+}
+
+/// RFC 6265 § 5.1.4
+fn path_matches(request: &str, cookie: &str) -> bool {
+    let request = if request.is_empty() { "/" } else { request };
+    if request == cookie {
+        return true;
+    }
+
+    request.starts_with(cookie) && (cookie.ends_with('/') || request.as_bytes().get(cookie.len()) == Some(&b'/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cookie, CookieJar};
+    use axum::http::{header, HeaderMap, HeaderValue, Uri};
+
+    fn set_cookies(values: &[&'static str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append(header::SET_COOKIE, HeaderValue::from_static(value));
+        }
+
+        headers
+    }
+
+    #[test]
+    fn respects_path_and_domain() {
+        let jar = CookieJar::default();
+        jar.store(
+            &Uri::from_static("http://api.charts.dev/login"),
+            &set_cookies(&[
+                "session=a; Path=/api",
+                "theme=dark",
+                "shared=b; Domain=charts.dev",
+                "evil=c; Domain=noelware.org",
+            ]),
+        );
+
+        assert!(jar.get("evil").is_none());
+        assert_eq!(
+            jar.header_for(&Uri::from_static("http://api.charts.dev/api/v1/me"))
+                .unwrap(),
+            "session=a; theme=dark; shared=b"
+        );
+
+        assert_eq!(
+            jar.header_for(&Uri::from_static("http://charts.dev/api")).unwrap(),
+            "shared=b"
+        );
+
+        assert_eq!(
+            jar.header_for(&Uri::from_static("http://api.charts.dev/apis")).unwrap(),
+            "theme=dark; shared=b"
+        );
+    }
+
+    #[test]
+    fn respects_expiry_and_secure() {
+        let jar = CookieJar::default();
+        let uri = Uri::from_static("http://charts.dev/");
+        jar.store(
+            &uri,
+            &set_cookies(&["a=1", "b=2; Secure", "c=3; Expires=Wed, 21 Oct 2015 07:28:00 GMT"]),
+        );
+
+        assert!(jar.get("c").is_none());
+        assert_eq!(jar.header_for(&uri).unwrap(), "a=1");
+        assert_eq!(
+            jar.header_for(&Uri::from_static("https://charts.dev/")).unwrap(),
+            "a=1; b=2"
+        );
+
+        // servers delete cookies by sending an expired cookie
+        jar.store(&uri, &set_cookies(&["a=; Max-Age=0"]));
+        assert!(jar.get("a").is_none());
+    }
+
+    #[test]
+    fn secure_cookies_are_sent_to_loopback() {
+        let jar = CookieJar::default();
+        let uri = Uri::from_static("http://127.0.0.1:3651/");
+        jar.store(&uri, &set_cookies(&["session=weow; Secure; HttpOnly"]));
+
+        assert_eq!(jar.header_for(&uri).unwrap(), "session=weow");
+    }
+
+    #[test]
+    fn insert_and_clear() {
+        let jar = CookieJar::default();
+        jar.insert(Cookie::new("a", "1"));
+        jar.insert(Cookie::new("a", "2"));
+
+        assert_eq!(jar.cookies().len(), 1);
+        assert_eq!(
+            jar.header_for(&Uri::from_static("http://localhost/anything")).unwrap(),
+            "a=2"
+        );
+
+        jar.clear();
+        assert!(jar.cookies().is_empty());
+    }
+}
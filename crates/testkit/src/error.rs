@@ -0,0 +1,98 @@
+// 📦🦋 charted TestKit: testing library for Axum services with testcontainers support
+// Copyright (c) 2024 Noelware, LLC. <team@noelware.org>
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::redirect::RedirectChain;
+use axum::http::Uri;
+use std::fmt::Display;
+
+/// Error type for sending requests with a [`TestContext`][crate::TestContext].
+#[derive(Debug)]
+pub enum Error {
+    /// The request couldn't be sent or its response couldn't be received.
+    Request(hyper_util::client::legacy::Error),
+
+    /// The response body couldn't be received.
+    Body(axum::Error),
+
+    /// The response body couldn't be decompressed.
+    Decompression { encoding: String, message: String },
+
+    /// More redirects than what the [`RedirectPolicy`][crate::redirect::RedirectPolicy] allows
+    /// were returned. `chain` contains every redirect that was followed.
+    TooManyRedirects { max_hops: usize, chain: RedirectChain },
+
+    /// A redirect to a different origin was returned while the [`RedirectPolicy`][crate::redirect::RedirectPolicy]
+    /// only allows redirects to the same origin.
+    CrossOriginRedirect { from: Uri, to: Uri },
+
+    /// A redirect had a `Location` header that couldn't be resolved into a URI.
+    InvalidRedirect { location: String },
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Request(err) => write!(f, "failed to send request: {err}"),
+            Error::Body(err) => write!(f, "failed to receive response body: {err}"),
+            Error::Decompression { encoding, message } => {
+                write!(f, "failed to decompress `{encoding}` response body: {message}")
+            }
+
+            Error::TooManyRedirects { max_hops, chain } => {
+                write!(f, "exceeded the maximum of {max_hops} redirects")?;
+                for hop in chain.hops() {
+                    write!(
+                        f,
+                        "\n  {} {} -> {} {}",
+                        hop.method(),
+                        hop.uri(),
+                        hop.status(),
+                        hop.location().unwrap_or("<no location>")
+                    )?;
+                }
+
+                Ok(())
+            }
+
+            Error::CrossOriginRedirect { from, to } => {
+                write!(f, "refusing to follow cross-origin redirect from `{from}` to `{to}`")
+            }
+
+            Error::InvalidRedirect { location } => write!(f, "redirect has an invalid `Location` header: `{location}`"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Request(err) => Some(err),
+            Error::Body(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<hyper_util::client::legacy::Error> for Error {
+    fn from(value: hyper_util::client::legacy::Error) -> Self {
+        Error::Request(value)
+    }
+}
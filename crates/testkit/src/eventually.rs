@@ -0,0 +1,196 @@
+// 📦🦋 charted TestKit: testing library for Axum services with testcontainers support
+// Copyright (c) 2024 Noelware, LLC. <team@noelware.org>
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Polling for eventually-consistent state with the [`eventually!`][crate::eventually!] macro
+//! and [`TestContext::wait_until_ready`][crate::TestContext::wait_until_ready].
+
+use std::time::{Duration, Instant};
+
+/// How often, and for how long, [`eventually!`][crate::eventually!] polls a value.
+///
+/// By default, the value is polled every 100 milliseconds for up to 10 seconds. With a
+/// [backoff][Eventually::backoff], the interval is multiplied after every attempt until it
+/// reaches the [maximum interval][Eventually::max_interval].
+///
+/// ## Example
+/// ```rust
+/// # use charted_testkit::eventually::Eventually;
+/// # use std::time::Duration;
+/// #
+/// let config = Eventually::default()
+///     .timeout(Duration::from_secs(30))
+///     .interval(Duration::from_millis(50))
+///     .backoff(2.0);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Eventually {
+    timeout: Duration,
+    interval: Duration,
+    backoff: f64,
+    max_interval: Duration,
+}
+
+impl Default for Eventually {
+    fn default() -> Self {
+        Eventually {
+            timeout: Duration::from_secs(10),
+            interval: Duration::from_millis(100),
+            backoff: 1.0,
+            max_interval: Duration::from_secs(5),
+        }
+    }
+}
+
+impl Eventually {
+    /// Sets how long the value is polled for before giving up.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets how long to wait between the first and second attempt.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Sets the factor that the interval is multiplied with after every attempt. `1.0`, the
+    /// default, keeps the interval constant.
+    ///
+    /// ## Panics
+    /// This will panic if `factor` is less than `1.0`.
+    pub fn backoff(mut self, factor: f64) -> Self {
+        assert!(factor >= 1.0, "backoff factor must be at least 1.0, got {factor}");
+
+        self.backoff = factor;
+        self
+    }
+
+    /// Sets the interval that a [backoff][Eventually::backoff] never goes beyond.
+    pub fn max_interval(mut self, max: Duration) -> Self {
+        self.max_interval = max;
+        self
+    }
+
+    #[doc(hidden)]
+    pub fn __start(self) -> Attempts {
+        Attempts {
+            config: self,
+            started: Instant::now(),
+            count: 0,
+            next: self.interval,
+        }
+    }
+}
+
+/// Attempts that were made since polling started.
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct Attempts {
+    config: Eventually,
+    started: Instant,
+    count: u32,
+    next: Duration,
+}
+
+impl Attempts {
+    /// Records a failed attempt and waits before the next one, returning `false` (without
+    /// waiting) if the timeout was reached.
+    pub async fn wait(&mut self) -> bool {
+        self.count += 1;
+
+        let Some(remaining) = self.config.timeout.checked_sub(self.started.elapsed()) else {
+            return false;
+        };
+
+        if remaining.is_zero() {
+            return false;
+        }
+
+        tokio::time::sleep(self.next.min(remaining)).await;
+        self.next = self.next.mul_f64(self.config.backoff).min(self.config.max_interval);
+
+        true
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Returns how long is left until the timeout is reached.
+    pub fn remaining(&self) -> Duration {
+        self.config.timeout.saturating_sub(self.started.elapsed())
+    }
+}
+
+/// Calls `predicate` with `value`, which lets closures passed to [`eventually!`][crate::eventually!]
+/// infer the type of their argument.
+#[doc(hidden)]
+pub fn __check<T, F: FnOnce(&T) -> bool>(value: &T, predicate: F) -> bool {
+    predicate(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Eventually;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn backoff() {
+        let mut attempts = Eventually::default()
+            .interval(Duration::from_millis(1))
+            .backoff(2.0)
+            .max_interval(Duration::from_millis(4))
+            .__start();
+
+        let mut intervals = Vec::new();
+        for _ in 0..4 {
+            intervals.push(attempts.next);
+            assert!(attempts.wait().await);
+        }
+
+        assert_eq!(intervals, [1, 2, 4, 4].map(Duration::from_millis));
+        assert_eq!(attempts.count(), 4);
+    }
+
+    #[tokio::test]
+    async fn timeout() {
+        let mut attempts = Eventually::default()
+            .timeout(Duration::from_millis(20))
+            .interval(Duration::from_millis(15))
+            .__start();
+
+        assert!(attempts.wait().await);
+        assert!(attempts.wait().await);
+        assert!(!attempts.wait().await);
+        assert!(attempts.elapsed() >= Duration::from_millis(20));
+    }
+
+    #[test]
+    #[should_panic(expected = "backoff factor must be at least 1.0")]
+    fn backoff_must_not_shrink() {
+        Eventually::default().backoff(0.5);
+    }
+}
@@ -0,0 +1,397 @@
+// 📦🦋 charted TestKit: testing library for Axum services with testcontainers support
+// Copyright (c) 2024 Noelware, LLC. <team@noelware.org>
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Records of the requests that were sent with a [`TestContext`][crate::TestContext] and the
+//! responses that were received for them.
+//!
+//! Every response that is returned by [`TestContext::request`][crate::TestContext::request] has an
+//! [`Exchange`] extension. TestKit's assertion macros use it to print the full request and response,
+//! plus a `curl` command that reproduces the request, when an assertion fails:
+//!
+//! ```text
+//! expected a successful response, got 404 Not Found
+//!
+//! --- request ---
+//! POST http://127.0.0.1:41521/repositories HTTP/1.1
+//! content-type: application/json
+//!
+//! {"name":"hello-world"}
+//!
+//! --- response ---
+//! HTTP/1.1 404 Not Found
+//! content-type: application/json
+//!
+//! {
+//!   "success": false
+//! }
+//!
+//! --- reproduce with ---
+//! curl -i -X POST 'http://127.0.0.1:41521/repositories' -H 'content-type: application/json' --data-binary '{"name":"hello-world"}'
+//! ```
+//!
+//! Response bodies aren't buffered by default, since that would make requests to endpoints that
+//! stream their responses wait for the whole body. Assertions that consume the body, like
+//! [`assert_json_eq!`][crate::assert_json_eq], print the body that they received, while other
+//! assertions only print it if buffering was enabled with
+//! [`TestContext::set_buffer_responses`][crate::TestContext::set_buffer_responses].
+//! `text/event-stream` responses are never buffered.
+//!
+//! Dumps, `curl` commands and the [`History`][crate::history::History] that is written when a test
+//! panics end up in CI logs and artifacts, so the values of the `Authorization`, `Proxy-Authorization`,
+//! `Cookie` and `Set-Cookie` headers, and of every header value that is [marked as sensitive][HeaderValue::set_sensitive],
+//! are replaced with [`REDACTED`]. Set the `TESTKIT_SHOW_SECRETS` environment variable to `1` to print
+//! them as-is when debugging locally.
+
+use crate::Error;
+use axum::{
+    body::{Body, Bytes},
+    http::{header, HeaderMap, HeaderName, HeaderValue, Method, Response, StatusCode, Uri, Version},
+};
+use http_body_util::BodyExt;
+use std::{
+    borrow::Cow,
+    fmt::{Display, Write},
+};
+
+/// Value that secrets are replaced with when an [`Exchange`] is printed.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// A request that was sent and the response that was received for it.
+#[derive(Debug, Clone)]
+pub struct Exchange {
+    pub(crate) method: Method,
+    pub(crate) uri: Uri,
+    pub(crate) version: Version,
+    pub(crate) request_headers: HeaderMap,
+    pub(crate) request_body: Bytes,
+    pub(crate) status: StatusCode,
+    pub(crate) response_headers: HeaderMap,
+    pub(crate) response_body: Option<Bytes>,
+}
+
+impl Exchange {
+    /// Returns the exchange that a response was received in, if it was received by a [`TestContext`][crate::TestContext].
+    pub fn of<B>(res: &Response<B>) -> Option<&Exchange> {
+        res.extensions().get()
+    }
+
+    /// Method of the request.
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+
+    /// URI of the request.
+    pub fn uri(&self) -> &Uri {
+        &self.uri
+    }
+
+    /// HTTP version of the response.
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    /// Headers of the request.
+    pub fn request_headers(&self) -> &HeaderMap {
+        &self.request_headers
+    }
+
+    /// Body of the request.
+    pub fn request_body(&self) -> &Bytes {
+        &self.request_body
+    }
+
+    /// Status code of the response.
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Headers of the response, as they were received.
+    pub fn response_headers(&self) -> &HeaderMap {
+        &self.response_headers
+    }
+
+    /// Body of the response, if it was buffered. If the response was decompressed, then this is
+    /// the decompressed body.
+    pub fn response_body(&self) -> Option<&Bytes> {
+        self.response_body.as_ref()
+    }
+
+    /// Returns a `curl` command that sends the same request, with the values of secret headers
+    /// replaced with [`REDACTED`] unless `TESTKIT_SHOW_SECRETS=1` is set.
+    pub fn curl(&self) -> String {
+        let mut command = format!("curl -i -X {} {}", self.method, shell_quote(&self.uri.to_string()));
+        for (name, value) in &self.request_headers {
+            if name == header::CONTENT_LENGTH || name == header::HOST {
+                continue;
+            }
+
+            let header = format!("{name}: {}", printable(name, value));
+            let _ = write!(command, " -H {}", shell_quote(&header));
+        }
+
+        if !self.request_body.is_empty() {
+            match std::str::from_utf8(&self.request_body) {
+                Ok(body) => {
+                    let _ = write!(command, " --data-binary {}", shell_quote(body));
+                }
+
+                // binary bodies can't be written as an argument, so they have to be piped in
+                Err(_) => {
+                    command = format!(
+                        "printf '{}' | {command} --data-binary @-",
+                        escape_printf(&self.request_body)
+                    )
+                }
+            }
+        }
+
+        command
+    }
+}
+
+impl Exchange {
+    /// Returns a dump of this exchange that shows `body` as the response body if it wasn't
+    /// buffered, for assertions that received the body themselves.
+    pub(crate) fn with_body<'a>(&'a self, body: &'a [u8]) -> WithBody<'a> {
+        WithBody(self, body)
+    }
+
+    fn render(&self, f: &mut std::fmt::Formatter<'_>, response_body: Option<&[u8]>) -> std::fmt::Result {
+        writeln!(f, "--- request ---")?;
+        writeln!(f, "{} {} {:?}", self.method, self.uri, self.version)?;
+        write_headers(f, &self.request_headers)?;
+        if !self.request_body.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "{}", render_body(&self.request_headers, &self.request_body))?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "--- response ---")?;
+        writeln!(f, "{:?} {}", self.version, self.status)?;
+        write_headers(f, &self.response_headers)?;
+        match response_body {
+            Some([]) => {}
+            Some(body) => {
+                writeln!(f)?;
+                writeln!(f, "{}", render_body(&self.response_headers, body))?;
+            }
+
+            None => {
+                writeln!(f)?;
+                writeln!(f, "<body was not buffered, see `TestContext::set_buffer_responses`>")?;
+            }
+        }
+
+        writeln!(f)?;
+        writeln!(f, "--- reproduce with ---")?;
+        write!(f, "{}", self.curl())
+    }
+}
+
+impl Display for Exchange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.render(f, self.response_body.as_deref())
+    }
+}
+
+/// An [`Exchange`] with a response body that was received after the response was returned.
+pub(crate) struct WithBody<'a>(&'a Exchange, &'a [u8]);
+
+impl Display for WithBody<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0
+            .render(f, Some(self.0.response_body.as_deref().unwrap_or(self.1)))
+    }
+}
+
+fn write_headers(f: &mut std::fmt::Formatter<'_>, headers: &HeaderMap) -> std::fmt::Result {
+    for (name, value) in headers {
+        writeln!(f, "{name}: {}", printable(name, value))?;
+    }
+
+    Ok(())
+}
+
+/// Checks whenever if a header value is a secret, which are credentials, cookies and values that
+/// are marked as sensitive.
+pub(crate) fn is_secret(name: &HeaderName, value: &HeaderValue) -> bool {
+    value.is_sensitive()
+        || [
+            header::AUTHORIZATION,
+            header::PROXY_AUTHORIZATION,
+            header::COOKIE,
+            header::SET_COOKIE,
+        ]
+        .contains(name)
+}
+
+/// Checks whenever if secrets should be printed as-is, which is opt-in with the
+/// `TESTKIT_SHOW_SECRETS` environment variable.
+pub(crate) fn shows_secrets() -> bool {
+    std::env::var_os("TESTKIT_SHOW_SECRETS").is_some_and(|value| value == "1")
+}
+
+/// Returns how a header value is printed, which is [`REDACTED`] for secrets.
+pub(crate) fn printable<'a>(name: &HeaderName, value: &'a HeaderValue) -> Cow<'a, str> {
+    match is_secret(name, value) && !shows_secrets() {
+        true => Cow::Borrowed(REDACTED),
+        false => String::from_utf8_lossy(value.as_bytes()),
+    }
+}
+
+/// Renders a body for an [`Exchange`] dump, pretty-printing JSON bodies.
+fn render_body(headers: &HeaderMap, body: &[u8]) -> String {
+    #[cfg(feature = "json")]
+    {
+        let is_json = crate::util::content_type_essence(headers)
+            .is_some_and(|essence| essence == "application/json" || essence.ends_with("+json"));
+
+        if is_json {
+            if let Ok(pretty) =
+                serde_json::from_slice::<serde_json::Value>(body).and_then(|value| serde_json::to_vec_pretty(&value))
+            {
+                return crate::util::display_body(&pretty).into_owned();
+            }
+        }
+    }
+
+    #[cfg(not(feature = "json"))]
+    let _ = headers;
+
+    crate::util::display_body(body).into_owned()
+}
+
+/// Quotes `value` for a POSIX shell.
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+fn escape_printf(body: &[u8]) -> String {
+    body.iter().fold(String::new(), |mut escaped, byte| {
+        let _ = write!(escaped, "\\{byte:03o}");
+        escaped
+    })
+}
+
+/// Buffers the body of a response into its [`Exchange`], if it has one and the body should be buffered.
+pub(crate) async fn buffer(res: Response<Body>, buffer: bool) -> Result<Response<Body>, Error> {
+    let is_stream =
+        crate::util::content_type_essence(res.headers()).is_some_and(|essence| essence == "text/event-stream");
+    if !buffer || is_stream || res.extensions().get::<Exchange>().is_none() {
+        return Ok(res);
+    }
+
+    let (mut parts, body) = res.into_parts();
+    let body = body.collect().await.map_err(Error::Body)?.to_bytes();
+    if let Some(exchange) = parts.extensions.get_mut::<Exchange>() {
+        exchange.response_body = Some(body.clone());
+    }
+
+    Ok(Response::from_parts(parts, Body::from(body)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{shell_quote, Exchange, REDACTED};
+    use axum::{
+        body::Bytes,
+        http::{header, HeaderMap, HeaderValue, Method, StatusCode, Uri, Version},
+    };
+
+    fn exchange(body: &'static [u8]) -> Exchange {
+        let mut request_headers = HeaderMap::new();
+        request_headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        request_headers.insert(header::CONTENT_LENGTH, HeaderValue::from_static("2"));
+        request_headers.insert("x-quote", HeaderValue::from_static("it's"));
+
+        let mut response_headers = HeaderMap::new();
+        response_headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
+
+        Exchange {
+            method: Method::POST,
+            uri: Uri::from_static("http://127.0.0.1:3651/repos"),
+            version: Version::HTTP_11,
+            request_headers,
+            request_body: Bytes::from_static(body),
+            status: StatusCode::NOT_FOUND,
+            response_headers,
+            response_body: Some(Bytes::from_static(br#"{"success":false}"#)),
+        }
+    }
+
+    #[test]
+    fn curl_commands() {
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+        assert_eq!(
+            exchange(b"{}").curl(),
+            r"curl -i -X POST 'http://127.0.0.1:3651/repos' -H 'content-type: application/json' -H 'x-quote: it'\''s' --data-binary '{}'"
+        );
+
+        assert_eq!(
+            exchange(b"\xff\x01").curl(),
+            r"printf '\377\001' | curl -i -X POST 'http://127.0.0.1:3651/repos' -H 'content-type: application/json' -H 'x-quote: it'\''s' --data-binary @-"
+        );
+    }
+
+    #[test]
+    fn dumps() {
+        let dump = exchange(b"{}").to_string();
+
+        assert!(dump.starts_with("--- request ---\nPOST http://127.0.0.1:3651/repos HTTP/1.1\n"));
+        assert!(dump.contains("\n--- response ---\nHTTP/1.1 404 Not Found\ncontent-type: application/json\n"));
+        assert!(dump.contains("\n--- reproduce with ---\ncurl -i -X POST"));
+
+        #[cfg(feature = "json")]
+        assert!(dump.contains("{\n  \"success\": false\n}"));
+
+        let mut with_secrets = exchange(b"{}");
+        let mut token = HeaderValue::from_static("Bearer hunter2");
+        token.set_sensitive(true);
+
+        with_secrets
+            .request_headers
+            .insert(header::AUTHORIZATION, HeaderValue::from_static("Basic bm9lbDp3ZW93"));
+        with_secrets
+            .request_headers
+            .insert(header::COOKIE, HeaderValue::from_static("session=weow"));
+        with_secrets.request_headers.insert("x-api-key", token);
+        with_secrets
+            .response_headers
+            .insert(header::SET_COOKIE, HeaderValue::from_static("session=weow2"));
+
+        let dump = with_secrets.to_string();
+        for secret in ["bm9lbDp3ZW93", "session=weow", "hunter2"] {
+            assert!(!dump.contains(secret), "{secret} was leaked in:\n{dump}");
+        }
+
+        assert!(dump.contains(&format!("authorization: {REDACTED}\n")));
+        assert!(dump.contains(&format!("set-cookie: {REDACTED}\n")));
+        assert!(with_secrets.curl().contains(&format!(" -H 'x-api-key: {REDACTED}'")));
+
+        let mut unbuffered = exchange(b"");
+        unbuffered.response_body = None;
+        assert!(unbuffered.to_string().contains("<body was not buffered, see"));
+        assert!(unbuffered
+            .with_body(b"received")
+            .to_string()
+            .contains("\n--- response ---\nHTTP/1.1 404 Not Found\ncontent-type: application/json\n\nreceived\n"));
+    }
+}
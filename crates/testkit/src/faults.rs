@@ -0,0 +1,422 @@
+// 📦🦋 charted TestKit: testing library for Axum services with testcontainers support
+// Copyright (c) 2024 Noelware, LLC. <team@noelware.org>
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Fault injection for the ephemeral server, to test how clients handle slow and misbehaving
+//! services.
+//!
+//! Every [`TestContext`][crate::TestContext] has a set of [`Faults`] that is applied to the
+//! responses of the router that it [serves][crate::TestContext::serve]. Faults can be added and
+//! removed at any time while the server is running, and only apply to the requests that were
+//! received after that.
+//!
+//! ## Example
+//! ```rust,no_run
+//! # use charted_testkit::{TestContext, faults::Fault};
+//! # use axum::http::StatusCode;
+//! # use std::time::Duration;
+//! #
+//! # #[tokio::main]
+//! # async fn main() {
+//! let mut ctx = TestContext::default();
+//! ctx.serve(axum::Router::new()).await;
+//!
+//! let slow = ctx.faults().add(Fault::latency(Duration::from_millis(500)).on("/v1/repositories/*"));
+//! ctx.faults().add(Fault::error(StatusCode::SERVICE_UNAVAILABLE, 0.25));
+//!
+//! // ...
+//!
+//! ctx.faults().remove(slow);
+//! # }
+//! ```
+
+use crate::util::Rng;
+use axum::{
+    body::{Body, Bytes},
+    extract::{Request, State},
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use http_body::{Frame, SizeHint};
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{ready, Context, Poll},
+    time::Duration,
+};
+use tokio::time::Sleep;
+
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    Latency(Duration),
+    Error { status: StatusCode, rate: f64 },
+    Reset { after: usize },
+    Truncate { after: usize },
+    SlowWrite { delay: Duration },
+}
+
+/// A fault that is injected into the responses of the routes that it applies to.
+#[derive(Debug, Clone)]
+pub struct Fault {
+    kind: Kind,
+    route: Option<String>,
+    method: Option<Method>,
+}
+
+impl Fault {
+    fn new(kind: Kind) -> Fault {
+        Fault {
+            kind,
+            route: None,
+            method: None,
+        }
+    }
+
+    /// Delays the response by `delay` before the request is handled.
+    pub fn latency(delay: Duration) -> Fault {
+        Fault::new(Kind::Latency(delay))
+    }
+
+    /// Responds with `status` instead of handling the request, for a `rate` (between `0.0` and
+    /// `1.0`) of the requests.
+    ///
+    /// ## Panics
+    /// This will panic if `rate` isn't between `0.0` and `1.0`.
+    pub fn error(status: StatusCode, rate: f64) -> Fault {
+        assert!(
+            (0.0..=1.0).contains(&rate),
+            "error rate must be between 0.0 and 1.0, got {rate}"
+        );
+        Fault::new(Kind::Error { status, rate })
+    }
+
+    /// Aborts the connection after `bytes` bytes of the response body were sent.
+    pub fn reset_after(bytes: usize) -> Fault {
+        Fault::new(Kind::Reset { after: bytes })
+    }
+
+    /// Ends the response body after `bytes` bytes while its `Content-Length` header still has the
+    /// length of the whole body, so the response is shorter than the client expects.
+    pub fn truncate_after(bytes: usize) -> Fault {
+        Fault::new(Kind::Truncate { after: bytes })
+    }
+
+    /// Sends the response body byte by byte, waiting `delay` after each byte.
+    pub fn slow_write(delay: Duration) -> Fault {
+        Fault::new(Kind::SlowWrite { delay })
+    }
+
+    /// Only applies this fault to requests to `route`, which is either an exact path (i.e,
+    /// `/v1/users`) or a prefix if it ends with `*` (i.e, `/v1/*`). By default, faults apply to
+    /// every route.
+    pub fn on<R: Into<String>>(mut self, route: R) -> Self {
+        self.route = Some(route.into());
+        self
+    }
+
+    /// Only applies this fault to requests with `method`.
+    pub fn method(mut self, method: Method) -> Self {
+        self.method = Some(method);
+        self
+    }
+
+    fn applies_to(&self, method: &Method, path: &str) -> bool {
+        if self.method.as_ref().is_some_and(|expected| expected != method) {
+            return false;
+        }
+
+        match self.route.as_deref() {
+            None => true,
+            Some(route) => match route.strip_suffix('*') {
+                Some(prefix) => path.starts_with(prefix),
+                None => path == route,
+            },
+        }
+    }
+}
+
+/// Identifier of a [`Fault`] that was added with [`Faults::add`], which can be used to remove it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FaultId(u64);
+
+#[derive(Debug)]
+struct Inner {
+    faults: Vec<(FaultId, Fault)>,
+    next_id: u64,
+    rng: Rng,
+}
+
+/// The faults of a [`TestContext`][crate::TestContext], which is cheap to clone.
+#[derive(Debug, Clone)]
+pub struct Faults(Arc<Mutex<Inner>>);
+
+impl Default for Faults {
+    fn default() -> Self {
+        Faults(Arc::new(Mutex::new(Inner {
+            faults: Vec::new(),
+            next_id: 0,
+            rng: Rng::from_time(),
+        })))
+    }
+}
+
+impl Faults {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Adds a fault, which applies to every request that is received from now on.
+    pub fn add(&self, fault: Fault) -> FaultId {
+        let mut inner = self.lock();
+        let id = FaultId(inner.next_id);
+
+        inner.next_id += 1;
+        inner.faults.push((id, fault));
+
+        id
+    }
+
+    /// Removes a fault that was added with [`Faults::add`], returning whenever if it existed.
+    pub fn remove(&self, id: FaultId) -> bool {
+        let mut inner = self.lock();
+        let len = inner.faults.len();
+        inner.faults.retain(|(fault, _)| *fault != id);
+
+        inner.faults.len() != len
+    }
+
+    /// Removes every fault.
+    pub fn clear(&self) {
+        self.lock().faults.clear();
+    }
+
+    /// Returns how many faults were added.
+    pub fn len(&self) -> usize {
+        self.lock().faults.len()
+    }
+
+    /// Returns `true` if no faults were added.
+    pub fn is_empty(&self) -> bool {
+        self.lock().faults.is_empty()
+    }
+
+    /// Seeds the random number generator that decides which requests fail with
+    /// [`Fault::error`], so that they are the same on every run.
+    pub fn seed(&self, seed: u64) {
+        self.lock().rng = Rng::new(seed);
+    }
+
+    /// Resolves what happens to a request with `method` to `path`.
+    fn plan(&self, method: &Method, path: &str) -> Plan {
+        let mut inner = self.lock();
+        let mut plan = Plan::default();
+
+        for index in 0..inner.faults.len() {
+            let fault = &inner.faults[index].1;
+            if !fault.applies_to(method, path) {
+                continue;
+            }
+
+            match fault.kind {
+                Kind::Latency(delay) => plan.latency += delay,
+                Kind::Error { status, rate } => {
+                    if plan.error.is_none() && inner.rng.next_f64() < rate {
+                        plan.error = Some(status);
+                    }
+                }
+
+                Kind::Reset { after } => {
+                    plan.cut = plan.cut.or(Some(Cut { after, reset: true }));
+                }
+
+                Kind::Truncate { after } => {
+                    plan.cut = plan.cut.or(Some(Cut { after, reset: false }));
+                }
+
+                Kind::SlowWrite { delay } => plan.delay = plan.delay.or(Some(delay)),
+            }
+        }
+
+        plan
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Cut {
+    after: usize,
+    reset: bool,
+}
+
+#[derive(Debug, Default)]
+struct Plan {
+    latency: Duration,
+    error: Option<StatusCode>,
+    cut: Option<Cut>,
+    delay: Option<Duration>,
+}
+
+/// Middleware that injects the faults into the responses of the ephemeral server.
+pub(crate) async fn inject(State(faults): State<Faults>, req: Request, next: Next) -> Response {
+    let plan = faults.plan(req.method(), req.uri().path());
+    if !plan.latency.is_zero() {
+        tokio::time::sleep(plan.latency).await;
+    }
+
+    if let Some(status) = plan.error {
+        return (status, "injected fault").into_response();
+    }
+
+    let res = next.run(req).await;
+    if plan.cut.is_none() && plan.delay.is_none() {
+        return res;
+    }
+
+    let (mut parts, body) = res.into_parts();
+    let body = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response(),
+    };
+
+    parts.headers.remove(header::TRANSFER_ENCODING);
+    parts
+        .headers
+        .insert(header::CONTENT_LENGTH, HeaderValue::from(body.len()));
+
+    let end = plan.cut.map_or(body.len(), |cut| cut.after.min(body.len()));
+    let body = FaultyBody {
+        body,
+        position: 0,
+        end,
+        reset: plan.cut.is_some_and(|cut| cut.reset),
+        delay: plan.delay,
+        sleep: None,
+    };
+
+    Response::from_parts(parts, Body::new(body))
+}
+
+/// Body that is cut off after `end` bytes and sent byte by byte if it has a `delay`.
+struct FaultyBody {
+    body: Bytes,
+    position: usize,
+    end: usize,
+    reset: bool,
+    delay: Option<Duration>,
+    sleep: Option<Pin<Box<Sleep>>>,
+}
+
+impl http_body::Body for FaultyBody {
+    type Data = Bytes;
+    type Error = std::io::Error;
+
+    fn poll_frame(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Frame<Bytes>, Self::Error>>> {
+        if let Some(sleep) = self.sleep.as_mut() {
+            ready!(sleep.as_mut().poll(cx));
+            self.sleep = None;
+        }
+
+        if self.position >= self.end {
+            if self.reset && self.end < self.body.len() {
+                return Poll::Ready(Some(Err(std::io::Error::new(
+                    std::io::ErrorKind::ConnectionReset,
+                    "injected connection reset",
+                ))));
+            }
+
+            return Poll::Ready(None);
+        }
+
+        let len = match self.delay {
+            Some(_) => 1,
+            None => self.end - self.position,
+        };
+
+        let chunk = self.body.slice(self.position..self.position + len);
+        self.position += len;
+
+        if let Some(delay) = self.delay {
+            self.sleep = Some(Box::pin(tokio::time::sleep(delay)));
+        }
+
+        Poll::Ready(Some(Ok(Frame::data(chunk))))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        !self.reset && self.position >= self.end && self.sleep.is_none()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        SizeHint::with_exact((self.body.len() - self.position) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Fault, Faults};
+    use axum::http::{Method, StatusCode};
+    use std::time::Duration;
+
+    #[test]
+    fn routes() {
+        let fault = Fault::latency(Duration::ZERO).on("/v1/*").method(Method::GET);
+        assert!(fault.applies_to(&Method::GET, "/v1/users"));
+        assert!(!fault.applies_to(&Method::POST, "/v1/users"));
+        assert!(!fault.applies_to(&Method::GET, "/v2/users"));
+
+        let fault = Fault::latency(Duration::ZERO).on("/v1/users");
+        assert!(fault.applies_to(&Method::DELETE, "/v1/users"));
+        assert!(!fault.applies_to(&Method::DELETE, "/v1/users/1"));
+    }
+
+    #[test]
+    fn plans() {
+        let faults = Faults::default();
+        faults.add(Fault::latency(Duration::from_millis(10)));
+        faults.add(Fault::latency(Duration::from_millis(20)).on("/slow"));
+        let error = faults.add(Fault::error(StatusCode::BAD_GATEWAY, 1.0).on("/slow"));
+
+        let plan = faults.plan(&Method::GET, "/slow");
+        assert_eq!(plan.latency, Duration::from_millis(30));
+        assert_eq!(plan.error, Some(StatusCode::BAD_GATEWAY));
+
+        assert!(faults.remove(error));
+        assert!(!faults.remove(error));
+        assert_eq!(faults.plan(&Method::GET, "/slow").error, None);
+        assert_eq!(faults.plan(&Method::GET, "/").latency, Duration::from_millis(10));
+
+        faults.clear();
+        assert!(faults.is_empty());
+    }
+
+    #[test]
+    fn error_rates() {
+        let faults = Faults::default();
+        faults.seed(42);
+        faults.add(Fault::error(StatusCode::SERVICE_UNAVAILABLE, 0.5));
+
+        let errors = (0..1000)
+            .filter(|_| faults.plan(&Method::GET, "/").error.is_some())
+            .count();
+
+        assert!((400..600).contains(&errors), "{errors} of 1000 requests failed");
+    }
+}
@@ -0,0 +1,374 @@
+// 📦🦋 charted TestKit: testing library for Axum services with testcontainers support
+// Copyright (c) 2024 Noelware, LLC. <team@noelware.org>
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Rendering of a [`History`][crate::history::History] as a [HAR 1.2](http://www.softwareishard.com/blog/har-12-spec) log.
+
+use crate::{
+    exchange::{self, Exchange, REDACTED},
+    history::Record,
+};
+use axum::{
+    body::Bytes,
+    http::{header, HeaderMap, StatusCode, Version},
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde_json::{json, Value};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Renders every record as a HAR log.
+pub(crate) fn render(records: &[Record]) -> Value {
+    json!({
+        "log": {
+            "version": "1.2",
+            "creator": {
+                "name": "charted-testkit",
+                "version": env!("CARGO_PKG_VERSION"),
+            },
+            "entries": records.iter().map(entry).collect::<Vec<_>>(),
+        }
+    })
+}
+
+fn entry(record: &Record) -> Value {
+    let exchange = record.exchange();
+    let timings = record.timings();
+
+    let mut entry = json!({
+        "startedDateTime": timestamp(record.started_at()),
+        "time": millis(timings.total()),
+        "request": request(exchange),
+        "response": response(exchange),
+        "cache": {},
+        "timings": {
+            "blocked": -1,
+            "dns": -1,
+            "connect": -1,
+            "ssl": -1,
+            "send": 0,
+            "wait": millis(timings.wait()),
+            "receive": millis(timings.receive()),
+        },
+    });
+
+    if let Some(session) = record.session() {
+        entry["_session"] = Value::from(session);
+    }
+
+    if record.is_truncated() {
+        entry["comment"] = Value::from(format!("bodies were truncated to {} bytes", crate::history::BODY_LIMIT));
+    }
+
+    entry
+}
+
+fn request(exchange: &Exchange) -> Value {
+    let headers = exchange.request_headers();
+    let body = exchange.request_body();
+    let query = exchange
+        .uri()
+        .query()
+        .into_iter()
+        .flat_map(|query| query.split('&'))
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            json!({ "name": name, "value": value })
+        })
+        .collect::<Vec<_>>();
+
+    let cookies = headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .map(|(name, value)| json!({ "name": name, "value": cookie_value(value) }))
+        .collect::<Vec<_>>();
+
+    let mut request = json!({
+        "method": exchange.method().as_str(),
+        "url": exchange.uri().to_string(),
+        "httpVersion": http_version(exchange.version()),
+        "cookies": cookies,
+        "headers": headers_of(headers),
+        "queryString": query,
+        "headersSize": -1,
+        "bodySize": body.len(),
+    });
+
+    if !body.is_empty() {
+        let mut post_data = json!({ "mimeType": mime_type(headers) });
+        match std::str::from_utf8(body) {
+            Ok(text) => post_data["text"] = Value::from(text),
+
+            // HAR 1.2 doesn't allow an `encoding` for request bodies, but most viewers
+            // (including Chrome's) understand it anyway
+            Err(_) => {
+                post_data["text"] = Value::from(STANDARD.encode(body));
+                post_data["encoding"] = Value::from("base64");
+            }
+        }
+
+        request["postData"] = post_data;
+    }
+
+    request
+}
+
+fn response(exchange: &Exchange) -> Value {
+    let headers = exchange.response_headers();
+    let status = exchange.status();
+    let cookies = headers
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .filter_map(|value| cookie::Cookie::parse(value.to_owned()).ok())
+        .map(|cookie| {
+            let mut value = json!({ "name": cookie.name(), "value": cookie_value(cookie.value()) });
+            if let Some(path) = cookie.path() {
+                value["path"] = Value::from(path);
+            }
+
+            if let Some(domain) = cookie.domain() {
+                value["domain"] = Value::from(domain);
+            }
+
+            if let Some(http_only) = cookie.http_only() {
+                value["httpOnly"] = Value::from(http_only);
+            }
+
+            if let Some(secure) = cookie.secure() {
+                value["secure"] = Value::from(secure);
+            }
+
+            value
+        })
+        .collect::<Vec<_>>();
+
+    let mut content = json!({ "size": 0, "mimeType": mime_type(headers) });
+    match exchange.response_body() {
+        Some(body) => {
+            content["size"] = Value::from(body.len());
+            set_text(&mut content, body);
+        }
+
+        None => content["comment"] = Value::from("body was not buffered"),
+    }
+
+    json!({
+        "status": status.as_u16(),
+        "statusText": status_text(status),
+        "httpVersion": http_version(exchange.version()),
+        "cookies": cookies,
+        "headers": headers_of(headers),
+        "content": content,
+        "redirectURL": headers
+            .get(header::LOCATION)
+            .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+            .unwrap_or_default(),
+        "headersSize": -1,
+        "bodySize": exchange.response_body().map_or(-1, |body| body.len() as i64),
+    })
+}
+
+fn set_text(content: &mut Value, body: &Bytes) {
+    if body.is_empty() {
+        return;
+    }
+
+    match std::str::from_utf8(body) {
+        Ok(text) => content["text"] = Value::from(text),
+        Err(_) => {
+            content["text"] = Value::from(STANDARD.encode(body));
+            content["encoding"] = Value::from("base64");
+        }
+    }
+}
+
+fn headers_of(headers: &HeaderMap) -> Vec<Value> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            json!({
+                "name": name.as_str(),
+                "value": exchange::printable(name, value),
+            })
+        })
+        .collect()
+}
+
+fn cookie_value(value: &str) -> &str {
+    match exchange::shows_secrets() {
+        true => value,
+        false => REDACTED,
+    }
+}
+
+fn mime_type(headers: &HeaderMap) -> String {
+    headers
+        .get(header::CONTENT_TYPE)
+        .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+        .unwrap_or_default()
+}
+
+fn status_text(status: StatusCode) -> &'static str {
+    status.canonical_reason().unwrap_or_default()
+}
+
+fn http_version(version: Version) -> &'static str {
+    match version {
+        Version::HTTP_09 => "HTTP/0.9",
+        Version::HTTP_10 => "HTTP/1.0",
+        Version::HTTP_2 => "HTTP/2.0",
+        Version::HTTP_3 => "HTTP/3.0",
+        _ => "HTTP/1.1",
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// Formats `time` as an ISO 8601 timestamp in UTC with millisecond precision, like
+/// `2024-08-18T16:53:20.123Z`.
+fn timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (days, secs_of_day) = (secs / 86_400, secs % 86_400);
+
+    // https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render, timestamp};
+    use crate::{
+        exchange::{Exchange, REDACTED},
+        history::{Record, Timings},
+    };
+    use axum::{
+        body::Bytes,
+        http::{header, HeaderMap, HeaderValue, Method, StatusCode, Uri, Version},
+    };
+    use serde_json::json;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(timestamp(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+        assert_eq!(
+            timestamp(UNIX_EPOCH + Duration::from_millis(1_724_000_000_123)),
+            "2024-08-18T16:53:20.123Z"
+        );
+
+        // leap day
+        assert_eq!(
+            timestamp(UNIX_EPOCH + Duration::from_secs(1_709_164_800)),
+            "2024-02-29T00:00:00.000Z"
+        );
+    }
+
+    #[test]
+    fn entries() {
+        let mut request_headers = HeaderMap::new();
+        request_headers.insert(header::COOKIE, HeaderValue::from_static("session=1; theme=dark"));
+
+        let mut response_headers = HeaderMap::new();
+        response_headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/octet-stream"),
+        );
+        response_headers.insert(
+            header::SET_COOKIE,
+            HeaderValue::from_static("session=2; Path=/; HttpOnly"),
+        );
+
+        let exchange = Exchange {
+            method: Method::POST,
+            uri: Uri::from_static("http://127.0.0.1:3651/upload?name=a&force"),
+            version: Version::HTTP_2,
+            request_headers,
+            request_body: Bytes::from_static(b"hello"),
+            status: StatusCode::CREATED,
+            response_headers,
+            response_body: Some(Bytes::from_static(b"\xff\x00")),
+        };
+
+        let timings = Timings {
+            wait: Duration::from_millis(3),
+            receive: Duration::from_millis(1),
+        };
+
+        let har = render(&[Record::new(Some(String::from("alice")), UNIX_EPOCH, timings, &exchange)]);
+
+        let entry = &har["log"]["entries"][0];
+        assert_eq!(entry["startedDateTime"], "1970-01-01T00:00:00.000Z");
+        assert_eq!(entry["time"], 4.0);
+        assert_eq!(entry["_session"], "alice");
+        assert_eq!(
+            entry["timings"],
+            json!({ "blocked": -1, "dns": -1, "connect": -1, "ssl": -1, "send": 0, "wait": 3.0, "receive": 1.0 })
+        );
+
+        let request = &entry["request"];
+        assert_eq!(request["method"], "POST");
+        assert_eq!(request["httpVersion"], "HTTP/2.0");
+        assert_eq!(
+            request["queryString"],
+            json!([{ "name": "name", "value": "a" }, { "name": "force", "value": "" }])
+        );
+        assert_eq!(
+            request["cookies"],
+            json!([{ "name": "session", "value": REDACTED }, { "name": "theme", "value": REDACTED }])
+        );
+        assert_eq!(request["headers"], json!([{ "name": "cookie", "value": REDACTED }]));
+        assert_eq!(request["postData"], json!({ "mimeType": "", "text": "hello" }));
+
+        let response = &entry["response"];
+        assert_eq!(response["status"], 201);
+        assert_eq!(response["statusText"], "Created");
+        assert_eq!(
+            response["cookies"],
+            json!([{ "name": "session", "value": REDACTED, "path": "/", "httpOnly": true }])
+        );
+        assert_eq!(
+            response["content"],
+            json!({ "size": 2, "mimeType": "application/octet-stream", "text": "/wA=", "encoding": "base64" })
+        );
+    }
+}
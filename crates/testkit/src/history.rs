@@ -0,0 +1,427 @@
+// 📦🦋 charted TestKit: testing library for Axum services with testcontainers support
+// Copyright (c) 2024 Noelware, LLC. <team@noelware.org>
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Recording of every request that was sent with a [`TestContext`][crate::TestContext].
+//!
+//! Every exchange of every session is recorded into the context's [`History`] with its timings,
+//! headers and bodies, which are truncated to [`BODY_LIMIT`] bytes. Response bodies are only
+//! recorded if they are [buffered][crate::TestContext::set_buffer_responses]. When a test panics while its
+//! [`TestContext`][crate::TestContext] is still alive, the history is written to
+//! `{artifacts}/{test}/history.txt` (and as a HAR log to `history.har` if the `json` crate feature is
+//! enabled) so that flaky failures can be debugged after the fact, where
+//! `{test}` is the name of the test and `{artifacts}` is, in order:
+//!
+//! 1. the directory that was set with [`TestContext::artifacts_dir`][crate::TestContext::artifacts_dir]
+//! 2. the `TESTKIT_ARTIFACTS_DIR` environment variable
+//! 3. `testkit-artifacts` in the `CARGO_TARGET_DIR` environment variable, or in the target directory
+//!    that the test executable was built into
+//!
+//! If more than one context of the same test recorded exchanges, every other context writes its
+//! history to `history-2.txt`, `history-3.txt` and so on, in the order that they are dropped.
+//!
+//! ## Example
+//! ```rust,no_run
+//! # use charted_testkit::TestContext;
+//! # use axum::http::Method;
+//! #
+//! # #[tokio::main]
+//! # async fn main() {
+//! let ctx = TestContext::default();
+//! ctx.request("/", Method::GET, None, charted_testkit::noop_request).await.unwrap();
+//!
+//! let records = ctx.history().records();
+//! assert_eq!(records.len(), 1);
+//! println!("took {:?}", records[0].duration());
+//! # }
+//! ```
+
+use crate::exchange::Exchange;
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+/// Maximum amount of bytes of a request or response body that is recorded.
+pub const BODY_LIMIT: usize = 64 * 1024;
+
+/// How long the parts of an exchange took.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Timings {
+    pub(crate) wait: Duration,
+    pub(crate) receive: Duration,
+}
+
+impl Timings {
+    /// How long it took from sending the request until the response headers were received,
+    /// including redirects that were followed.
+    pub fn wait(&self) -> Duration {
+        self.wait
+    }
+
+    /// How long it took to receive the response body after its headers were received. This is
+    /// zero if the body wasn't buffered.
+    pub fn receive(&self) -> Duration {
+        self.receive
+    }
+
+    /// How long the whole exchange took.
+    pub fn total(&self) -> Duration {
+        self.wait + self.receive
+    }
+}
+
+/// A recorded exchange.
+#[derive(Debug, Clone)]
+pub struct Record {
+    session: Option<String>,
+    started_at: SystemTime,
+    timings: Timings,
+    exchange: Exchange,
+    truncated: bool,
+}
+
+impl Record {
+    pub(crate) fn new(
+        session: Option<String>,
+        started_at: SystemTime,
+        timings: Timings,
+        exchange: &Exchange,
+    ) -> Record {
+        let mut exchange = exchange.clone();
+        let mut truncated = false;
+
+        if exchange.request_body.len() > BODY_LIMIT {
+            exchange.request_body = exchange.request_body.slice(..BODY_LIMIT);
+            truncated = true;
+        }
+
+        if let Some(body) = exchange.response_body.as_mut().filter(|body| body.len() > BODY_LIMIT) {
+            *body = body.slice(..BODY_LIMIT);
+            truncated = true;
+        }
+
+        Record {
+            session,
+            started_at,
+            timings,
+            exchange,
+            truncated,
+        }
+    }
+
+    /// Name of the [`Session`][crate::session::Session] that sent the request, or `None` if it was
+    /// sent by the default session.
+    pub fn session(&self) -> Option<&str> {
+        self.session.as_deref()
+    }
+
+    /// When the request was sent.
+    pub fn started_at(&self) -> SystemTime {
+        self.started_at
+    }
+
+    /// How long it took until the whole response was received, including redirects that were
+    /// followed and, if it was buffered, the body.
+    pub fn duration(&self) -> Duration {
+        self.timings.total()
+    }
+
+    /// How long the parts of the exchange took.
+    pub fn timings(&self) -> Timings {
+        self.timings
+    }
+
+    /// The exchange, with bodies that are truncated to [`BODY_LIMIT`] bytes.
+    pub fn exchange(&self) -> &Exchange {
+        &self.exchange
+    }
+
+    /// Checks whenever if the request or response body was truncated.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+}
+
+/// Every exchange that was recorded by a [`TestContext`][crate::TestContext], in the order that
+/// their responses were received.
+#[derive(Debug, Default)]
+pub struct History(Mutex<Vec<Record>>);
+
+impl History {
+    pub(crate) fn push(&self, record: Record) {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).push(record);
+    }
+
+    /// Returns a copy of every record.
+    pub fn records(&self) -> Vec<Record> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Returns the last record.
+    pub fn last(&self) -> Option<Record> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).last().cloned()
+    }
+
+    /// Returns how many exchanges were recorded.
+    pub fn len(&self) -> usize {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    /// Checks whenever if no exchanges were recorded.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes every record.
+    pub fn clear(&self) {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+
+    /// Renders the history as a [HAR 1.2](http://www.softwareishard.com/blog/har-12-spec) log, which can
+    /// be imported into the network tab of browser devtools and other HAR viewers.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use charted_testkit::TestContext;
+    /// #
+    /// let ctx = TestContext::default();
+    /// let har = ctx.history().to_har();
+    ///
+    /// assert_eq!(har["log"]["version"], "1.2");
+    /// assert_eq!(har["log"]["entries"].as_array().unwrap().len(), 0);
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn to_har(&self) -> serde_json::Value {
+        crate::har::render(&self.0.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// Writes the history into `path` as a HAR 1.2 log.
+    #[cfg(feature = "json")]
+    pub fn write_har<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        std::fs::write(path, serde_json::to_vec_pretty(&self.to_har())?)
+    }
+
+    /// Writes the history into `path` as text.
+    pub fn write_to<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        std::fs::write(path, self.to_string())
+    }
+}
+
+impl Display for History {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let records = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(first) = records.first().map(Record::started_at) else {
+            return f.write_str("no exchanges were recorded");
+        };
+
+        for (idx, record) in records.iter().enumerate() {
+            if idx > 0 {
+                f.write_str("\n\n")?;
+            }
+
+            let offset = record.started_at.duration_since(first).unwrap_or_default();
+            write!(
+                f,
+                "=== #{} {} {} (+{offset:?}, took {:?}",
+                idx + 1,
+                record.exchange.method(),
+                record.exchange.uri(),
+                record.duration()
+            )?;
+
+            if let Some(ref session) = record.session {
+                write!(f, ", session `{session}`")?;
+            }
+
+            if record.truncated {
+                write!(f, ", bodies truncated to {BODY_LIMIT} bytes")?;
+            }
+
+            writeln!(f, ") ===")?;
+            write!(f, "{}", record.exchange)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the directory that artifacts of the current test are written to.
+pub(crate) fn artifacts_dir(root: Option<&Path>) -> PathBuf {
+    let root = match root {
+        Some(root) => root.to_path_buf(),
+        None => match std::env::var_os("TESTKIT_ARTIFACTS_DIR") {
+            Some(dir) => PathBuf::from(dir),
+            None => std::env::var_os("CARGO_TARGET_DIR")
+                .map(PathBuf::from)
+                .or_else(target_dir)
+                .unwrap_or_else(|| PathBuf::from("target"))
+                .join("testkit-artifacts"),
+        },
+    };
+
+    root.join(test_name())
+}
+
+/// Returns the name of the file, without its extension, that the history of a context is
+/// written to in `dir`, which is unique for every context that writes into the same directory.
+pub(crate) fn file_stem(dir: &Path) -> String {
+    static WRITTEN: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+    let mut written = WRITTEN.lock().unwrap_or_else(|e| e.into_inner());
+    written.push(dir.to_path_buf());
+
+    match written.iter().filter(|written| *written == dir).count() {
+        1 => String::from("history"),
+        n => format!("history-{n}"),
+    }
+}
+
+/// Returns the target directory that the current executable was built into. `cargo test` runs
+/// tests in the directory of their package, so a relative `target` directory would be wrong for
+/// packages in a workspace.
+fn target_dir() -> Option<PathBuf> {
+    let exe = std::env::current_exe().ok()?;
+
+    // Cargo marks its target directories with a `CACHEDIR.TAG` file
+    exe.ancestors()
+        .skip(1)
+        .find(|dir| dir.join("CACHEDIR.TAG").is_file())
+        .map(Path::to_path_buf)
+}
+
+/// Returns the name of the current test, which the test harness uses as the name of the
+/// thread that runs it.
+fn test_name() -> String {
+    let name = match std::thread::current().name() {
+        Some(name) if name != "main" => name.to_owned(),
+        _ => format!("unknown-{}", std::process::id()),
+    };
+
+    name.replace("::", "__")
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{file_stem, target_dir, History, Record, Timings, BODY_LIMIT};
+    use crate::exchange::Exchange;
+    use axum::{
+        body::Bytes,
+        http::{HeaderMap, Method, StatusCode, Uri, Version},
+    };
+    use std::time::{Duration, SystemTime};
+
+    fn exchange(body: Bytes) -> Exchange {
+        Exchange {
+            method: Method::POST,
+            uri: Uri::from_static("http://127.0.0.1:3651/echo"),
+            version: Version::HTTP_11,
+            request_headers: HeaderMap::new(),
+            request_body: body.clone(),
+            status: StatusCode::OK,
+            response_headers: HeaderMap::new(),
+            response_body: Some(body),
+        }
+    }
+
+    #[test]
+    fn artifact_paths() {
+        // `cargo test` runs in `crates/testkit`, but the test executable is in the workspace's target directory
+        let target = target_dir().expect("test executable to be in a target directory");
+        assert!(std::env::current_exe().unwrap().starts_with(&target));
+        assert!(!target.starts_with(env!("CARGO_MANIFEST_DIR")));
+
+        let dir = std::env::temp_dir().join(format!("charted-testkit-stems-{}", std::process::id()));
+        assert_eq!(file_stem(&dir), "history");
+        assert_eq!(file_stem(&dir), "history-2");
+        assert_eq!(file_stem(&dir.join("other")), "history");
+    }
+
+    #[test]
+    fn truncation() {
+        let record = Record::new(
+            None,
+            SystemTime::now(),
+            Timings::default(),
+            &exchange(Bytes::from(vec![b'a'; BODY_LIMIT + 1])),
+        );
+
+        assert!(record.is_truncated());
+        assert_eq!(record.exchange().request_body().len(), BODY_LIMIT);
+        assert_eq!(record.exchange().response_body().unwrap().len(), BODY_LIMIT);
+
+        let record = Record::new(
+            None,
+            SystemTime::now(),
+            Timings::default(),
+            &exchange(Bytes::from_static(b"a")),
+        );
+        assert!(!record.is_truncated());
+    }
+
+    #[test]
+    fn rendering() {
+        let history = History::default();
+        assert_eq!(history.to_string(), "no exchanges were recorded");
+
+        let started_at = SystemTime::now();
+        let exchange = exchange(Bytes::from_static(b"hello"));
+        history.push(Record::new(
+            None,
+            started_at,
+            Timings {
+                wait: Duration::from_millis(2),
+                receive: Duration::ZERO,
+            },
+            &exchange,
+        ));
+        history.push(Record::new(
+            Some(String::from("alice")),
+            started_at + Duration::from_millis(5),
+            Timings {
+                wait: Duration::from_micros(500),
+                receive: Duration::from_micros(500),
+            },
+            &exchange,
+        ));
+
+        let rendered = history.to_string();
+        assert!(rendered.starts_with("=== #1 POST http://127.0.0.1:3651/echo (+0ns, took 2ms) ===\n--- request ---\n"));
+        assert!(rendered.contains("\n\n=== #2 POST http://127.0.0.1:3651/echo (+5ms, took 1ms, session `alice`) ===\n"));
+    }
+}
@@ -0,0 +1,618 @@
+// 📦🦋 charted TestKit: testing library for Axum services with testcontainers support
+// Copyright (c) 2024 Noelware, LLC. <team@noelware.org>
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Structural comparison of JSON bodies, used by [`assert_json_eq!`][crate::assert_json_eq] and
+//! [`assert_json_include!`][crate::assert_json_include].
+//!
+//! Differences are reported path-by-path, i.e. `.items[2].name: expected "a", got "b"`. Paths are
+//! also used to ignore values that can't be known ahead of time: a path is a list of `.key`, `[index]`
+//! segments where `*` matches any key or index (`.items[*].created_at`).
+//!
+//! Values that only need to have a certain shape can be matched with placeholders:
+//!
+//! ```rust
+//! use charted_testkit::json::{any_string, any_uuid, diff, Mode};
+//! use serde_json::json;
+//!
+//! let actual = json!({ "id": "9f0b6a5e-2d8c-4f6e-a8f7-5d1c6c1a2b3c", "name": "noel" });
+//! let expected = json!({ "id": any_uuid(), "name": any_string() });
+//!
+//! assert!(diff(&actual, &expected, Mode::Exact, &[]).is_empty());
+//! ```
+
+use axum::body::Bytes;
+use serde::Serialize;
+use serde_json::{Number, Value};
+use std::fmt::{Display, Write};
+
+/// Prefix of the strings that placeholders are encoded as, so that they can be embedded in a
+/// [`json!`][serde_json::json] value.
+const PLACEHOLDER_PREFIX: &str = "\u{0}charted-testkit:";
+
+/// Matches any value, as long as it is present.
+pub fn anything() -> Value {
+    placeholder("anything")
+}
+
+/// Matches any string.
+pub fn any_string() -> Value {
+    placeholder("any string")
+}
+
+/// Matches any number.
+pub fn any_number() -> Value {
+    placeholder("any number")
+}
+
+/// Matches any boolean.
+pub fn any_bool() -> Value {
+    placeholder("any boolean")
+}
+
+/// Matches any string that is a hyphenated UUID (`xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx`).
+pub fn any_uuid() -> Value {
+    placeholder("any UUID")
+}
+
+fn placeholder(name: &str) -> Value {
+    Value::String(format!("{PLACEHOLDER_PREFIX}{name}"))
+}
+
+/// How two JSON values are compared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Both values must be equal; objects can't have extra keys and arrays must have the same length.
+    Exact,
+
+    /// The actual value only needs to include the expected value: objects can have extra keys,
+    /// and arrays can have extra elements after the ones that are expected.
+    Include,
+}
+
+/// A difference between two JSON values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Difference {
+    path: String,
+    kind: DifferenceKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum DifferenceKind {
+    Mismatch { expected: Value, actual: Value },
+    Missing { expected: Value },
+    Unexpected { actual: Value },
+}
+
+impl Difference {
+    /// Path to the value that is different, i.e. `.items[2].name`.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+impl Display for Difference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.kind {
+            DifferenceKind::Mismatch { expected, actual } => write!(
+                f,
+                "{}: expected {}, got {}",
+                self.path,
+                DisplayValue(expected),
+                DisplayValue(actual)
+            ),
+
+            DifferenceKind::Missing { expected } => {
+                write!(
+                    f,
+                    "{}: expected {}, but it was missing",
+                    self.path,
+                    DisplayValue(expected)
+                )
+            }
+
+            DifferenceKind::Unexpected { actual } => {
+                write!(f, "{}: unexpected value {}", self.path, DisplayValue(actual))
+            }
+        }
+    }
+}
+
+/// Displays a value as compact JSON, or a placeholder by its name.
+struct DisplayValue<'a>(&'a Value);
+
+impl Display for DisplayValue<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0.as_str().and_then(|s| s.strip_prefix(PLACEHOLDER_PREFIX)) {
+            Some(name) => write!(f, "<{name}>"),
+            None => write!(f, "{}", self.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// Compares `actual` with `expected` and returns every difference between them. Values under
+/// any of the `ignore` paths are skipped.
+///
+/// ## Panics
+/// This will panic if any of the `ignore` paths are invalid.
+pub fn diff(actual: &Value, expected: &Value, mode: Mode, ignore: &[&str]) -> Vec<Difference> {
+    let ignore = ignore
+        .iter()
+        .map(|path| parse_path(path).unwrap_or_else(|| panic!("invalid JSON path to ignore: `{path}`")))
+        .collect::<Vec<_>>();
+
+    let mut differences = Vec::new();
+    compare(actual, expected, mode, &ignore, &mut Vec::new(), &mut differences);
+
+    differences
+}
+
+fn compare(
+    actual: &Value,
+    expected: &Value,
+    mode: Mode,
+    ignore: &[Vec<Option<Segment>>],
+    path: &mut Vec<Segment>,
+    differences: &mut Vec<Difference>,
+) {
+    if is_ignored(path, ignore) {
+        return;
+    }
+
+    if let Some(name) = expected.as_str().and_then(|s| s.strip_prefix(PLACEHOLDER_PREFIX)) {
+        if !matches_placeholder(name, actual) {
+            differences.push(Difference {
+                path: render_path(path),
+                kind: DifferenceKind::Mismatch {
+                    expected: expected.clone(),
+                    actual: actual.clone(),
+                },
+            });
+        }
+
+        return;
+    }
+
+    match (actual, expected) {
+        (Value::Object(actual), Value::Object(expected)) => {
+            for (key, expected) in expected {
+                path.push(Segment::Key(key.clone()));
+                match actual.get(key) {
+                    Some(actual) => compare(actual, expected, mode, ignore, path, differences),
+                    None if !is_ignored(path, ignore) => differences.push(Difference {
+                        path: render_path(path),
+                        kind: DifferenceKind::Missing {
+                            expected: expected.clone(),
+                        },
+                    }),
+
+                    None => {}
+                }
+
+                path.pop();
+            }
+
+            if mode == Mode::Exact {
+                for (key, actual) in actual.iter().filter(|(key, _)| !expected.contains_key(*key)) {
+                    path.push(Segment::Key(key.clone()));
+                    if !is_ignored(path, ignore) {
+                        differences.push(Difference {
+                            path: render_path(path),
+                            kind: DifferenceKind::Unexpected { actual: actual.clone() },
+                        });
+                    }
+
+                    path.pop();
+                }
+            }
+        }
+
+        (Value::Array(actual), Value::Array(expected)) => {
+            for (idx, expected) in expected.iter().enumerate() {
+                path.push(Segment::Index(idx));
+                match actual.get(idx) {
+                    Some(actual) => compare(actual, expected, mode, ignore, path, differences),
+                    None if !is_ignored(path, ignore) => differences.push(Difference {
+                        path: render_path(path),
+                        kind: DifferenceKind::Missing {
+                            expected: expected.clone(),
+                        },
+                    }),
+
+                    None => {}
+                }
+
+                path.pop();
+            }
+
+            if mode == Mode::Exact {
+                for (idx, actual) in actual.iter().enumerate().skip(expected.len()) {
+                    path.push(Segment::Index(idx));
+                    if !is_ignored(path, ignore) {
+                        differences.push(Difference {
+                            path: render_path(path),
+                            kind: DifferenceKind::Unexpected { actual: actual.clone() },
+                        });
+                    }
+
+                    path.pop();
+                }
+            }
+        }
+
+        (actual, expected) if actual == expected => {}
+
+        // `1` and `1.0` are different `Value`s, but they are the same number
+        (Value::Number(a), Value::Number(b)) if numbers_equal(a, b) => {}
+
+        (actual, expected) => differences.push(Difference {
+            path: render_path(path),
+            kind: DifferenceKind::Mismatch {
+                expected: expected.clone(),
+                actual: actual.clone(),
+            },
+        }),
+    }
+}
+
+/// Checks whenever if two numbers have the same value, so that `1` and `1.0` are equal. Integers
+/// are compared as integers, since large ones (like snowflake IDs) lose precision as a `f64`.
+pub(crate) fn numbers_equal(a: &Number, b: &Number) -> bool {
+    if a.is_f64() || b.is_f64() {
+        return a.as_f64() == b.as_f64();
+    }
+
+    match (a.as_i64(), b.as_i64()) {
+        (Some(a), Some(b)) => a == b,
+        _ => a.as_u64().is_some() && a.as_u64() == b.as_u64(),
+    }
+}
+
+fn matches_placeholder(name: &str, actual: &Value) -> bool {
+    match name {
+        "any string" => actual.is_string(),
+        "any number" => actual.is_number(),
+        "any boolean" => actual.is_boolean(),
+        "any UUID" => actual.as_str().is_some_and(crate::util::is_uuid),
+        _ => true,
+    }
+}
+
+/// Parses a path like `.items[*].name` into its segments, where `None` is a wildcard.
+fn parse_path(path: &str) -> Option<Vec<Option<Segment>>> {
+    let mut segments = Vec::new();
+    let mut rest = path.strip_prefix('$').unwrap_or(path);
+
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']')?;
+            let index = &after[..end];
+
+            segments.push(match index {
+                "*" => None,
+                _ => match index.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+                    Some(key) => Some(Segment::Key(key.to_owned())),
+                    None => Some(Segment::Index(index.parse().ok()?)),
+                },
+            });
+
+            rest = &after[end + 1..];
+        } else if let Some(after) = rest.strip_prefix('.') {
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            let key = &after[..end];
+
+            match key {
+                "" if end == after.len() && segments.is_empty() => {}
+                "" => return None,
+                "*" => segments.push(None),
+                _ => segments.push(Some(Segment::Key(key.to_owned()))),
+            }
+
+            rest = &after[end..];
+        } else {
+            return None;
+        }
+    }
+
+    Some(segments)
+}
+
+/// Selects every value at `path`, or returns `None` if `path` is invalid.
+pub(crate) fn select<'a>(value: &'a Value, path: &str) -> Option<Vec<&'a Value>> {
+    let mut selected = vec![value];
+    for segment in parse_path(path)? {
+        selected = selected
+            .into_iter()
+            .flat_map(|value| -> Vec<&Value> {
+                match (&segment, value) {
+                    (None, Value::Object(map)) => map.values().collect(),
+                    (None, Value::Array(values)) => values.iter().collect(),
+                    (Some(Segment::Key(key)), Value::Object(map)) => map.get(key).into_iter().collect(),
+                    (Some(Segment::Index(idx)), Value::Array(values)) => values.get(*idx).into_iter().collect(),
+                    _ => Vec::new(),
+                }
+            })
+            .collect();
+    }
+
+    Some(selected)
+}
+
+/// Mutable version of [`select`].
+pub(crate) fn select_mut<'a>(value: &'a mut Value, path: &str) -> Option<Vec<&'a mut Value>> {
+    let mut selected = vec![value];
+    for segment in parse_path(path)? {
+        selected = selected
+            .into_iter()
+            .flat_map(|value| -> Vec<&mut Value> {
+                match (&segment, value) {
+                    (None, Value::Object(map)) => map.values_mut().collect(),
+                    (None, Value::Array(values)) => values.iter_mut().collect(),
+                    (Some(Segment::Key(key)), Value::Object(map)) => map.get_mut(key).into_iter().collect(),
+                    (Some(Segment::Index(idx)), Value::Array(values)) => values.get_mut(*idx).into_iter().collect(),
+                    _ => Vec::new(),
+                }
+            })
+            .collect();
+    }
+
+    Some(selected)
+}
+
+fn is_ignored(path: &[Segment], ignore: &[Vec<Option<Segment>>]) -> bool {
+    ignore.iter().any(|pattern| {
+        pattern.len() == path.len()
+            && pattern
+                .iter()
+                .zip(path)
+                .all(|(pattern, segment)| pattern.as_ref().map_or(true, |p| p == segment))
+    })
+}
+
+fn render_path(path: &[Segment]) -> String {
+    if path.is_empty() {
+        return String::from(".");
+    }
+
+    let mut rendered = String::new();
+    for segment in path {
+        match segment {
+            Segment::Key(key)
+                if !key.is_empty() && key.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-') =>
+            {
+                let _ = write!(rendered, ".{key}");
+            }
+
+            Segment::Key(key) => {
+                let _ = write!(rendered, "[{}]", Value::String(key.clone()));
+            }
+
+            Segment::Index(idx) => {
+                let _ = write!(rendered, "[{idx}]");
+            }
+        }
+    }
+
+    rendered
+}
+
+#[doc(hidden)]
+#[track_caller]
+pub fn __assert_json<T: Serialize + ?Sized>(
+    body: Bytes,
+    expected: &T,
+    mode: Mode,
+    ignore: &[&str],
+    exchange: Option<&crate::exchange::Exchange>,
+) {
+    let expected = serde_json::to_value(expected).expect("expected value to be serializable as JSON");
+    let actual: Value = match serde_json::from_slice(&body) {
+        Ok(value) => value,
+        Err(err) => {
+            return crate::soft::__fail(format!(
+                "response body is not valid JSON: {err}\n\n{}",
+                describe(&body, exchange)
+            ))
+        }
+    };
+
+    let differences = diff(&actual, &expected, mode, ignore);
+    if differences.is_empty() {
+        return;
+    }
+
+    let mut message = String::from(match mode {
+        Mode::Exact => "JSON body doesn't equal the expected value:",
+        Mode::Include => "JSON body doesn't include the expected value:",
+    });
+
+    for difference in &differences {
+        let _ = write!(message, "\n    {difference}");
+    }
+
+    let _ = write!(message, "\n\n{}", describe(&body, exchange));
+    crate::soft::__fail(message);
+}
+
+fn describe(body: &[u8], exchange: Option<&crate::exchange::Exchange>) -> String {
+    match exchange {
+        Some(exchange) => exchange.with_body(body).to_string(),
+        None => format!("body: {}", crate::util::display_body(body)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rendered(actual: Value, expected: Value, mode: Mode, ignore: &[&str]) -> Vec<String> {
+        diff(&actual, &expected, mode, ignore)
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn exact_differences() {
+        let actual = json!({ "items": [{ "name": "a" }, { "name": "b" }, { "name": "b" }], "extra": true });
+        let expected = json!({ "items": [{ "name": "a" }, { "name": "b" }, { "name": "a" }], "total": 3 });
+
+        assert_eq!(
+            rendered(actual, expected, Mode::Exact, &[]),
+            [
+                r#".items[2].name: expected "a", got "b""#,
+                ".total: expected 3, but it was missing",
+                ".extra: unexpected value true",
+            ]
+        );
+
+        assert_eq!(
+            rendered(json!([1, 2, 3]), json!([1, 2]), Mode::Exact, &[]),
+            ["[2]: unexpected value 3"]
+        );
+
+        assert_eq!(rendered(json!(1), json!(1.0), Mode::Exact, &[]), Vec::<String>::new());
+        assert_eq!(
+            rendered(json!(1), json!("1"), Mode::Exact, &[]),
+            [r#".: expected "1", got 1"#]
+        );
+    }
+
+    #[test]
+    fn large_integers() {
+        assert_eq!(
+            rendered(
+                json!({ "id": 9_007_199_254_740_993_u64 }),
+                json!({ "id": 9_007_199_254_740_992_u64 }),
+                Mode::Exact,
+                &[]
+            ),
+            [".id: expected 9007199254740992, got 9007199254740993"]
+        );
+
+        assert_eq!(
+            rendered(json!(-1), json!(u64::MAX), Mode::Exact, &[]),
+            [".: expected 18446744073709551615, got -1"]
+        );
+
+        assert!(diff(&json!(u64::MAX), &json!(u64::MAX), Mode::Exact, &[]).is_empty());
+        assert!(diff(&json!(-2), &json!(-2.0), Mode::Exact, &[]).is_empty());
+    }
+
+    #[test]
+    fn include_differences() {
+        let actual = json!({ "id": 1, "tags": ["a", "b", "c"], "owner": { "name": "noel", "admin": false } });
+
+        assert!(diff(
+            &actual,
+            &json!({ "tags": ["a"], "owner": { "name": "noel" } }),
+            Mode::Include,
+            &[]
+        )
+        .is_empty());
+        assert_eq!(
+            rendered(
+                actual,
+                json!({ "owner": { "admin": true }, "tags": ["b"] }),
+                Mode::Include,
+                &[]
+            ),
+            [
+                ".owner.admin: expected true, got false",
+                r#".tags[0]: expected "b", got "a""#
+            ]
+        );
+    }
+
+    #[test]
+    fn ignored_paths() {
+        let actual = json!({ "items": [{ "id": 1, "created_at": "now" }, { "id": 2, "created_at": "later" }] });
+        let expected = json!({ "items": [{ "id": 1 }, { "id": 2 }] });
+
+        assert!(diff(&actual, &expected, Mode::Exact, &[".items[*].created_at"]).is_empty());
+        assert!(diff(&actual, &json!({}), Mode::Exact, &["$.items"]).is_empty());
+        assert!(diff(&actual, &json!({ "items": [] }), Mode::Exact, &[".items[*]"]).is_empty());
+        assert_eq!(
+            rendered(actual, expected, Mode::Exact, &[".items[0].created_at"]),
+            [r#".items[1].created_at: unexpected value "later""#]
+        );
+    }
+
+    #[test]
+    fn placeholders() {
+        let actual = json!({ "id": "9F0B6A5E-2D8C-4F6E-A8F7-5D1C6C1A2B3C", "name": "noel", "age": 1, "admin": false });
+        let expected = json!({ "id": any_uuid(), "name": any_string(), "age": any_number(), "admin": anything() });
+        assert!(diff(&actual, &expected, Mode::Exact, &[]).is_empty());
+
+        assert_eq!(
+            rendered(
+                json!({ "id": "1234", "name": 1 }),
+                json!({ "id": any_uuid(), "name": any_string(), "admin": any_bool() }),
+                Mode::Exact,
+                &[]
+            ),
+            [
+                ".admin: expected <any boolean>, but it was missing",
+                r#".id: expected <any UUID>, got "1234""#,
+                ".name: expected <any string>, got 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn selecting() {
+        let value = json!({ "items": [{ "name": "a" }, { "name": "b" }], "total": 2 });
+
+        assert_eq!(select(&value, "$.total"), Some(vec![&json!(2)]));
+        assert_eq!(select(&value, ".items[1].name"), Some(vec![&json!("b")]));
+        assert_eq!(select(&value, ".items[*].name"), Some(vec![&json!("a"), &json!("b")]));
+        assert_eq!(select(&value, ".missing[0]"), Some(vec![]));
+        assert_eq!(select(&value, "items"), None);
+    }
+
+    #[test]
+    fn paths() {
+        assert_eq!(parse_path("."), Some(vec![]));
+        assert_eq!(
+            parse_path(r#"$.items[*]["a key"].*"#),
+            Some(vec![
+                Some(Segment::Key("items".into())),
+                None,
+                Some(Segment::Key("a key".into())),
+                None
+            ])
+        );
+
+        assert_eq!(parse_path("items"), None);
+        assert_eq!(parse_path(".items[x]"), None);
+        assert_eq!(parse_path(".items..name"), None);
+        assert_eq!(
+            render_path(&[Segment::Key("a key".into()), Segment::Index(0)]),
+            r#"["a key"][0]"#
+        );
+    }
+}
@@ -25,24 +25,80 @@
 #[cfg(feature = "macros")]
 pub use charted_testkit_macros::*;
 
+#[cfg(any(feature = "serde", feature = "protobuf"))]
+pub mod codec;
+
+#[cfg(feature = "json")]
+pub mod cassette;
+pub mod compression;
+pub mod cookies;
+mod error;
+pub mod eventually;
+pub mod exchange;
+pub mod faults;
+#[cfg(feature = "json")]
+mod har;
+pub mod history;
+#[cfg(feature = "json")]
+pub mod json;
 mod macros;
+pub mod matchers;
+pub mod mock;
+#[cfg(feature = "openapi")]
+pub mod openapi;
+pub mod process;
+pub mod proxy;
+pub mod redirect;
+#[cfg(feature = "json")]
+pub mod scenario;
+#[cfg(feature = "json-schema")]
+pub mod schema;
+pub mod session;
+pub mod snapshot;
+pub mod soft;
+mod util;
+
+pub use error::Error;
+pub use headers;
 
-use axum::{body::Bytes, extract::Request, Router};
+use axum::{
+    body::{Body, Bytes},
+    extract::Request,
+    http::{header, HeaderMap, HeaderName, HeaderValue},
+    response::Response,
+    Router,
+};
+use cookies::CookieJar;
+use faults::Faults;
+use history::History;
 use http_body_util::Full;
 use hyper::{body::Incoming, Method};
 use hyper_util::{
-    client::legacy::{connect::HttpConnector, Client, ResponseFuture},
+    client::legacy::Client,
     rt::{TokioExecutor, TokioIo},
 };
-use std::{fmt::Debug, net::SocketAddr};
+use redirect::RedirectPolicy;
+use session::{Auth, Defaults, Session, Shared};
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    future::Future,
+    net::SocketAddr,
+    path::PathBuf,
+    sync::{Arc, Mutex, RwLockReadGuard, RwLockWriteGuard},
+    time::Duration,
+};
 use tokio::{net::TcpListener, task::JoinHandle};
 use tower::{Service, ServiceExt};
 
 pub struct TestContext {
     _handle: Option<JoinHandle<()>>,
-    client: Client<HttpConnector, http_body_util::Full<Bytes>>,
+    shared: Arc<Shared>,
+    session: Session,
+    sessions: Mutex<HashMap<String, Session>>,
+    artifacts_dir: Option<PathBuf>,
+    faults: Faults,
     http1: bool,
-    addr: Option<SocketAddr>,
 
     // TODO(@auguwu): should `containers` be a `HashMap<TypeId, Box<dyn Any>>` to easily
     //                identify a image?
@@ -55,17 +111,30 @@ pub struct TestContext {
 
 impl Debug for TestContext {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("TestContext").field("local_addr", &self.addr).finish()
+        f.debug_struct("TestContext")
+            .field("local_addr", &self.server_addr())
+            .finish()
     }
 }
 
 impl Default for TestContext {
     fn default() -> Self {
+        let shared = Arc::new(Shared {
+            client: Client::builder(TokioExecutor::new()).build_http(),
+            addr: Default::default(),
+            origin: Default::default(),
+            defaults: Default::default(),
+            history: History::default(),
+        });
+
         TestContext {
             _handle: None,
-            client: Client::builder(TokioExecutor::new()).build_http(),
+            session: Session::new(None, shared.clone(), None),
+            sessions: Mutex::default(),
+            artifacts_dir: None,
+            faults: Faults::default(),
             http1: true,
-            addr: None,
+            shared,
 
             #[cfg(feature = "testcontainers")]
             containers: Vec::new(),
@@ -77,6 +146,75 @@ impl Default for TestContext {
 }
 
 impl TestContext {
+    /// Creates a [`TestContext`] that sends requests to an already running service at `url`
+    /// rather than an ephemeral server, which is useful for smoke testing a deployed service.
+    /// See [`TestContext::set_base_url`] for how `url` is used.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use charted_testkit::TestContext;
+    /// #
+    /// let ctx = TestContext::from_base_url("http://localhost:3651/api/v1");
+    ///
+    /// assert!(ctx.server_addr().is_none());
+    /// assert_eq!(ctx.base_url(), Some("http://localhost:3651"));
+    /// assert_eq!(ctx.base_path().as_deref(), Some("/api/v1"));
+    /// ```
+    pub fn from_base_url<U: AsRef<str>>(url: U) -> TestContext {
+        let ctx = TestContext::default();
+        ctx.set_base_url(url);
+
+        ctx
+    }
+
+    /// Sends all requests to an already running service at `url` rather than an ephemeral server,
+    /// so that the same test can run against a separately started binary. The path of `url`, if any,
+    /// is used as the [base path][TestContext::set_base_path].
+    ///
+    /// The `#[charted_testkit::test]` macro calls this instead of [`TestContext::serve`] if the
+    /// [`TESTKIT_BASE_URL`][TestContext::external_base_url] environment variable is set.
+    ///
+    /// ## Panics
+    /// This will panic if `url` isn't an absolute `http://` URL, as only plain-text HTTP is supported,
+    /// or if the ephemeral server was already served or a base URL was already set.
+    pub fn set_base_url<U: AsRef<str>>(&self, url: U) {
+        let url = url.as_ref();
+        let uri = url
+            .parse::<axum::http::Uri>()
+            .unwrap_or_else(|e| panic!("invalid base URL `{url}`: {e}"));
+
+        if uri.scheme_str() != Some("http") {
+            panic!("invalid base URL `{url}`: only `http://` URLs are supported");
+        }
+
+        let authority = uri
+            .authority()
+            .unwrap_or_else(|| panic!("invalid base URL `{url}`: missing host"));
+
+        self.shared
+            .origin
+            .set(format!("http://{authority}"))
+            .expect("ephermeral server was already served or a base URL was set");
+
+        let path = uri.path().trim_end_matches('/');
+        if !path.is_empty() {
+            self.set_base_path(Some(path));
+        }
+    }
+
+    /// Returns the value of the `TESTKIT_BASE_URL` environment variable, which points tests that
+    /// use the `#[charted_testkit::test]` macro at an already running service rather than the
+    /// ephemeral server. An empty value is the same as it not being set.
+    pub fn external_base_url() -> Option<String> {
+        std::env::var("TESTKIT_BASE_URL").ok().filter(|url| !url.is_empty())
+    }
+
+    /// Returns the scheme and authority that requests are sent to, if [`TestContext::serve`] or
+    /// [`TestContext::set_base_url`] was called.
+    pub fn base_url(&self) -> Option<&str> {
+        self.shared.origin.get().map(String::as_str)
+    }
+
     /// Allows HTTP/1 connections to be used. By disabling this, the ephermeral TCP listener
     /// won't know what to do unless HTTP/2 connections are allowed.
     pub fn allow_http1(mut self, yes: bool) -> Self {
@@ -91,6 +229,256 @@ impl TestContext {
         self
     }
 
+    /// Enables a persistent [cookie jar][CookieJar] that stores cookies from `Set-Cookie` headers and
+    /// sends them on all subsequent requests made with [`TestContext::request`].
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use charted_testkit::TestContext;
+    /// #
+    /// let ctx = TestContext::default().cookie_jar(true);
+    /// assert!(ctx.cookies().is_some());
+    /// ```
+    pub fn cookie_jar(mut self, yes: bool) -> Self {
+        let cookies = match yes {
+            true => Some(self.cookies().cloned().unwrap_or_default()),
+            false => None,
+        };
+
+        self.session = Session::new(None, self.shared.clone(), cookies);
+        self
+    }
+
+    /// Returns the [cookie jar][CookieJar] if it was enabled with [`TestContext::cookie_jar`], which
+    /// can be used to inspect, insert and clear cookies.
+    pub fn cookies(&self) -> Option<&CookieJar> {
+        self.session.cookies()
+    }
+
+    /// Sets the directory that artifacts, like the [`History`] of a test that panicked, are written
+    /// to. See the [`history`] module for the default directory.
+    pub fn artifacts_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.artifacts_dir = Some(dir.into());
+        self
+    }
+
+    /// Returns the [faults][faults::Fault] that are injected into the responses of the ephemeral
+    /// server, which can be changed while it is serving.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use charted_testkit::{TestContext, faults::Fault};
+    /// # use std::time::Duration;
+    /// #
+    /// let ctx = TestContext::default();
+    /// ctx.faults().add(Fault::latency(Duration::from_millis(100)).on("/v1/*"));
+    ///
+    /// assert_eq!(ctx.faults().len(), 1);
+    /// ```
+    pub fn faults(&self) -> &Faults {
+        &self.faults
+    }
+
+    /// Returns every exchange that was sent with this context and its sessions.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use charted_testkit::TestContext;
+    /// #
+    /// let ctx = TestContext::default();
+    /// assert!(ctx.history().is_empty());
+    /// ```
+    pub fn history(&self) -> &History {
+        &self.shared.history
+    }
+
+    /// Sets a header that is sent on every request of every session, unless a session or the
+    /// request itself sets the same header.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use charted_testkit::TestContext;
+    /// # use axum::http::{header, HeaderValue};
+    /// #
+    /// let ctx = TestContext::default();
+    /// ctx.set_default_header(header::USER_AGENT, HeaderValue::from_static("charted-testkit"));
+    ///
+    /// assert!(ctx.default_headers().contains_key(header::USER_AGENT));
+    /// ```
+    pub fn set_default_header(&self, name: HeaderName, value: HeaderValue) {
+        self.defaults_mut().headers.insert(name, value);
+    }
+
+    /// Removes a header that was set with [`TestContext::set_default_header`].
+    pub fn remove_default_header(&self, name: HeaderName) {
+        self.defaults_mut().headers.remove(name);
+    }
+
+    /// Returns a copy of the headers that are sent on every request of every session.
+    pub fn default_headers(&self) -> HeaderMap {
+        self.defaults().headers.clone()
+    }
+
+    /// Sets the base path (i.e, `/api/v1`) that is prefixed to the path of every request of every
+    /// session, unless a session sets its own base path. Passing `None` removes it.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use charted_testkit::TestContext;
+    /// #
+    /// let ctx = TestContext::default();
+    /// ctx.set_base_path(Some("/api/v1"));
+    ///
+    /// assert_eq!(ctx.base_path().as_deref(), Some("/api/v1"));
+    /// ```
+    pub fn set_base_path<P: Into<String>>(&self, path: Option<P>) {
+        self.defaults_mut().base_path = path.map(Into::into);
+    }
+
+    /// Returns the base path that was set with [`TestContext::set_base_path`].
+    pub fn base_path(&self) -> Option<String> {
+        self.defaults().base_path.clone()
+    }
+
+    /// Sets the authentication scheme that is sent on every request of every session, unless a session
+    /// sets its own scheme or the request sets the `Authorization` header. Passing `None` removes it.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use charted_testkit::{TestContext, session::Auth};
+    /// #
+    /// let ctx = TestContext::default();
+    /// ctx.set_auth(Auth::bearer("some token"));
+    ///
+    /// assert!(ctx.auth().is_some());
+    /// ```
+    pub fn set_auth<A: Into<Option<Auth>>>(&self, auth: A) {
+        self.defaults_mut().auth = auth.into();
+    }
+
+    /// Returns the authentication scheme that was set with [`TestContext::set_auth`].
+    pub fn auth(&self) -> Option<Auth> {
+        self.defaults().auth.clone()
+    }
+
+    /// Sets the [`RedirectPolicy`] that is used to follow redirects for every session. By default,
+    /// redirects are not followed. Passing `None` disables following redirects. Every hop of a
+    /// followed redirect is recorded in the [history][TestContext::history] as its own exchange.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use charted_testkit::{TestContext, redirect::RedirectPolicy};
+    /// #
+    /// let ctx = TestContext::default();
+    /// ctx.set_redirect_policy(RedirectPolicy::limited(5).same_origin_only(true));
+    ///
+    /// assert_eq!(ctx.redirect_policy().unwrap().max_hops(), 5);
+    /// ```
+    pub fn set_redirect_policy<P: Into<Option<RedirectPolicy>>>(&self, policy: P) {
+        self.defaults_mut().redirect = policy.into();
+    }
+
+    /// Returns the [`RedirectPolicy`] that was set with [`TestContext::set_redirect_policy`].
+    pub fn redirect_policy(&self) -> Option<RedirectPolicy> {
+        self.defaults().redirect.clone()
+    }
+
+    /// Sets the `Accept-Encoding` header that is sent on every request of every session. Passing `None`
+    /// removes it. This is the same as calling [`TestContext::set_default_header`] with `Accept-Encoding`.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use charted_testkit::TestContext;
+    /// # use axum::http::{header, HeaderValue};
+    /// #
+    /// let ctx = TestContext::default();
+    /// ctx.set_accept_encoding(HeaderValue::from_static("br, gzip"));
+    ///
+    /// assert_eq!(ctx.default_headers().get(header::ACCEPT_ENCODING).unwrap(), "br, gzip");
+    /// ```
+    pub fn set_accept_encoding<V: Into<Option<HeaderValue>>>(&self, value: V) {
+        match value.into() {
+            Some(value) => self.set_default_header(header::ACCEPT_ENCODING, value),
+            None => self.remove_default_header(header::ACCEPT_ENCODING),
+        }
+    }
+
+    /// Decompresses response bodies that were compressed with an encoding whose `decompression-*` crate
+    /// feature is enabled. The `Content-Encoding` and `Content-Length` headers of decompressed responses
+    /// are removed, but the encoding is still available with [`WireEncoding`][compression::WireEncoding].
+    pub fn set_auto_decompress(&self, yes: bool) {
+        self.defaults_mut().decompress = yes;
+    }
+
+    /// Checks whenever if response bodies are decompressed.
+    pub fn auto_decompress(&self) -> bool {
+        self.defaults().decompress
+    }
+
+    /// Buffers response bodies so that they can be printed when an assertion fails and are
+    /// recorded in the [`History`]. This is disabled by default, since a request doesn't complete
+    /// until the whole body was received, which never happens for endpoints that stream their
+    /// responses. Response bodies are always buffered while an OpenAPI document is set with
+    /// `TestContext::set_openapi`, so that they can be validated.
+    pub fn set_buffer_responses(&self, yes: bool) {
+        self.defaults_mut().buffer = yes;
+    }
+
+    /// Checks whenever if response bodies are buffered.
+    pub fn buffer_responses(&self) -> bool {
+        self.defaults().buffer
+    }
+
+    /// Validates every response against an OpenAPI document, failing the test if the response
+    /// doesn't match the documented operation. See the [`openapi`] module for what is validated.
+    ///
+    /// ## Example
+    /// ```rust,no_run
+    /// # use charted_testkit::{TestContext, openapi::OpenApiSpec};
+    /// #
+    /// let ctx = TestContext::default();
+    /// ctx.set_openapi(OpenApiSpec::from_file("assets/openapi.yaml").unwrap());
+    /// ```
+    #[cfg(feature = "openapi")]
+    pub fn set_openapi<S: Into<Option<openapi::OpenApiSpec>>>(&self, spec: S) {
+        self.defaults_mut().openapi = spec.into().map(std::sync::Arc::new);
+    }
+
+    fn defaults(&self) -> RwLockReadGuard<'_, Defaults> {
+        self.shared.defaults.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn defaults_mut(&self) -> RwLockWriteGuard<'_, Defaults> {
+        self.shared.defaults.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Returns a named [`Session`] that sends requests to the same ephemeral server, but with its own
+    /// default headers, authentication and cookie jar. The session is created on first use and the same
+    /// session is returned for the same name afterwards.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use charted_testkit::{TestContext, session::Auth};
+    /// #
+    /// let ctx = TestContext::default();
+    /// ctx.client("alice").set_auth(Auth::bearer("alice's token"));
+    ///
+    /// assert!(ctx.client("alice").auth().is_some());
+    /// assert!(ctx.client("bob").auth().is_none());
+    /// ```
+    pub fn client<N: AsRef<str>>(&self, name: N) -> Session {
+        let name = name.as_ref();
+        let mut sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(session) = sessions.get(name) {
+            return session.clone();
+        }
+
+        let session = Session::new(Some(name.to_owned()), self.shared.clone(), Some(CookieJar::default()));
+        sessions.insert(name.to_owned(), session.clone());
+
+        session
+    }
+
     /// Checks whenever if the ephermeral TCP listener should allow both HTTP/1 and HTTP/2 connections.
     #[cfg(feature = "http2")]
     pub fn allows_both(&self) -> bool {
@@ -139,10 +527,18 @@ impl TestContext {
     /// # };
     /// ```
     pub fn server_addr(&self) -> Option<&SocketAddr> {
-        self.addr.as_ref()
+        self.shared.addr.get()
     }
 
-    /// Sends a request to the ephemeral server and returns a [`ResponseFuture`].
+    /// Sends a request to the ephemeral server and returns a future that resolves to its response.
+    ///
+    /// The [base path][TestContext::set_base_path], [default headers][TestContext::set_default_header] and
+    /// [authentication scheme][TestContext::set_auth] are applied before `build` is called, so they can be
+    /// overwritten per request. If a [redirect policy][TestContext::set_redirect_policy] is set, redirects
+    /// are followed and the returned response is the final response.
+    ///
+    /// If the [cookie jar][TestContext::cookie_jar] is enabled, the `Cookie` header is set before `build`
+    /// is called, so it can be overwritten, and cookies from the response are stored in the jar.
     ///
     /// ## Example
     /// ```no_run
@@ -173,17 +569,64 @@ impl TestContext {
         method: Method,
         body: B,
         build: F,
-    ) -> ResponseFuture {
-        let addr = self.server_addr().expect("failed to get socket address");
+    ) -> impl Future<Output = Result<Response<Body>, Error>> + Send + 'static {
+        self.session.request(uri, method, body, build)
+    }
+
+    /// Polls `path` with `GET` requests until it responds with a successful status code, which is
+    /// useful after starting containers or a [process][process::Process]. If it doesn't respond
+    /// successfully within `timeout`, the assertion fails with the last response or error.
+    ///
+    /// See [`eventually!`] to poll for anything else.
+    ///
+    /// ## Example
+    /// ```no_run
+    /// # use charted_testkit::TestContext;
+    /// # use std::time::Duration;
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let ctx = TestContext::from_base_url("http://localhost:3651");
+    /// ctx.wait_until_ready("/heartbeat", Duration::from_secs(30)).await;
+    /// # }
+    /// ```
+    pub async fn wait_until_ready<P: AsRef<str>>(&self, path: P, timeout: Duration) {
+        let path = path.as_ref();
+        let mut attempts = eventually::Eventually::default().timeout(timeout).__start();
+        let mut last = None;
 
-        let mut req = Request::<Full<Bytes>>::new(Full::new(body.into().unwrap_or_default()));
-        *req.method_mut() = method;
-        *req.uri_mut() = format!("http://{addr}{}", uri.as_ref())
-            .parse()
-            .expect("failed to parse into `hyper::Uri`");
+        loop {
+            // a server that accepts connections but never answers would otherwise hang forever
+            let remaining = attempts.remaining();
+            let request = self.request(path.to_owned(), Method::GET, None, noop_request);
 
-        build(&mut req);
-        self.client.request(req)
+            match tokio::time::timeout(remaining, request).await {
+                Ok(Ok(res)) if res.status().is_success() => return,
+                Ok(Ok(res)) => {
+                    last = Some(match exchange::Exchange::of(&res) {
+                        Some(exchange) => format!("{}\n\n{exchange}", res.status()),
+                        None => res.status().to_string(),
+                    });
+                }
+
+                Ok(Err(err)) => last = Some(err.to_string()),
+
+                // keep what an earlier attempt observed if time ran out during the last one
+                Err(_) => {
+                    last.get_or_insert_with(|| format!("no response within {remaining:?}"));
+                }
+            }
+
+            if !attempts.wait().await {
+                soft::__fail(format!(
+                    "expected `{path}` to respond successfully within {timeout:?}, but it didn't after {} attempts\n\nlast observed: {}",
+                    attempts.count(),
+                    last.unwrap_or_default()
+                ));
+
+                return;
+            }
+        }
     }
 
     /// Serves the ephermeral server.
@@ -205,7 +648,21 @@ impl TestContext {
             .await
             .expect("failed to create tcp listener");
 
-        self.addr = Some(listener.local_addr().expect("unable to get local addr"));
+        let addr = listener.local_addr().expect("unable to get local addr");
+        self.shared
+            .origin
+            .set(format!("http://{addr}"))
+            .expect("ephermeral server was already served or a base URL was set");
+
+        self.shared
+            .addr
+            .set(addr)
+            .expect("ephermeral server was already served");
+
+        let router = router.layer(axum::middleware::from_fn_with_state(
+            self.faults.clone(),
+            faults::inject,
+        ));
 
         // based off https://github.com/tokio-rs/axum/blob/934b1aac067dba596feb617817409345f9835db5/examples/serve-with-hyper/src/main.rs#L79-L118
         // since we don't need `axum::serve` and we want to customise the HTTP transport to use (i.e, if you want
@@ -265,6 +722,35 @@ impl TestContext {
     }
 }
 
+impl Drop for TestContext {
+    fn drop(&mut self) {
+        if !std::thread::panicking() || self.history().is_empty() {
+            return;
+        }
+
+        let dir = history::artifacts_dir(self.artifacts_dir.as_deref());
+        let stem = history::file_stem(&dir);
+        let path = dir.join(format!("{stem}.txt"));
+        match self.history().write_to(&path) {
+            Ok(()) => eprintln!(
+                "charted-testkit: wrote {} recorded exchanges to {}",
+                self.history().len(),
+                path.display()
+            ),
+
+            Err(err) => eprintln!("charted-testkit: failed to write history to {}: {err}", path.display()),
+        }
+
+        #[cfg(feature = "json")]
+        {
+            let path = dir.join(format!("{stem}.har"));
+            if let Err(err) = self.history().write_har(&path) {
+                eprintln!("charted-testkit: failed to write HAR to {}: {err}", path.display());
+            }
+        }
+    }
+}
+
 /// A empty function that can be used with [`TestContext::request`].
 pub fn noop_request(_: &mut Request<Full<Bytes>>) {
     // should be empty.
@@ -273,22 +759,152 @@ pub fn noop_request(_: &mut Request<Full<Bytes>>) {
 // Private APIs used by macros; do not use!
 #[doc(hidden)]
 pub mod __private {
+    pub use crate::soft::__fail as fail;
     pub use axum::http::header;
     pub use http_body_util::BodyExt;
+    pub use regex::Regex;
+
+    use axum::http::{HeaderMap, HeaderName, Response};
+    use std::fmt::Debug;
+
+    /// Reports a failed assertion about `res`, including its [`Exchange`][crate::exchange::Exchange] if it has one.
+    #[track_caller]
+    pub fn fail_for<B>(res: &Response<B>, message: String) {
+        match crate::exchange::Exchange::of(res) {
+            Some(exchange) => fail(format!("{message}\n\n{exchange}")),
+            None => fail(message),
+        }
+    }
+
+    #[track_caller]
+    pub fn header_name<K>(name: K) -> HeaderName
+    where
+        K: TryInto<HeaderName>,
+        K::Error: Debug,
+    {
+        name.try_into().expect("header name to be valid")
+    }
+
+    pub fn header_str<'a>(headers: &'a HeaderMap, name: &HeaderName) -> Result<&'a str, String> {
+        let Some(value) = headers.get(name) else {
+            return Err(format!("expected header `{name}` to be present, but it was missing"));
+        };
+
+        value
+            .to_str()
+            .map_err(|_| format!("header `{name}` has a value that isn't visible ASCII: {value:?}"))
+    }
+
+    pub fn header_values<'a>(headers: &'a HeaderMap, name: &HeaderName) -> Result<Vec<&'a str>, String> {
+        headers
+            .get_all(name)
+            .iter()
+            .map(|value| {
+                value
+                    .to_str()
+                    .map_err(|_| format!("header `{name}` has a value that isn't visible ASCII: {value:?}"))
+            })
+            .collect()
+    }
+
+    /// Decodes the typed header of the same type as `_expected`.
+    pub fn typed_header_like<H: headers::Header>(headers: &HeaderMap, _expected: &H) -> Result<Option<H>, String> {
+        let mut values = headers.get_all(H::name()).iter().peekable();
+        if values.peek().is_none() {
+            return Ok(None);
+        }
+
+        H::decode(&mut values)
+            .map(Some)
+            .map_err(|_| format!("header `{}` couldn't be decoded", H::name()))
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{assert_successful, consume_body, TestContext};
-    use axum::{body::Bytes, routing, Router};
+    use crate::{
+        assert_successful, consume_body,
+        redirect::{RedirectChain, RedirectPolicy},
+        session::Auth,
+        Error, TestContext,
+    };
+    use axum::{
+        body::Bytes,
+        http::{header, HeaderMap, HeaderValue, StatusCode},
+        routing, Router,
+    };
     use hyper::Method;
 
     async fn hello() -> &'static str {
         "Hello, world!"
     }
 
+    async fn login() -> [(header::HeaderName, &'static str); 1] {
+        [(header::SET_COOKIE, "session=weow; Path=/; HttpOnly")]
+    }
+
+    async fn whoami(headers: HeaderMap) -> String {
+        headers
+            .get(header::AUTHORIZATION)
+            .map(|value| value.to_str().unwrap().to_owned())
+            .unwrap_or_default()
+    }
+
+    async fn user_agent(headers: HeaderMap) -> String {
+        headers
+            .get(header::USER_AGENT)
+            .map(|value| value.to_str().unwrap().to_owned())
+            .unwrap_or_default()
+    }
+
+    async fn echo(method: Method, body: Bytes) -> String {
+        format!("{method} {}", String::from_utf8_lossy(&body))
+    }
+
+    fn redirect(status: StatusCode, location: &'static str) -> (StatusCode, [(header::HeaderName, &'static str); 1]) {
+        (status, [(header::LOCATION, location)])
+    }
+
+    async fn me(headers: HeaderMap) -> String {
+        headers
+            .get(header::COOKIE)
+            .map(|value| value.to_str().unwrap().to_owned())
+            .unwrap_or_default()
+    }
+
     fn router() -> Router {
-        Router::new().route("/", routing::get(hello))
+        Router::new()
+            .route("/", routing::get(hello))
+            .route("/login", routing::post(login))
+            .route("/me", routing::get(me))
+            .route("/whoami", routing::get(whoami))
+            .route("/echo", routing::any(echo))
+            .route(
+                "/redirect/found",
+                routing::any(|| async { redirect(StatusCode::FOUND, "see-other") }),
+            )
+            .route(
+                "/redirect/see-other",
+                routing::any(|| async { redirect(StatusCode::SEE_OTHER, "/echo") }),
+            )
+            .route(
+                "/redirect/temporary",
+                routing::any(|| async { redirect(StatusCode::TEMPORARY_REDIRECT, "/echo") }),
+            )
+            .route(
+                "/redirect/loop",
+                routing::any(|| async { redirect(StatusCode::FOUND, "/redirect/loop") }),
+            )
+            .route(
+                "/redirect/external",
+                routing::any(|| async { redirect(StatusCode::FOUND, "http://charts.dev/") }),
+            )
+            .nest(
+                "/api/v1",
+                Router::new()
+                    .route("/whoami", routing::get(whoami))
+                    .route("/user-agent", routing::get(user_agent)),
+            )
     }
 
     #[tokio::test]
@@ -309,6 +925,673 @@ mod tests {
         assert_eq!(consume_body!(res), Bytes::from_static(b"Hello, world!"));
     }
 
+    #[tokio::test]
+    #[cfg_attr(
+        windows,
+        ignore = "fails on Windows: hyper_util::client::legacy::Error(Connect, ConnectError(\"tcp connect error\", Os { code: 10049, kind: AddrNotAvailable, message: \"The requested address is not valid in its context.\" })))"
+    )]
+    async fn test_base_url() {
+        let mut server = TestContext::default();
+        server.serve(router()).await;
+
+        let addr = server.server_addr().unwrap();
+        let ctx = TestContext::from_base_url(format!("http://{addr}/api/v1/"));
+        assert_eq!(ctx.base_url(), Some(format!("http://{addr}").as_str()));
+        assert_eq!(ctx.base_path().as_deref(), Some("/api/v1"));
+
+        ctx.set_auth(Some(Auth::Bearer("token".into())));
+        let res = ctx
+            .request("/whoami", Method::GET, None, super::noop_request)
+            .await
+            .expect("unable to send request");
+
+        assert_successful!(res);
+        assert_eq!(consume_body!(res), Bytes::from_static(b"Bearer token"));
+    }
+
+    #[test]
+    #[should_panic(expected = "only `http://` URLs are supported")]
+    fn test_base_url_rejects_https() {
+        TestContext::from_base_url("https://charts.noelware.org");
+    }
+
+    #[test]
+    #[should_panic(expected = "a base URL was set")]
+    fn test_base_url_can_only_be_set_once() {
+        let ctx = TestContext::from_base_url("http://localhost:3651");
+        ctx.set_base_url("http://localhost:3652");
+    }
+
+    fn warming_up(ready_after: usize) -> Router {
+        let polls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        Router::new().route(
+            "/healthz",
+            routing::get(move || async move {
+                match polls.fetch_add(1, std::sync::atomic::Ordering::SeqCst) < ready_after {
+                    true => (StatusCode::SERVICE_UNAVAILABLE, "warming up"),
+                    false => (StatusCode::OK, "ok"),
+                }
+            }),
+        )
+    }
+
+    #[tokio::test]
+    #[cfg_attr(
+        windows,
+        ignore = "fails on Windows: hyper_util::client::legacy::Error(Connect, ConnectError(\"tcp connect error\", Os { code: 10049, kind: AddrNotAvailable, message: \"The requested address is not valid in its context.\" })))"
+    )]
+    async fn test_wait_until_ready() {
+        let mut ctx = TestContext::default();
+        ctx.serve(warming_up(2)).await;
+
+        ctx.wait_until_ready("/healthz", std::time::Duration::from_secs(5))
+            .await;
+        assert_eq!(ctx.history().len(), 3);
+    }
+
+    #[tokio::test]
+    #[cfg_attr(
+        windows,
+        ignore = "fails on Windows: hyper_util::client::legacy::Error(Connect, ConnectError(\"tcp connect error\", Os { code: 10049, kind: AddrNotAvailable, message: \"The requested address is not valid in its context.\" })))"
+    )]
+    #[should_panic(expected = "last observed: 503 Service Unavailable")]
+    async fn test_wait_until_ready_times_out() {
+        let mut ctx = TestContext::default();
+        ctx.serve(warming_up(usize::MAX)).await;
+
+        ctx.wait_until_ready("/healthz", std::time::Duration::from_millis(250))
+            .await;
+    }
+
+    #[tokio::test]
+    #[cfg_attr(
+        windows,
+        ignore = "fails on Windows: hyper_util::client::legacy::Error(Connect, ConnectError(\"tcp connect error\", Os { code: 10049, kind: AddrNotAvailable, message: \"The requested address is not valid in its context.\" })))"
+    )]
+    #[should_panic(expected = "last observed: no response within")]
+    async fn test_wait_until_ready_unresponsive() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // accepts connections, but never answers them
+        tokio::spawn(async move {
+            let mut connections = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                connections.push(stream);
+            }
+        });
+
+        let ctx = TestContext::from_base_url(format!("http://{addr}"));
+        ctx.wait_until_ready("/healthz", std::time::Duration::from_millis(250))
+            .await;
+    }
+
+    #[tokio::test]
+    #[cfg_attr(
+        windows,
+        ignore = "fails on Windows: hyper_util::client::legacy::Error(Connect, ConnectError(\"tcp connect error\", Os { code: 10049, kind: AddrNotAvailable, message: \"The requested address is not valid in its context.\" })))"
+    )]
+    async fn test_faults() {
+        use crate::faults::Fault;
+        use std::time::{Duration, Instant};
+
+        let mut ctx = TestContext::default();
+        ctx.serve(router().route("/data", routing::get(|| async { "a".repeat(100) })))
+            .await;
+
+        // so that responses whose body is cut off fail
+        ctx.set_buffer_responses(true);
+
+        let latency = ctx.faults().add(Fault::latency(Duration::from_millis(200)).on("/data"));
+        let started = Instant::now();
+        let res = ctx
+            .request("/data", Method::GET, None, super::noop_request)
+            .await
+            .unwrap();
+
+        assert_successful!(res);
+        assert!(started.elapsed() >= Duration::from_millis(200));
+
+        let started = Instant::now();
+        ctx.request("/", Method::GET, None, super::noop_request).await.unwrap();
+        assert!(started.elapsed() < Duration::from_millis(200));
+        ctx.faults().remove(latency);
+
+        let error = ctx.faults().add(Fault::error(StatusCode::SERVICE_UNAVAILABLE, 1.0));
+        let res = ctx
+            .request("/data", Method::GET, None, super::noop_request)
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        ctx.faults().remove(error);
+
+        for fault in [Fault::reset_after(10), Fault::truncate_after(10)] {
+            let id = ctx.faults().add(fault);
+            ctx.request("/data", Method::GET, None, super::noop_request)
+                .await
+                .expect_err("body should be cut off");
+
+            ctx.faults().remove(id);
+        }
+
+        ctx.faults()
+            .add(Fault::slow_write(Duration::from_millis(2)).on("/data"));
+        let started = Instant::now();
+        let res = ctx
+            .request("/data", Method::GET, None, super::noop_request)
+            .await
+            .unwrap();
+
+        assert_eq!(consume_body!(res), Bytes::from("a".repeat(100)));
+        assert!(started.elapsed() >= Duration::from_millis(200));
+    }
+
+    #[tokio::test]
+    #[cfg_attr(
+        windows,
+        ignore = "fails on Windows: hyper_util::client::legacy::Error(Connect, ConnectError(\"tcp connect error\", Os { code: 10049, kind: AddrNotAvailable, message: \"The requested address is not valid in its context.\" })))"
+    )]
+    async fn test_cookie_jar() {
+        let mut ctx = TestContext::default().cookie_jar(true);
+        ctx.serve(router()).await;
+
+        let res = ctx
+            .request("/login", Method::POST, None, super::noop_request)
+            .await
+            .expect("unable to send request");
+
+        assert_successful!(res);
+        assert_eq!(ctx.cookies().unwrap().get("session").unwrap().value(), "weow");
+
+        let res = ctx
+            .request("/me", Method::GET, None, super::noop_request)
+            .await
+            .expect("unable to send request");
+
+        assert_eq!(consume_body!(res), Bytes::from_static(b"session=weow"));
+
+        ctx.cookies().unwrap().clear();
+        let res = ctx
+            .request("/me", Method::GET, None, super::noop_request)
+            .await
+            .expect("unable to send request");
+
+        assert_eq!(consume_body!(res), Bytes::new());
+    }
+
+    #[tokio::test]
+    #[cfg_attr(
+        windows,
+        ignore = "fails on Windows: hyper_util::client::legacy::Error(Connect, ConnectError(\"tcp connect error\", Os { code: 10049, kind: AddrNotAvailable, message: \"The requested address is not valid in its context.\" })))"
+    )]
+    async fn test_named_sessions() {
+        let mut ctx = TestContext::default();
+        ctx.serve(router()).await;
+
+        let alice = ctx.client("alice");
+        alice.set_auth(Auth::bearer("alice"));
+
+        let bob = ctx.client("bob");
+        bob.set_auth(Auth::bearer("bob"));
+
+        let res = alice
+            .request("/login", Method::POST, None, super::noop_request)
+            .await
+            .expect("unable to send request");
+
+        assert_successful!(res);
+        assert!(alice.cookies().unwrap().get("session").is_some());
+        assert!(bob.cookies().unwrap().get("session").is_none());
+
+        let res = ctx
+            .client("bob")
+            .request("/whoami", Method::GET, None, super::noop_request)
+            .await
+            .expect("unable to send request");
+
+        assert_eq!(consume_body!(res), Bytes::from_static(b"Bearer bob"));
+
+        // the default session doesn't inherit anything from named sessions
+        let res = ctx
+            .request("/whoami", Method::GET, None, super::noop_request)
+            .await
+            .expect("unable to send request");
+
+        assert_eq!(consume_body!(res), Bytes::new());
+    }
+
+    #[tokio::test]
+    #[cfg_attr(
+        windows,
+        ignore = "fails on Windows: hyper_util::client::legacy::Error(Connect, ConnectError(\"tcp connect error\", Os { code: 10049, kind: AddrNotAvailable, message: \"The requested address is not valid in its context.\" })))"
+    )]
+    async fn test_context_defaults() {
+        let mut ctx = TestContext::default();
+        ctx.serve(router()).await;
+
+        ctx.set_base_path(Some("/api/v1"));
+        ctx.set_auth(Auth::bearer("ctx"));
+        ctx.set_default_header(header::USER_AGENT, HeaderValue::from_static("charted-testkit"));
+
+        let res = ctx
+            .request("/whoami", Method::GET, None, super::noop_request)
+            .await
+            .expect("unable to send request");
+
+        assert_eq!(consume_body!(res), Bytes::from_static(b"Bearer ctx"));
+
+        let res = ctx
+            .request("/user-agent", Method::GET, None, |req| {
+                req.headers_mut()
+                    .insert(header::USER_AGENT, HeaderValue::from_static("overwritten"));
+            })
+            .await
+            .expect("unable to send request");
+
+        assert_eq!(consume_body!(res), Bytes::from_static(b"overwritten"));
+
+        // named sessions inherit the context's defaults unless they override them
+        let alice = ctx.client("alice");
+        alice.set_auth(Auth::bearer("alice"));
+
+        let res = alice
+            .request("/whoami", Method::GET, None, super::noop_request)
+            .await
+            .expect("unable to send request");
+
+        assert_eq!(consume_body!(res), Bytes::from_static(b"Bearer alice"));
+
+        let res = ctx
+            .client("bob")
+            .request("/user-agent", Method::GET, None, super::noop_request)
+            .await
+            .expect("unable to send request");
+
+        assert_eq!(consume_body!(res), Bytes::from_static(b"charted-testkit"));
+
+        alice.set_base_path(Some(""));
+        let res = alice
+            .request("/whoami", Method::GET, None, super::noop_request)
+            .await
+            .expect("unable to send request");
+
+        assert_successful!(res);
+        assert_eq!(consume_body!(res), Bytes::from_static(b"Bearer alice"));
+    }
+
+    #[tokio::test]
+    #[cfg_attr(
+        windows,
+        ignore = "fails on Windows: hyper_util::client::legacy::Error(Connect, ConnectError(\"tcp connect error\", Os { code: 10049, kind: AddrNotAvailable, message: \"The requested address is not valid in its context.\" })))"
+    )]
+    async fn test_redirects() {
+        let mut ctx = TestContext::default();
+        ctx.serve(router()).await;
+
+        // redirects aren't followed by default
+        let res = ctx
+            .request("/redirect/found", Method::POST, None, super::noop_request)
+            .await
+            .expect("unable to send request");
+
+        assert_eq!(res.status(), StatusCode::FOUND);
+        assert!(RedirectChain::of(&res).is_none());
+
+        ctx.set_redirect_policy(RedirectPolicy::limited(3).same_origin_only(true));
+
+        let res = ctx
+            .request(
+                "/redirect/found",
+                Method::POST,
+                Bytes::from_static(b"hello"),
+                super::noop_request,
+            )
+            .await
+            .expect("unable to send request");
+
+        let chain = RedirectChain::of(&res).unwrap();
+        assert_eq!(chain.len(), 2);
+        assert_eq!(chain.hops()[0].status(), StatusCode::FOUND);
+        assert_eq!(chain.hops()[0].location(), Some("see-other"));
+        assert_eq!(chain.hops()[1].method(), Method::GET);
+        assert_eq!(chain.hops()[1].uri().path(), "/redirect/see-other");
+        assert_eq!(consume_body!(res), Bytes::from_static(b"GET "));
+
+        // every hop is recorded, not only the final response
+        let records = ctx.history().records();
+        let paths = records[1..]
+            .iter()
+            .map(|record| record.exchange().uri.path().to_owned())
+            .collect::<Vec<_>>();
+
+        assert_eq!(paths, ["/redirect/found", "/redirect/see-other", "/echo"]);
+        assert_eq!(records[1].exchange().status, StatusCode::FOUND);
+        assert_eq!(records[1].exchange().response_body.as_deref(), Some(&b""[..]));
+
+        let res = ctx
+            .request(
+                "/redirect/temporary",
+                Method::PUT,
+                Bytes::from_static(b"hello"),
+                super::noop_request,
+            )
+            .await
+            .expect("unable to send request");
+
+        assert_eq!(consume_body!(res), Bytes::from_static(b"PUT hello"));
+
+        let err = ctx
+            .request("/redirect/loop", Method::GET, None, super::noop_request)
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, Error::TooManyRedirects { max_hops: 3, ref chain } if chain.len() == 4));
+
+        let err = ctx
+            .request("/redirect/external", Method::GET, None, super::noop_request)
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, Error::CrossOriginRedirect { .. }));
+    }
+
+    #[tokio::test]
+    #[cfg_attr(
+        windows,
+        ignore = "fails on Windows: hyper_util::client::legacy::Error(Connect, ConnectError(\"tcp connect error\", Os { code: 10049, kind: AddrNotAvailable, message: \"The requested address is not valid in its context.\" })))"
+    )]
+    async fn test_failure_output() {
+        use crate::{assert_status_code, exchange::Exchange, soft::SoftAssertions};
+
+        let mut ctx = TestContext::default();
+        ctx.serve(router()).await;
+        ctx.set_buffer_responses(true);
+
+        let res = ctx
+            .request("/echo?page=1", Method::PUT, Bytes::from_static(b"hello"), |req| {
+                req.headers_mut()
+                    .insert(header::CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+            })
+            .await
+            .expect("unable to send request");
+
+        let exchange = Exchange::of(&res).expect("exchange to be recorded");
+        assert_eq!(exchange.method(), Method::PUT);
+        assert_eq!(exchange.request_body(), &Bytes::from_static(b"hello"));
+        assert_eq!(exchange.response_body(), Some(&Bytes::from_static(b"PUT hello")));
+
+        let soft = SoftAssertions::new();
+        assert_status_code!(res, StatusCode::CREATED);
+
+        let failures = soft.failures();
+        let message = failures[0].message();
+        let addr = ctx.server_addr().unwrap();
+
+        assert!(message.starts_with("expected status code 201 Created, got 200 OK\n\n--- request ---\n"));
+        assert!(message.contains(&format!(
+            "PUT http://{addr}/echo?page=1 HTTP/1.1\ncontent-type: text/plain\n\nhello\n"
+        )));
+        assert!(message.contains("--- response ---\nHTTP/1.1 200 OK\n"));
+        assert!(message.ends_with(&format!(
+            "--- reproduce with ---\ncurl -i -X PUT 'http://{addr}/echo?page=1' -H 'content-type: text/plain' --data-binary 'hello'"
+        )));
+
+        // the body is still readable after it was buffered
+        assert_eq!(consume_body!(res), Bytes::from_static(b"PUT hello"));
+        drop(std::panic::catch_unwind(move || soft.finish()));
+
+        ctx.set_buffer_responses(false);
+        let res = ctx
+            .request("/", Method::GET, None, super::noop_request)
+            .await
+            .expect("unable to send request");
+
+        assert!(Exchange::of(&res).unwrap().response_body().is_none());
+    }
+
+    #[tokio::test]
+    #[cfg_attr(
+        windows,
+        ignore = "fails on Windows: hyper_util::client::legacy::Error(Connect, ConnectError(\"tcp connect error\", Os { code: 10049, kind: AddrNotAvailable, message: \"The requested address is not valid in its context.\" })))"
+    )]
+    async fn test_snapshots() {
+        use crate::{assert_response_snapshot, snapshot::Settings};
+
+        let mut ctx = TestContext::default();
+        ctx.serve(router()).await;
+
+        let res = ctx
+            .request("/", Method::GET, None, super::noop_request)
+            .await
+            .expect("unable to send request");
+
+        assert_response_snapshot!(res, Settings::new().header("date").redact_header("date"));
+
+        let res = ctx
+            .request("/echo", Method::POST, Bytes::from_static(b"hello"), super::noop_request)
+            .await
+            .expect("unable to send request");
+
+        assert_response_snapshot!(res);
+    }
+
+    #[tokio::test]
+    #[cfg_attr(
+        windows,
+        ignore = "fails on Windows: hyper_util::client::legacy::Error(Connect, ConnectError(\"tcp connect error\", Os { code: 10049, kind: AddrNotAvailable, message: \"The requested address is not valid in its context.\" })))"
+    )]
+    async fn test_history() {
+        let mut ctx = TestContext::default();
+        ctx.serve(router()).await;
+        ctx.set_buffer_responses(true);
+
+        ctx.request("/", Method::GET, None, super::noop_request)
+            .await
+            .expect("unable to send request");
+
+        ctx.client("alice")
+            .request("/echo", Method::POST, Bytes::from_static(b"hello"), super::noop_request)
+            .await
+            .expect("unable to send request");
+
+        let records = ctx.history().records();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].session(), None);
+        assert_eq!(records[0].exchange().uri().path(), "/");
+        assert_eq!(records[1].session(), Some("alice"));
+        assert_eq!(
+            records[1].exchange().response_body(),
+            Some(&Bytes::from_static(b"POST hello"))
+        );
+
+        assert!(records[0].started_at() <= records[1].started_at());
+
+        ctx.history().clear();
+        assert!(ctx.history().is_empty());
+    }
+
+    #[test]
+    #[cfg_attr(
+        windows,
+        ignore = "fails on Windows: hyper_util::client::legacy::Error(Connect, ConnectError(\"tcp connect error\", Os { code: 10049, kind: AddrNotAvailable, message: \"The requested address is not valid in its context.\" })))"
+    )]
+    fn test_history_is_written_on_panic() {
+        let artifacts = std::env::temp_dir().join(format!("charted-testkit-artifacts-{}", std::process::id()));
+        let dir = artifacts.clone();
+
+        let result = std::thread::Builder::new()
+            .name(String::from("tests::failing_test"))
+            .spawn(move || {
+                let rt = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .unwrap();
+
+                rt.block_on(async move {
+                    // dropped after `ctx`, so it writes `history-2.txt`
+                    let other;
+
+                    let mut ctx = TestContext::default().artifacts_dir(dir.clone());
+                    ctx.serve(router()).await;
+                    ctx.set_buffer_responses(true);
+
+                    ctx.request("/", Method::GET, None, super::noop_request)
+                        .await
+                        .expect("unable to send request");
+
+                    other = TestContext::from_base_url(ctx.base_url().unwrap()).artifacts_dir(dir);
+                    other
+                        .request("/echo", Method::POST, Bytes::from_static(b"hi"), super::noop_request)
+                        .await
+                        .expect("unable to send request");
+
+                    panic!("test failed");
+                })
+            })
+            .unwrap()
+            .join();
+
+        assert!(result.is_err());
+
+        let history =
+            std::fs::read_to_string(artifacts.join("tests__failing_test/history.txt")).expect("history to be written");
+
+        assert!(history.starts_with("=== #1 GET http://"));
+        assert!(history.contains("\n\nHello, world!\n"));
+
+        let other = std::fs::read_to_string(artifacts.join("tests__failing_test/history-2.txt"))
+            .expect("history of the other context to be written");
+        assert!(other.starts_with("=== #1 POST http://"));
+
+        #[cfg(feature = "json")]
+        {
+            let har = std::fs::read(artifacts.join("tests__failing_test/history.har")).expect("HAR to be written");
+            let har = serde_json::from_slice::<serde_json::Value>(&har).unwrap();
+
+            assert_eq!(har["log"]["entries"][0]["response"]["status"], 200);
+            assert_eq!(har["log"]["entries"][0]["response"]["content"]["text"], "Hello, world!");
+        }
+
+        let _ = std::fs::remove_dir_all(artifacts);
+    }
+
+    #[cfg(all(feature = "decompression-br", feature = "decompression-gzip"))]
+    #[tokio::test]
+    #[cfg_attr(
+        windows,
+        ignore = "fails on Windows: hyper_util::client::legacy::Error(Connect, ConnectError(\"tcp connect error\", Os { code: 10049, kind: AddrNotAvailable, message: \"The requested address is not valid in its context.\" })))"
+    )]
+    async fn test_decompression() {
+        use crate::{assert_content_encoding, assert_not_compressed, compression::WireEncoding};
+        use tower_http::compression::CompressionLayer;
+
+        async fn large() -> String {
+            "charted ".repeat(512)
+        }
+
+        let mut ctx = TestContext::default();
+        ctx.serve(
+            Router::new()
+                .route("/", routing::get(hello))
+                .route("/large", routing::get(large))
+                .layer(CompressionLayer::new()),
+        )
+        .await;
+
+        ctx.set_accept_encoding(HeaderValue::from_static("br"));
+
+        // bodies are left alone unless auto decompression is enabled
+        let res = ctx
+            .request("/large", Method::GET, None, super::noop_request)
+            .await
+            .expect("unable to send request");
+
+        assert_content_encoding!(res, "br");
+        assert!(!WireEncoding::of(&res).was_decompressed());
+        assert_ne!(consume_body!(res), "charted ".repeat(512));
+
+        ctx.set_auto_decompress(true);
+        for encoding in ["br", "gzip"] {
+            ctx.set_accept_encoding(HeaderValue::from_static(encoding));
+
+            let res = ctx
+                .request("/large", Method::GET, None, super::noop_request)
+                .await
+                .expect("unable to send request");
+
+            assert_content_encoding!(res, encoding);
+            assert!(WireEncoding::of(&res).was_decompressed());
+            assert!(res.headers().get(header::CONTENT_ENCODING).is_none());
+            assert_eq!(consume_body!(res), "charted ".repeat(512));
+        }
+
+        // small bodies aren't compressed by `CompressionLayer`
+        let res = ctx
+            .request("/", Method::GET, None, super::noop_request)
+            .await
+            .expect("unable to send request");
+
+        assert_not_compressed!(res);
+        assert_eq!(consume_body!(res), Bytes::from_static(b"Hello, world!"));
+    }
+
+    #[cfg(feature = "openapi")]
+    #[tokio::test]
+    #[cfg_attr(
+        windows,
+        ignore = "fails on Windows: hyper_util::client::legacy::Error(Connect, ConnectError(\"tcp connect error\", Os { code: 10049, kind: AddrNotAvailable, message: \"The requested address is not valid in its context.\" })))"
+    )]
+    async fn test_openapi() {
+        use crate::{openapi::OpenApiSpec, soft::SoftAssertions};
+
+        let mut ctx = TestContext::default();
+        ctx.serve(router()).await;
+        ctx.set_openapi(
+            r#"
+openapi: 3.1.0
+info:
+  title: charted
+  version: 0.1.0
+paths:
+  /:
+    get:
+      operationId: hello
+      responses:
+        "200":
+          description: ok
+          content:
+            text/plain: {}
+  /echo:
+    post:
+      operationId: echo
+      responses:
+        "201":
+          description: created
+"#
+            .parse::<OpenApiSpec>()
+            .expect("valid document"),
+        );
+
+        let soft = SoftAssertions::new();
+        ctx.request("/", Method::GET, None, super::noop_request)
+            .await
+            .expect("unable to send request");
+
+        assert!(soft.is_empty());
+
+        ctx.request("/echo", Method::POST, Bytes::from_static(b"hello"), super::noop_request)
+            .await
+            .expect("unable to send request");
+
+        let failures = soft.failures();
+        assert_eq!(failures.len(), 1);
+        assert!(failures[0].message().starts_with(
+            "response to `POST /echo` (operation `echo`) violates the OpenAPI document:\n    - status code 200 OK isn't documented (documented: 201)\n\n--- request ---\n"
+        ));
+
+        drop(std::panic::catch_unwind(move || soft.finish()));
+    }
+
     #[cfg(feature = "testcontainers")]
     #[tokio::test]
     #[cfg_attr(
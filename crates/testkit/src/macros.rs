@@ -31,9 +31,12 @@
 /// ```
 #[macro_export]
 macro_rules! assert_successful {
-    ($res:expr) => {
-        assert!(($res).status().is_success());
-    };
+    ($res:expr) => {{
+        let res = &$res;
+        if !res.status().is_success() {
+            $crate::__private::fail_for(res, format!("expected a successful response, got {}", res.status()));
+        }
+    }};
 }
 
 /// Checks whenever if a [`Response`][axum::http::response::Response] failed.
@@ -47,9 +50,12 @@ macro_rules! assert_successful {
 /// ```
 #[macro_export]
 macro_rules! assert_failure {
-    ($res:expr) => {
-        assert!(!($res).status().is_success());
-    };
+    ($res:expr) => {{
+        let res = &$res;
+        if res.status().is_success() {
+            $crate::__private::fail_for(res, format!("expected a failed response, got {}", res.status()));
+        }
+    }};
 }
 
 /// Macro to easily assert if a given [response][axum::http::response::Response]'s status code
@@ -64,9 +70,13 @@ macro_rules! assert_failure {
 /// ```
 #[macro_export]
 macro_rules! assert_status_code {
-    ($res:expr, $status:expr) => {
-        assert_eq!($status, ($res).status());
-    };
+    ($res:expr, $status:expr) => {{
+        let res = &$res;
+        let expected = $status;
+        if expected != res.status() {
+            $crate::__private::fail_for(res, format!("expected status code {}, got {}", expected, res.status()));
+        }
+    }};
 }
 
 /// Macro to consume the full body of a [response][axum::http::response::Response] and returns
@@ -95,6 +105,222 @@ macro_rules! consume_body {
     }};
 }
 
+/// Consumes the body of a [response][axum::http::response::Response] and asserts that it is equal to
+/// the expected JSON value. On mismatch, every difference is reported by its path:
+///
+/// ```text
+/// JSON body doesn't equal the expected value:
+///     .items[2].name: expected "a", got "b"
+/// ```
+///
+/// Paths can be ignored with `ignore = [...]`, and values that can't be known ahead of time can be
+/// matched with the placeholders in the [`json`][crate::json] module.
+///
+/// ## Example
+/// ```rust
+/// # use axum::http::{response::Response, StatusCode};
+/// use charted_testkit::json::any_uuid;
+/// use serde_json::json;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let res = Response::builder()
+///     .status(StatusCode::OK)
+///     .body(String::from(r#"{"id":"9f0b6a5e-2d8c-4f6e-a8f7-5d1c6c1a2b3c","name":"noel","created_at":1724000000}"#))
+///     .expect("response to be constructed");
+///
+/// charted_testkit::assert_json_eq!(res, json!({ "id": any_uuid(), "name": "noel" }), ignore = [".created_at"]);
+/// # }
+/// ```
+#[cfg(feature = "json")]
+#[macro_export]
+macro_rules! assert_json_eq {
+    ($res:expr, $expected:expr $(, ignore = [$($path:expr),* $(,)?])? $(,)?) => {{
+        let res = $res;
+        let exchange = $crate::exchange::Exchange::of(&res).cloned();
+        let body = $crate::consume_body!(res);
+
+        $crate::json::__assert_json(
+            body,
+            &$expected,
+            $crate::json::Mode::Exact,
+            &[$($($path),*)?],
+            exchange.as_ref(),
+        );
+    }};
+}
+
+/// Consumes the body of a [response][axum::http::response::Response] and asserts that it includes
+/// the expected JSON value: objects can have keys that aren't expected, and arrays can have more
+/// elements than expected. This otherwise works the same as [`assert_json_eq!`].
+///
+/// ## Example
+/// ```rust
+/// # use axum::http::{response::Response, StatusCode};
+/// use serde_json::json;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let res = Response::builder()
+///     .status(StatusCode::OK)
+///     .body(String::from(r#"{"success":true,"data":{"name":"noel","admin":false}}"#))
+///     .expect("response to be constructed");
+///
+/// charted_testkit::assert_json_include!(res, json!({ "success": true, "data": { "name": "noel" } }));
+/// # }
+/// ```
+#[cfg(feature = "json")]
+#[macro_export]
+macro_rules! assert_json_include {
+    ($res:expr, $expected:expr $(, ignore = [$($path:expr),* $(,)?])? $(,)?) => {{
+        let res = $res;
+        let exchange = $crate::exchange::Exchange::of(&res).cloned();
+        let body = $crate::consume_body!(res);
+
+        $crate::json::__assert_json(
+            body,
+            &$expected,
+            $crate::json::Mode::Include,
+            &[$($($path),*)?],
+            exchange.as_ref(),
+        );
+    }};
+}
+
+/// Consumes the body of a [response][axum::http::response::Response] and validates it against a
+/// JSON Schema. On failure, every violation is reported by the path of the invalid value:
+///
+/// ```text
+/// JSON body doesn't match the schema:
+///     /items/1: "2" is not of type "integer" (at schema path `/properties/items/items/type`)
+/// ```
+///
+/// The schema can be given inline as a [`serde_json::Value`] or [`Schema`][crate::schema::Schema], loaded
+/// from a file with `file = "..."`, or derived from a [`schemars::JsonSchema`] type with `type = T` if
+/// the `schemars` crate feature is enabled.
+///
+/// ## Example
+/// ```rust
+/// # use axum::http::{response::Response, StatusCode};
+/// use serde_json::json;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// # let response = || Response::builder()
+/// #     .status(StatusCode::OK)
+/// #     .body(String::from(r#"{"id":1,"name":"noel"}"#))
+/// #     .expect("response to be constructed");
+/// #
+/// charted_testkit::assert_json_schema!(response(), json!({ "type": "object", "required": ["id"] }));
+/// # let schemas = std::env::temp_dir();
+/// # std::fs::write(schemas.join("user.json"), r#"{"type":"object"}"#).unwrap();
+/// charted_testkit::assert_json_schema!(response(), file = schemas.join("user.json"));
+/// # }
+/// ```
+#[cfg(feature = "json-schema")]
+#[macro_export]
+macro_rules! assert_json_schema {
+    ($res:expr, file = $path:expr $(,)?) => {{
+        let path = $path;
+        let schema = match $crate::schema::Schema::from_file(&path) {
+            Ok(schema) => schema,
+            Err(err) => panic!(
+                "failed to load schema from `{}`: {err}",
+                ::std::convert::AsRef::<::std::path::Path>::as_ref(&path).display()
+            ),
+        };
+
+        $crate::assert_json_schema!($res, schema)
+    }};
+
+    ($res:expr, type = $ty:ty $(,)?) => {
+        $crate::assert_json_schema!($res, $crate::schema::Schema::of::<$ty>())
+    };
+
+    ($res:expr, $schema:expr $(,)?) => {{
+        let res = $res;
+        let exchange = $crate::exchange::Exchange::of(&res).cloned();
+        let body = $crate::consume_body!(res);
+
+        $crate::schema::__assert_json_schema(body, $schema, exchange.as_ref());
+    }};
+}
+
+/// Consumes the body of a [response][axum::http::response::Response] and checks it against a
+/// [`Matcher`][crate::matchers::Matcher]. On failure, every predicate that failed is reported.
+///
+/// ## Example
+/// ```rust
+/// # use axum::http::{response::Response, StatusCode};
+/// use charted_testkit::matchers::{body, contains, header, status, Matcher};
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let res = Response::builder()
+///     .status(StatusCode::OK)
+///     .header("content-type", "text/plain")
+///     .body(String::from("Hello, world!"))
+///     .expect("response to be constructed");
+///
+/// charted_testkit::assert_response!(
+///     res,
+///     status(StatusCode::OK).and(header("content-type", contains("text"))).and(body(contains("world")))
+/// );
+/// # }
+/// ```
+#[macro_export]
+macro_rules! assert_response {
+    ($res:expr, $matcher:expr $(,)?) => {{
+        let (parts, body) = ($res).into_parts();
+        let body = $crate::consume_body!(body);
+
+        $crate::matchers::__assert_response(&$crate::matchers::BufferedResponse::from_parts(parts, body), &$matcher);
+    }};
+}
+
+/// Consumes the body of a [response][axum::http::response::Response] and compares it with a snapshot
+/// that is stored in the `tests/snapshots` directory of the crate. Snapshots are named after the test,
+/// which can be overridden with [`Settings::name`][crate::snapshot::Settings::name]. See the
+/// [`snapshot`][crate::snapshot] module for how snapshots are created and updated.
+///
+/// ## Example
+/// ```rust,no_run
+/// # use axum::http::{response::Response, StatusCode};
+/// use charted_testkit::snapshot::Settings;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let res = Response::builder()
+///     .status(StatusCode::OK)
+///     .header("date", "Mon, 19 Aug 2024 04:20:00 GMT")
+///     .body(String::from("Hello, world!"))
+///     .expect("response to be constructed");
+///
+/// charted_testkit::assert_response_snapshot!(res, Settings::new().header("date").redact_header("date"));
+/// # }
+/// ```
+#[macro_export]
+macro_rules! assert_response_snapshot {
+    ($res:expr $(,)?) => {
+        $crate::assert_response_snapshot!($res, $crate::snapshot::Settings::default())
+    };
+
+    ($res:expr, $settings:expr $(,)?) => {{
+        fn __f() {}
+
+        let (parts, body) = ($res).into_parts();
+        let body = $crate::consume_body!(body);
+
+        $crate::snapshot::__assert_snapshot(
+            env!("CARGO_MANIFEST_DIR"),
+            ::std::any::type_name_of_val(&__f),
+            &parts,
+            &body,
+            &$settings,
+        );
+    }};
+}
+
 /// Assertion macro to indicate that a [`Response`][axum::http::response::Response] has the header it needs.
 ///
 /// ## Example
@@ -115,9 +341,16 @@ macro_rules! assert_has_header {
         $crate::assert_has_header!($res, $crate::__private::header::$header);
     };
 
-    ($res:expr, $header:expr) => {
-        assert!($res.headers().get($header).is_some());
-    };
+    ($res:expr, $header:expr) => {{
+        let res = &$res;
+        let name = $crate::__private::header_name($header);
+        if res.headers().get(&name).is_none() {
+            $crate::__private::fail_for(
+                res,
+                format!("expected header `{}` to be present, but it was missing", name),
+            );
+        }
+    }};
 }
 
 /// Assertion macro to indicate that a [`Response`][axum::http::response::Response] doesn't have the header it needs.
@@ -132,10 +365,673 @@ macro_rules! assert_has_header {
 #[macro_export]
 macro_rules! assert_doesnt_have_header {
     ($res:expr, $header:ident) => {
-        $crate::assert_has_header!($res, $crate::__private::header::$header);
+        $crate::assert_doesnt_have_header!($res, $crate::__private::header::$header);
     };
 
-    ($res:expr, $header:expr) => {
-        assert!($res.headers().get($header).is_none());
+    ($res:expr, $header:expr) => {{
+        let res = &$res;
+        let name = $crate::__private::header_name($header);
+        if let Some(value) = res.headers().get(&name) {
+            $crate::__private::fail_for(
+                res,
+                format!("expected header `{}` to be missing, got {:?}", name, value),
+            );
+        }
+    }};
+}
+
+/// Assertion macro to check that a [`Response`][axum::http::response::Response]'s header is equal to
+/// the given value. Fails if the header is missing or isn't visible ASCII.
+///
+/// ## Example
+/// ```
+/// # use axum::http::{response::Response, StatusCode, header};
+/// #
+/// let res = Response::builder()
+///     .status(StatusCode::OK)
+///     .header("Content-Type", "application/json")
+///     .body(())
+///     .expect("response to be avaliable");
+///
+/// charted_testkit::assert_header_eq!(res, CONTENT_TYPE, "application/json");
+/// charted_testkit::assert_header_eq!(res, "content-type", "application/json");
+/// ```
+#[macro_export]
+macro_rules! assert_header_eq {
+    ($res:expr, $header:ident, $value:expr) => {
+        $crate::assert_header_eq!($res, $crate::__private::header::$header, $value);
     };
+
+    ($res:expr, $header:expr, $value:expr) => {{
+        let res = &$res;
+        let name = $crate::__private::header_name($header);
+        let expected = $value;
+        match $crate::__private::header_str(res.headers(), &name) {
+            Ok(actual) if actual == expected => {}
+            Ok(actual) => $crate::__private::fail_for(
+                res,
+                format!(
+                    "header `{}` has an unexpected value\n  expected: {:?}\n    actual: {:?}",
+                    name, expected, actual
+                ),
+            ),
+
+            Err(message) => $crate::__private::fail_for(res, message),
+        }
+    }};
+}
+
+/// Assertion macro to check that a [`Response`][axum::http::response::Response]'s header contains
+/// the given substring.
+///
+/// ## Example
+/// ```
+/// # use axum::http::{response::Response, StatusCode, header};
+/// #
+/// let res = Response::builder()
+///     .status(StatusCode::OK)
+///     .header("Content-Type", "application/json; charset=utf-8")
+///     .body(())
+///     .expect("response to be avaliable");
+///
+/// charted_testkit::assert_header_contains!(res, header::CONTENT_TYPE, "json");
+/// ```
+#[macro_export]
+macro_rules! assert_header_contains {
+    ($res:expr, $header:ident, $needle:expr) => {
+        $crate::assert_header_contains!($res, $crate::__private::header::$header, $needle);
+    };
+
+    ($res:expr, $header:expr, $needle:expr) => {{
+        let res = &$res;
+        let name = $crate::__private::header_name($header);
+        let needle = $needle;
+        match $crate::__private::header_str(res.headers(), &name) {
+            Ok(actual) if actual.contains(needle) => {}
+            Ok(actual) => $crate::__private::fail_for(
+                res,
+                format!("expected header `{}` to contain {:?}, got {:?}", name, needle, actual),
+            ),
+
+            Err(message) => $crate::__private::fail_for(res, message),
+        }
+    }};
+}
+
+/// Assertion macro to check that a [`Response`][axum::http::response::Response]'s header matches the
+/// given [regular expression][regex::Regex].
+///
+/// ## Example
+/// ```
+/// # use axum::http::{response::Response, StatusCode, header};
+/// #
+/// let res = Response::builder()
+///     .status(StatusCode::OK)
+///     .header("ETag", "\"5f2b1c\"")
+///     .body(())
+///     .expect("response to be avaliable");
+///
+/// charted_testkit::assert_header_matches!(res, ETAG, r#"^"[0-9a-f]+"$"#);
+/// ```
+#[macro_export]
+macro_rules! assert_header_matches {
+    ($res:expr, $header:ident, $pattern:expr) => {
+        $crate::assert_header_matches!($res, $crate::__private::header::$header, $pattern);
+    };
+
+    ($res:expr, $header:expr, $pattern:expr) => {{
+        let res = &$res;
+        let name = $crate::__private::header_name($header);
+        let pattern = $crate::__private::Regex::new($pattern).expect("pattern to be a valid regular expression");
+        match $crate::__private::header_str(res.headers(), &name) {
+            Ok(actual) if pattern.is_match(actual) => {}
+            Ok(actual) => $crate::__private::fail_for(
+                res,
+                format!("expected header `{}` to match `{}`, got {:?}", name, pattern, actual),
+            ),
+
+            Err(message) => $crate::__private::fail_for(res, message),
+        }
+    }};
+}
+
+/// Assertion macro to check every value of a [`Response`][axum::http::response::Response]'s header,
+/// in the order that they were sent.
+///
+/// ## Example
+/// ```
+/// # use axum::http::{response::Response, StatusCode, header};
+/// #
+/// let res = Response::builder()
+///     .status(StatusCode::OK)
+///     .header("Vary", "accept-encoding")
+///     .header("Vary", "origin")
+///     .body(())
+///     .expect("response to be avaliable");
+///
+/// charted_testkit::assert_header_values!(res, VARY, ["accept-encoding", "origin"]);
+/// ```
+#[macro_export]
+macro_rules! assert_header_values {
+    ($res:expr, $header:ident, $values:expr) => {
+        $crate::assert_header_values!($res, $crate::__private::header::$header, $values);
+    };
+
+    ($res:expr, $header:expr, $values:expr) => {{
+        let res = &$res;
+        let name = $crate::__private::header_name($header);
+        let expected = $values;
+        match $crate::__private::header_values(res.headers(), &name) {
+            Ok(actual) if actual == expected => {}
+            Ok(actual) => $crate::__private::fail_for(
+                res,
+                format!(
+                    "header `{}` has unexpected values\n  expected: {:?}\n    actual: {:?}",
+                    name, expected, actual
+                ),
+            ),
+
+            Err(message) => $crate::__private::fail_for(res, message),
+        }
+    }};
+}
+
+/// Assertion macro to decode a [`Response`][axum::http::response::Response]'s header as a typed header
+/// from the [`headers`] crate and check that it is equal to the expected value.
+///
+/// ## Example
+/// ```
+/// # use axum::http::{response::Response, StatusCode};
+/// use charted_testkit::headers::{ContentLength, ContentType};
+///
+/// let res = Response::builder()
+///     .status(StatusCode::OK)
+///     .header("Content-Type", "application/json")
+///     .header("Content-Length", "2")
+///     .body(())
+///     .expect("response to be avaliable");
+///
+/// charted_testkit::assert_typed_header!(res, ContentType::json());
+/// charted_testkit::assert_typed_header!(res, ContentLength(2));
+/// ```
+#[macro_export]
+macro_rules! assert_typed_header {
+    ($res:expr, $expected:expr) => {{
+        let res = &$res;
+        let expected = $expected;
+        match $crate::__private::typed_header_like(res.headers(), &expected) {
+            Ok(actual) if actual.as_ref() == Some(&expected) => {}
+            Ok(actual) => $crate::__private::fail_for(
+                res,
+                format!(
+                    "typed header has an unexpected value\n  expected: {:?}\n    actual: {:?}",
+                    Some(&expected),
+                    actual
+                ),
+            ),
+
+            Err(message) => $crate::__private::fail_for(res, message),
+        }
+    }};
+}
+
+/// Assertion macro to check that a [`Response`][axum::http::response::Response] body was sent with the
+/// given `Content-Encoding`. This also works on responses that were decompressed by
+/// [`TestContext::set_auto_decompress`][crate::TestContext::set_auto_decompress].
+///
+/// ## Example
+/// ```
+/// # use axum::http::{response::Response, StatusCode, header};
+/// #
+/// let res = Response::builder()
+///     .status(StatusCode::OK)
+///     .header(header::CONTENT_ENCODING, "br")
+///     .body(())
+///     .expect("response to be avaliable");
+///
+/// charted_testkit::assert_content_encoding!(res, "br");
+/// ```
+#[macro_export]
+macro_rules! assert_content_encoding {
+    ($res:expr, $encoding:expr) => {{
+        let res = &$res;
+        let encoding = $crate::compression::WireEncoding::of(res);
+        if !encoding.is($encoding) {
+            $crate::__private::fail_for(
+                res,
+                format!(
+                    "expected response to be encoded with `{}`, but it was encoded with {:?}",
+                    $encoding,
+                    encoding.encodings()
+                ),
+            );
+        }
+    }};
+}
+
+/// Assertion macro to check that a [`Response`][axum::http::response::Response] body wasn't compressed.
+///
+/// ## Example
+/// ```
+/// # use axum::http::{response::Response, StatusCode};
+/// #
+/// let res = Response::builder().status(StatusCode::OK).body(()).expect("response to be avaliable");
+/// charted_testkit::assert_not_compressed!(res);
+/// ```
+#[macro_export]
+macro_rules! assert_not_compressed {
+    ($res:expr) => {{
+        let res = &$res;
+        let encoding = $crate::compression::WireEncoding::of(res);
+        if encoding.is_compressed() {
+            $crate::__private::fail_for(
+                res,
+                format!(
+                    "expected response to not be compressed, but it was encoded with {:?}",
+                    encoding.encodings()
+                ),
+            );
+        }
+    }};
+}
+
+/// Polls `value` until `predicate` returns `true` for it, and returns the value that satisfied it. The
+/// `value` expression is evaluated again on every attempt, so it can `.await` (i.e, send a request to
+/// check the status of a job) and must be used in an `async` context.
+///
+/// The options after the predicate are the methods of [`Eventually`][crate::eventually::Eventually]
+/// (`timeout`, `interval`, `backoff` and `max_interval`). If the predicate isn't satisfied before the
+/// timeout, the assertion fails with the last observed value.
+///
+/// ## Example
+/// ```rust
+/// # use std::{sync::atomic::{AtomicUsize, Ordering}, time::Duration};
+/// #
+/// # #[tokio::main]
+/// # async fn main() {
+/// let polls = AtomicUsize::new(0);
+/// let status = charted_testkit::eventually!(
+///     match polls.fetch_add(1, Ordering::SeqCst) {
+///         0..=2 => "running",
+///         _ => "done",
+///     },
+///     |status| *status == "done",
+///     interval = Duration::from_millis(10),
+///     backoff = 2.0,
+/// );
+///
+/// assert_eq!(status, "done");
+/// # }
+/// ```
+#[macro_export]
+macro_rules! eventually {
+    ($value:expr, $predicate:expr $(, $option:ident = $setting:expr)* $(,)?) => {{
+        let mut attempts = $crate::eventually::Eventually::default()
+            $(.$option($setting))*
+            .__start();
+
+        loop {
+            let value = $value;
+            if $crate::eventually::__check(&value, $predicate) {
+                break value;
+            }
+
+            if !attempts.wait().await {
+                $crate::__private::fail(format!(
+                    "expected `{}` to eventually satisfy `{}`, but it didn't after {} attempts in {:?}\n\nlast observed value: {:#?}",
+                    stringify!($value),
+                    stringify!($predicate),
+                    attempts.count(),
+                    attempts.elapsed(),
+                    value
+                ));
+
+                break value;
+            }
+        }
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Bytes,
+        http::{header, response::Response, HeaderName, StatusCode},
+    };
+
+    fn response() -> Response<String> {
+        Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "application/json; charset=utf-8")
+            .header(header::CONTENT_LENGTH, "27")
+            .header(header::VARY, "accept-encoding")
+            .header(header::VARY, "origin")
+            .header("x-request-id", "req-1234")
+            .body(String::from(r#"{"id":1,"name":"noel","a":[]}"#))
+            .unwrap()
+    }
+
+    #[test]
+    fn status_assertions() {
+        let res = response();
+        assert_successful!(res);
+        assert_status_code!(res, StatusCode::OK);
+
+        let res = Response::builder().status(StatusCode::NOT_FOUND).body(()).unwrap();
+        assert_failure!(res);
+    }
+
+    #[test]
+    #[should_panic(expected = "expected a successful response, got 404 Not Found")]
+    fn assert_successful_fails() {
+        assert_successful!(Response::builder().status(StatusCode::NOT_FOUND).body(()).unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "expected a failed response, got 200 OK")]
+    fn assert_failure_fails() {
+        assert_failure!(response());
+    }
+
+    #[test]
+    #[should_panic(expected = "expected status code 201 Created, got 200 OK")]
+    fn assert_status_code_fails() {
+        assert_status_code!(response(), StatusCode::CREATED);
+    }
+
+    #[tokio::test]
+    async fn consume_body() {
+        assert_eq!(
+            consume_body!(response()),
+            Bytes::from_static(br#"{"id":1,"name":"noel","a":[]}"#)
+        );
+    }
+
+    #[test]
+    fn presence_assertions() {
+        let res = response();
+        assert_has_header!(res, CONTENT_TYPE);
+        assert_has_header!(res, header::VARY);
+        assert_has_header!(res, "x-request-id");
+        assert_doesnt_have_header!(res, ETAG);
+        assert_doesnt_have_header!(res, header::LOCATION);
+        assert_doesnt_have_header!(res, "x-missing");
+    }
+
+    #[test]
+    #[should_panic(expected = "expected header `etag` to be present, but it was missing")]
+    fn assert_has_header_ident_fails() {
+        assert_has_header!(response(), ETAG);
+    }
+
+    #[test]
+    #[should_panic(expected = "expected header `etag` to be present, but it was missing")]
+    fn assert_has_header_expr_fails() {
+        assert_has_header!(response(), header::ETAG);
+    }
+
+    #[test]
+    #[should_panic(expected = "expected header `content-type` to be missing, got \"application/json; charset=utf-8\"")]
+    fn assert_doesnt_have_header_ident_fails() {
+        assert_doesnt_have_header!(response(), CONTENT_TYPE);
+    }
+
+    #[test]
+    #[should_panic(expected = "expected header `content-type` to be missing, got \"application/json; charset=utf-8\"")]
+    fn assert_doesnt_have_header_expr_fails() {
+        assert_doesnt_have_header!(response(), header::CONTENT_TYPE);
+    }
+
+    #[test]
+    fn value_assertions() {
+        let res = response();
+        let request_id = HeaderName::from_static("x-request-id");
+
+        assert_header_eq!(res, CONTENT_LENGTH, "27");
+        assert_header_eq!(res, &request_id, String::from("req-1234"));
+        assert_header_contains!(res, CONTENT_TYPE, "json");
+        assert_header_contains!(res, "content-type", "charset=utf-8");
+        assert_header_matches!(res, CONTENT_LENGTH, r"^\d+$");
+        assert_header_matches!(res, &request_id, "^req-[0-9]{4}$");
+        assert_header_values!(res, VARY, ["accept-encoding", "origin"]);
+        assert_header_values!(res, header::ETAG, Vec::<&str>::new());
+    }
+
+    #[test]
+    #[should_panic(expected = "header `content-length` has an unexpected value")]
+    fn assert_header_eq_ident_fails() {
+        assert_header_eq!(response(), CONTENT_LENGTH, "28");
+    }
+
+    #[test]
+    #[should_panic(expected = "expected header `etag` to be present, but it was missing")]
+    fn assert_header_eq_expr_fails() {
+        assert_header_eq!(response(), header::ETAG, "\"1\"");
+    }
+
+    #[test]
+    #[should_panic(expected = "expected header `content-type` to contain \"xml\"")]
+    fn assert_header_contains_ident_fails() {
+        assert_header_contains!(response(), CONTENT_TYPE, "xml");
+    }
+
+    #[test]
+    #[should_panic(expected = "expected header `x-request-id` to contain \"abc\"")]
+    fn assert_header_contains_expr_fails() {
+        assert_header_contains!(response(), "x-request-id", "abc");
+    }
+
+    #[test]
+    #[should_panic(expected = "expected header `content-length` to match `^[a-z]+$`")]
+    fn assert_header_matches_ident_fails() {
+        assert_header_matches!(response(), CONTENT_LENGTH, "^[a-z]+$");
+    }
+
+    #[test]
+    #[should_panic(expected = "expected header `x-request-id` to match `^\\d+$`")]
+    fn assert_header_matches_expr_fails() {
+        assert_header_matches!(response(), "x-request-id", r"^\d+$");
+    }
+
+    #[test]
+    #[should_panic(expected = "header `vary` has unexpected values")]
+    fn assert_header_values_ident_fails() {
+        assert_header_values!(response(), VARY, ["origin", "accept-encoding"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "header `vary` has unexpected values")]
+    fn assert_header_values_expr_fails() {
+        assert_header_values!(response(), header::VARY, ["accept-encoding"]);
+    }
+
+    #[test]
+    fn typed_header_assertions() {
+        use headers::{ContentLength, ContentType};
+
+        let res = response();
+        assert_typed_header!(res, ContentLength(27));
+        assert_typed_header!(res, "application/json; charset=utf-8".parse::<ContentType>().unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "typed header has an unexpected value")]
+    fn assert_typed_header_fails() {
+        assert_typed_header!(response(), "\"1\"".parse::<headers::ETag>().unwrap());
+    }
+
+    #[test]
+    fn compression_assertions() {
+        let res = Response::builder()
+            .header(header::CONTENT_ENCODING, "gzip")
+            .body(())
+            .unwrap();
+
+        assert_content_encoding!(res, "gzip");
+        assert_not_compressed!(response());
+    }
+
+    #[test]
+    #[should_panic(expected = "expected response to be encoded with `br`")]
+    fn assert_content_encoding_fails() {
+        assert_content_encoding!(response(), "br");
+    }
+
+    #[test]
+    #[should_panic(expected = "expected response to not be compressed")]
+    fn assert_not_compressed_fails() {
+        let res = Response::builder()
+            .header(header::CONTENT_ENCODING, "br")
+            .body(())
+            .unwrap();
+
+        assert_not_compressed!(res);
+    }
+
+    #[tokio::test]
+    async fn soft_assertions() {
+        let soft = crate::soft::SoftAssertions::new();
+        let res = response();
+
+        assert_failure!(res);
+        assert_status_code!(res, StatusCode::CREATED);
+        assert_has_header!(res, ETAG);
+        assert_doesnt_have_header!(res, VARY);
+        assert_header_eq!(res, "x-request-id", "req-1");
+        assert_header_contains!(res, CONTENT_TYPE, "xml");
+        assert_header_matches!(res, CONTENT_LENGTH, "^0$");
+        assert_header_values!(res, VARY, ["origin"]);
+        assert_typed_header!(res, headers::ContentLength(1));
+        assert_content_encoding!(res, "gzip");
+        assert_response!(response(), crate::matchers::status(201));
+
+        let messages = soft
+            .failures()
+            .into_iter()
+            .map(|f| f.message().to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(messages.len(), 11);
+        assert_eq!(messages[0], "expected a failed response, got 200 OK");
+        assert_eq!(messages[1], "expected status code 201 Created, got 200 OK");
+
+        let err = std::panic::catch_unwind(move || soft.finish()).unwrap_err();
+        assert!(err
+            .downcast_ref::<String>()
+            .unwrap()
+            .starts_with("11 soft assertions failed:"));
+    }
+
+    #[cfg(feature = "json")]
+    #[tokio::test]
+    async fn json_assertions() {
+        use serde_json::json;
+
+        assert_json_eq!(response(), json!({ "id": 1, "name": "noel", "a": [] }));
+        assert_json_eq!(response(), json!({ "id": 1 }), ignore = [".name", ".a"]);
+        assert_json_include!(response(), json!({ "name": "noel" }));
+        assert_json_include!(response(), json!({ "id": 2, "name": "noel" }), ignore = [".id"]);
+    }
+
+    #[cfg(feature = "json")]
+    #[tokio::test]
+    #[should_panic(expected = ".name: expected \"august\", got \"noel\"")]
+    async fn assert_json_eq_fails() {
+        assert_json_eq!(response(), serde_json::json!({ "id": 1, "name": "august", "a": [] }));
+    }
+
+    #[cfg(feature = "json")]
+    #[tokio::test]
+    #[should_panic(expected = ".a[0]: expected 1, but it was missing")]
+    async fn assert_json_include_fails() {
+        assert_json_include!(response(), serde_json::json!({ "a": [1] }));
+    }
+
+    #[cfg(feature = "json-schema")]
+    #[tokio::test]
+    async fn json_schema_assertions() {
+        use crate::schema::Schema;
+        use serde_json::json;
+
+        let schema = json!({
+            "type": "object",
+            "required": ["id", "name"],
+            "properties": {
+                "id": { "type": "integer" },
+                "name": { "type": "string" },
+                "a": { "type": "array" }
+            }
+        });
+
+        assert_json_schema!(response(), &schema);
+        assert_json_schema!(response(), Schema::new(&schema).unwrap());
+        assert_json_schema!(response(), schema);
+    }
+
+    #[cfg(feature = "schemars")]
+    #[tokio::test]
+    async fn json_schema_assertions_with_types() {
+        #[derive(schemars::JsonSchema)]
+        #[allow(dead_code)]
+        struct User {
+            id: u64,
+            name: String,
+        }
+
+        assert_json_schema!(response(), type = User);
+    }
+
+    #[cfg(feature = "json-schema")]
+    #[tokio::test]
+    #[should_panic(expected = "JSON body doesn't match the schema:\n    /name: \"noel\" is not of type \"integer\"")]
+    async fn assert_json_schema_fails() {
+        assert_json_schema!(
+            response(),
+            serde_json::json!({ "properties": { "name": { "type": "integer" } } })
+        );
+    }
+
+    #[cfg(feature = "json-schema")]
+    #[tokio::test]
+    #[should_panic(expected = "failed to load schema from `does-not-exist.json`")]
+    async fn assert_json_schema_missing_file() {
+        assert_json_schema!(response(), file = "does-not-exist.json");
+    }
+
+    #[tokio::test]
+    async fn eventually() {
+        #[derive(Debug)]
+        struct Job {
+            status: &'static str,
+        }
+
+        let mut polls = 0;
+        let job = eventually!(
+            {
+                polls += 1;
+                Job {
+                    status: if polls < 3 { "running" } else { "done" },
+                }
+            },
+            |job| job.status == "done",
+            interval = std::time::Duration::from_millis(1),
+        );
+
+        assert_eq!(job.status, "done");
+        assert_eq!(polls, 3);
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "to eventually satisfy `|polls| *polls > 10`")]
+    async fn eventually_fails_with_last_value() {
+        let mut polls = 0;
+        eventually!(
+            {
+                polls += 1;
+                polls
+            },
+            |polls| *polls > 10,
+            timeout = std::time::Duration::from_millis(25),
+            interval = std::time::Duration::from_millis(10),
+        );
+    }
 }
@@ -0,0 +1,632 @@
+// 📦🦋 charted TestKit: testing library for Axum services with testcontainers support
+// Copyright (c) 2024 Noelware, LLC. <team@noelware.org>
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Composable predicates for asserting responses with [`assert_response!`][crate::assert_response].
+//!
+//! A [`Matcher`] checks a [`BufferedResponse`] and can be combined with other matchers with
+//! [`Matcher::and`]. Every failed predicate is collected, so a single assertion reports all
+//! the problems with a response instead of only the first one:
+//!
+//! ```text
+//! response didn't match 2 of 3 predicates:
+//!     - header `content-type` contains "json": got "text/plain; charset=utf-8"
+//!     - json path `$.id` is a UUID: got 1
+//! ```
+//!
+//! ## Example
+//! ```rust
+//! # use axum::http::{Response, StatusCode};
+//! # #[tokio::main]
+//! # async fn main() {
+//! # #[cfg(feature = "json")] {
+//! use charted_testkit::matchers::{contains, header, is_uuid, json_path, status, Matcher};
+//!
+//! let res = Response::builder()
+//!     .status(StatusCode::OK)
+//!     .header("content-type", "application/json")
+//!     .body(String::from(r#"{"id":"9f0b6a5e-2d8c-4f6e-a8f7-5d1c6c1a2b3c"}"#))
+//!     .unwrap();
+//!
+//! charted_testkit::assert_response!(
+//!     res,
+//!     status(200)
+//!         .and(header("content-type", contains("json")))
+//!         .and(json_path("$.id", is_uuid()))
+//! );
+//! # }
+//! # }
+//! ```
+
+use crate::exchange::Exchange;
+use axum::{
+    body::Bytes,
+    http::{response::Parts, HeaderMap, HeaderName, StatusCode},
+};
+use regex::Regex;
+use std::fmt::{Debug, Write};
+
+/// A response whose body was fully received, which is what [`Matcher`]s are checked against.
+#[derive(Debug, Clone)]
+pub struct BufferedResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+    exchange: Option<Exchange>,
+}
+
+impl BufferedResponse {
+    /// Creates a new [`BufferedResponse`].
+    pub fn new(status: StatusCode, headers: HeaderMap, body: Bytes) -> BufferedResponse {
+        BufferedResponse {
+            status,
+            headers,
+            body,
+            exchange: None,
+        }
+    }
+
+    /// Creates a [`BufferedResponse`] from the parts of a response and its received body.
+    pub fn from_parts(mut parts: Parts, body: Bytes) -> BufferedResponse {
+        BufferedResponse {
+            exchange: parts.extensions.remove(),
+            ..BufferedResponse::new(parts.status, parts.headers, body)
+        }
+    }
+
+    /// Status code of the response.
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Headers of the response.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Body of the response.
+    pub fn body(&self) -> &Bytes {
+        &self.body
+    }
+
+    /// The [`Exchange`] that the response was received in, if it was received by a [`TestContext`][crate::TestContext].
+    pub fn exchange(&self) -> Option<&Exchange> {
+        self.exchange.as_ref()
+    }
+}
+
+/// A predicate that failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failure {
+    /// Description of the predicate, i.e. `status is 200 OK`.
+    pub predicate: String,
+
+    /// Why the predicate failed, i.e. `got 404 Not Found`.
+    pub reason: String,
+}
+
+/// A check against a [`BufferedResponse`].
+pub trait Matcher {
+    /// Checks the response and returns every predicate that failed.
+    fn check(&self, res: &BufferedResponse) -> Vec<Failure>;
+
+    /// Returns how many predicates this matcher checks.
+    fn predicates(&self) -> usize {
+        1
+    }
+
+    /// Combines this matcher with another matcher; both must match.
+    fn and<M: Matcher + 'static>(self, other: M) -> And
+    where
+        Self: Sized + 'static,
+    {
+        And(vec![Box::new(self), Box::new(other)])
+    }
+}
+
+/// Matcher that checks all of its matchers, created by [`Matcher::and`].
+pub struct And(Vec<Box<dyn Matcher>>);
+
+impl Matcher for And {
+    fn check(&self, res: &BufferedResponse) -> Vec<Failure> {
+        self.0.iter().flat_map(|matcher| matcher.check(res)).collect()
+    }
+
+    fn predicates(&self) -> usize {
+        self.0.iter().map(|matcher| matcher.predicates()).sum()
+    }
+
+    fn and<M: Matcher + 'static>(mut self, other: M) -> And {
+        self.0.push(Box::new(other));
+        self
+    }
+}
+
+/// A check against a single value, like a header value or a JSON value.
+pub trait Predicate<T: ?Sized> {
+    /// Checks whenever if the value satisfies this predicate.
+    fn test(&self, value: &T) -> bool;
+
+    /// Describes this predicate, i.e. `contains "json"`.
+    fn describe(&self) -> String;
+}
+
+/// Predicate that checks that a value is equal to the given value.
+pub fn eq<T>(value: T) -> Eq<T> {
+    Eq(value)
+}
+
+/// Predicate created by [`eq`].
+#[derive(Debug, Clone)]
+pub struct Eq<T>(T);
+
+impl<T: AsRef<str>> Predicate<str> for Eq<T> {
+    fn test(&self, value: &str) -> bool {
+        value == self.0.as_ref()
+    }
+
+    fn describe(&self) -> String {
+        format!("is {:?}", self.0.as_ref())
+    }
+}
+
+/// Predicate that checks that a string contains the given substring. For JSON values, an array
+/// contains a string if any of its elements is that string.
+pub fn contains<S: Into<String>>(needle: S) -> Contains {
+    Contains(needle.into())
+}
+
+/// Predicate created by [`contains`].
+#[derive(Debug, Clone)]
+pub struct Contains(String);
+
+impl Predicate<str> for Contains {
+    fn test(&self, value: &str) -> bool {
+        value.contains(&self.0)
+    }
+
+    fn describe(&self) -> String {
+        format!("contains {:?}", self.0)
+    }
+}
+
+/// Predicate that checks that a string matches the given regular expression.
+///
+/// ## Panics
+/// This will panic if `pattern` isn't a valid regular expression.
+pub fn regex(pattern: &str) -> Matches {
+    Matches(Regex::new(pattern).expect("pattern to be a valid regular expression"))
+}
+
+/// Predicate created by [`regex`].
+#[derive(Debug, Clone)]
+pub struct Matches(Regex);
+
+impl Predicate<str> for Matches {
+    fn test(&self, value: &str) -> bool {
+        self.0.is_match(value)
+    }
+
+    fn describe(&self) -> String {
+        format!("matches `{}`", self.0)
+    }
+}
+
+/// Predicate that checks that a string is a hyphenated UUID.
+pub fn is_uuid() -> IsUuid {
+    IsUuid
+}
+
+/// Predicate created by [`is_uuid`].
+#[derive(Debug, Clone, Copy)]
+pub struct IsUuid;
+
+impl Predicate<str> for IsUuid {
+    fn test(&self, value: &str) -> bool {
+        crate::util::is_uuid(value)
+    }
+
+    fn describe(&self) -> String {
+        String::from("is a UUID")
+    }
+}
+
+/// Predicate that inverts another predicate.
+pub fn not<P>(predicate: P) -> Not<P> {
+    Not(predicate)
+}
+
+/// Predicate created by [`not`].
+#[derive(Debug, Clone)]
+pub struct Not<P>(P);
+
+impl<T: ?Sized, P: Predicate<T>> Predicate<T> for Not<P> {
+    fn test(&self, value: &T) -> bool {
+        !self.0.test(value)
+    }
+
+    fn describe(&self) -> String {
+        format!("not {}", self.0.describe())
+    }
+}
+
+/// Matcher that checks the status code of a response.
+///
+/// ## Panics
+/// This will panic if `status` isn't a valid status code.
+pub fn status<S>(status: S) -> Status
+where
+    S: TryInto<StatusCode>,
+    S::Error: Debug,
+{
+    Status(status.try_into().expect("status code to be valid"))
+}
+
+/// Matcher created by [`status`].
+#[derive(Debug, Clone)]
+pub struct Status(StatusCode);
+
+impl Matcher for Status {
+    fn check(&self, res: &BufferedResponse) -> Vec<Failure> {
+        if res.status == self.0 {
+            return Vec::new();
+        }
+
+        vec![Failure {
+            predicate: format!("status is {}", self.0),
+            reason: format!("got {}", res.status),
+        }]
+    }
+}
+
+/// Matcher that checks a header of a response with a [`Predicate`]. The matcher fails if the
+/// header is missing; every value of the header is checked if it was sent multiple times.
+///
+/// ## Panics
+/// This will panic if `name` isn't a valid header name.
+pub fn header<K, P>(name: K, predicate: P) -> Header<P>
+where
+    K: TryInto<HeaderName>,
+    K::Error: Debug,
+    P: Predicate<str>,
+{
+    Header {
+        name: name.try_into().expect("header name to be valid"),
+        predicate,
+    }
+}
+
+/// Matcher created by [`header`].
+#[derive(Debug, Clone)]
+pub struct Header<P> {
+    name: HeaderName,
+    predicate: P,
+}
+
+impl<P: Predicate<str>> Matcher for Header<P> {
+    fn check(&self, res: &BufferedResponse) -> Vec<Failure> {
+        let predicate = format!("header `{}` {}", self.name, self.predicate.describe());
+        let values = res.headers.get_all(&self.name).iter().collect::<Vec<_>>();
+        if values.is_empty() {
+            return vec![Failure {
+                predicate,
+                reason: String::from("header was missing"),
+            }];
+        }
+
+        for value in values {
+            let reason = match value.to_str() {
+                Ok(value) if self.predicate.test(value) => continue,
+                Ok(value) => format!("got {value:?}"),
+                Err(_) => format!("got a value that isn't visible ASCII: {value:?}"),
+            };
+
+            return vec![Failure { predicate, reason }];
+        }
+
+        Vec::new()
+    }
+}
+
+/// Matcher that checks the body of a response, as a UTF-8 string, with a [`Predicate`].
+pub fn body<P: Predicate<str>>(predicate: P) -> Body<P> {
+    Body(predicate)
+}
+
+/// Matcher created by [`body`].
+#[derive(Debug, Clone)]
+pub struct Body<P>(P);
+
+impl<P: Predicate<str>> Matcher for Body<P> {
+    fn check(&self, res: &BufferedResponse) -> Vec<Failure> {
+        let predicate = format!("body {}", self.0.describe());
+        match std::str::from_utf8(&res.body) {
+            Ok(body) if self.0.test(body) => Vec::new(),
+            Ok(_) => vec![Failure {
+                predicate,
+                reason: String::from("got a different body"),
+            }],
+
+            Err(_) => vec![Failure {
+                predicate,
+                reason: String::from("body isn't valid UTF-8"),
+            }],
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+pub use json_matchers::*;
+
+#[cfg(feature = "json")]
+mod json_matchers {
+    use super::{BufferedResponse, Contains, Eq, Failure, IsUuid, Matcher, Matches, Predicate};
+    use serde::Serialize;
+    use serde_json::Value;
+
+    impl<T: Serialize> Predicate<Value> for Eq<T> {
+        fn test(&self, value: &Value) -> bool {
+            serde_json::to_value(&self.0)
+                .is_ok_and(|expected| crate::json::diff(value, &expected, crate::json::Mode::Exact, &[]).is_empty())
+        }
+
+        fn describe(&self) -> String {
+            match serde_json::to_value(&self.0) {
+                Ok(value) => format!("is {value}"),
+                Err(_) => String::from("is <unserializable value>"),
+            }
+        }
+    }
+
+    impl Predicate<Value> for Contains {
+        fn test(&self, value: &Value) -> bool {
+            match value {
+                Value::String(s) => s.contains(&self.0),
+                Value::Array(values) => values.iter().any(|v| v.as_str() == Some(&self.0)),
+                _ => false,
+            }
+        }
+
+        fn describe(&self) -> String {
+            <Self as Predicate<str>>::describe(self)
+        }
+    }
+
+    impl Predicate<Value> for Matches {
+        fn test(&self, value: &Value) -> bool {
+            value.as_str().is_some_and(|s| self.0.is_match(s))
+        }
+
+        fn describe(&self) -> String {
+            <Self as Predicate<str>>::describe(self)
+        }
+    }
+
+    impl Predicate<Value> for IsUuid {
+        fn test(&self, value: &Value) -> bool {
+            value.as_str().is_some_and(crate::util::is_uuid)
+        }
+
+        fn describe(&self) -> String {
+            <Self as Predicate<str>>::describe(self)
+        }
+    }
+
+    /// Matcher that parses the body of a response as JSON and checks the value at `path` with a
+    /// [`Predicate`]. Paths use the same syntax as [`json`][crate::json] paths with an optional
+    /// leading `$` (`$.items[0].name`); if the path has a wildcard, every value that it selects
+    /// is checked.
+    ///
+    /// ## Panics
+    /// This will panic if `path` is an invalid path.
+    pub fn json_path<P: Predicate<Value>>(path: &str, predicate: P) -> JsonPath<P> {
+        assert!(
+            crate::json::select(&Value::Null, path).is_some(),
+            "invalid JSON path: `{path}`"
+        );
+
+        JsonPath {
+            path: path.to_owned(),
+            predicate,
+        }
+    }
+
+    /// Matcher created by [`json_path`].
+    #[derive(Debug, Clone)]
+    pub struct JsonPath<P> {
+        path: String,
+        predicate: P,
+    }
+
+    impl<P: Predicate<Value>> Matcher for JsonPath<P> {
+        fn check(&self, res: &BufferedResponse) -> Vec<Failure> {
+            let predicate = format!("json path `{}` {}", self.path, self.predicate.describe());
+            let body = match serde_json::from_slice::<Value>(&res.body) {
+                Ok(body) => body,
+                Err(err) => {
+                    return vec![Failure {
+                        predicate,
+                        reason: format!("body isn't valid JSON: {err}"),
+                    }]
+                }
+            };
+
+            let values = crate::json::select(&body, &self.path).unwrap_or_default();
+            let reason = match values.iter().find(|value| !self.predicate.test(value)) {
+                _ if values.is_empty() => String::from("path doesn't exist"),
+                Some(value) => format!("got {value}"),
+                None => return Vec::new(),
+            };
+
+            vec![Failure { predicate, reason }]
+        }
+    }
+}
+
+#[doc(hidden)]
+#[track_caller]
+pub fn __assert_response<M: Matcher + ?Sized>(res: &BufferedResponse, matcher: &M) {
+    let failures = matcher.check(res);
+    if failures.is_empty() {
+        return;
+    }
+
+    let mut message = format!(
+        "response didn't match {} of {} predicates:",
+        failures.len(),
+        matcher.predicates()
+    );
+
+    for failure in &failures {
+        let _ = write!(message, "\n    - {}: {}", failure.predicate, failure.reason);
+    }
+
+    match res.exchange {
+        Some(ref exchange) => {
+            let _ = write!(message, "\n\n{}", exchange.with_body(&res.body));
+        }
+
+        None => {
+            let _ = write!(
+                message,
+                "\nstatus: {}\nbody: {}",
+                res.status,
+                crate::util::display_body(&res.body)
+            );
+        }
+    }
+
+    crate::soft::__fail(message);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::header;
+
+    fn response() -> BufferedResponse {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_TYPE, "text/plain; charset=utf-8".parse().unwrap());
+        headers.append(header::VARY, "accept-encoding".parse().unwrap());
+        headers.append(header::VARY, "origin".parse().unwrap());
+
+        BufferedResponse::new(
+            StatusCode::OK,
+            headers,
+            Bytes::from_static(br#"{"id":1,"tags":["a","b"],"owner":{"id":"9f0b6a5e-2d8c-4f6e-a8f7-5d1c6c1a2b3c"}}"#),
+        )
+    }
+
+    #[test]
+    fn leaf_matchers() {
+        let res = response();
+
+        assert!(status(200).check(&res).is_empty());
+        assert!(status(StatusCode::OK).check(&res).is_empty());
+        assert_eq!(
+            status(404).check(&res),
+            [Failure {
+                predicate: String::from("status is 404 Not Found"),
+                reason: String::from("got 200 OK"),
+            }]
+        );
+
+        assert!(header("content-type", contains("text")).check(&res).is_empty());
+        assert!(header(header::CONTENT_TYPE, regex("^text/.+")).check(&res).is_empty());
+        assert!(header(header::VARY, not(eq("*"))).check(&res).is_empty());
+        assert_eq!(
+            header(header::VARY, eq("origin")).check(&res)[0].reason,
+            "got \"accept-encoding\""
+        );
+        assert_eq!(
+            header("etag", contains("1")).check(&res)[0].reason,
+            "header was missing"
+        );
+
+        assert!(body(contains("\"tags\"")).check(&res).is_empty());
+        assert_eq!(body(eq("{}")).check(&res)[0].predicate, "body is \"{}\"");
+    }
+
+    #[test]
+    fn combined_matchers() {
+        let res = response();
+        let matcher = status(201)
+            .and(header("content-type", contains("json")))
+            .and(header("vary", contains("-")))
+            .and(body(not(contains("id"))));
+
+        assert_eq!(matcher.predicates(), 4);
+        assert_eq!(
+            matcher
+                .check(&res)
+                .into_iter()
+                .map(|failure| failure.predicate)
+                .collect::<Vec<_>>(),
+            [
+                "status is 201 Created",
+                "header `content-type` contains \"json\"",
+                "header `vary` contains \"-\"",
+                "body not contains \"id\"",
+            ]
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_matchers() {
+        let res = response();
+
+        assert!(json_path("$.id", eq(1)).check(&res).is_empty());
+        assert!(json_path("$.tags", contains("b")).check(&res).is_empty());
+        assert!(json_path("$.owner.id", is_uuid()).check(&res).is_empty());
+        assert!(json_path("$.tags[*]", regex("^[a-z]$")).check(&res).is_empty());
+        assert!(
+            json_path("$.owner", eq(serde_json::json!({ "id": crate::json::any_uuid() })))
+                .check(&res)
+                .is_empty()
+        );
+
+        assert_eq!(
+            json_path("$.id", is_uuid()).check(&res),
+            [Failure {
+                predicate: String::from("json path `$.id` is a UUID"),
+                reason: String::from("got 1"),
+            }]
+        );
+
+        assert_eq!(
+            json_path("$.missing", eq(1)).check(&res)[0].reason,
+            "path doesn't exist"
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "response didn't match 2 of 3 predicates:\n    - status is 204 No Content: got 200 OK\n    - header `content-type` contains \"json\""
+    )]
+    fn assert_response_reports_every_failure() {
+        __assert_response(
+            &response(),
+            &status(204)
+                .and(header("content-type", contains("json")))
+                .and(header("content-type", contains("text"))),
+        );
+    }
+}
@@ -0,0 +1,546 @@
+// 📦🦋 charted TestKit: testing library for Axum services with testcontainers support
+// Copyright (c) 2024 Noelware, LLC. <team@noelware.org>
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Mock upstream servers for services that call other HTTP APIs.
+//!
+//! A [`MockServer`] is served like the ephemeral server of a [`TestContext`] and responds to
+//! the requests that match one of its [`Mock`]s with a canned response. Every mock expects to
+//! be called at least once unless [`Mock::times`] says otherwise. When the server is dropped
+//! (or [verified][MockServer::verify]), the test fails if a mock wasn't called as often as it
+//! expected or if the server received a request that no mock matched.
+//!
+//! ## Example
+//! ```rust,no_run
+//! # use charted_testkit::{mock::{Mock, MockServer}, matchers::eq};
+//! # use axum::http::{Method, StatusCode};
+//! #
+//! # #[tokio::main]
+//! # async fn main() {
+//! let upstream = MockServer::start().await;
+//! upstream.expect(
+//!     Mock::new(Method::POST, "/v1/charges")
+//!         .header("authorization", eq("Bearer sk_test"))
+//!         .respond_with(|| (StatusCode::CREATED, r#"{"id":"ch_1"}"#))
+//!         .times(1),
+//! );
+//!
+//! // point the service under test at `upstream.url()` and exercise it...
+//! # }
+//! ```
+
+use crate::{matchers::Predicate, TestContext};
+use axum::{
+    body::{Body, Bytes},
+    extract::Request,
+    http::{HeaderMap, HeaderName, Method, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    Router,
+};
+use std::{
+    fmt::{Debug, Display, Write},
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+/// A request that was received by a [`MockServer`].
+#[derive(Debug, Clone)]
+pub struct ReceivedRequest {
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+impl ReceivedRequest {
+    /// Method of the request.
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+
+    /// URI of the request, which only has a path and query.
+    pub fn uri(&self) -> &Uri {
+        &self.uri
+    }
+
+    /// Headers of the request.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Body of the request.
+    pub fn body(&self) -> &Bytes {
+        &self.body
+    }
+}
+
+impl Display for ReceivedRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.method, self.uri)
+    }
+}
+
+/// How many times a [`Mock`] expects to be called.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Times {
+    Exactly(usize),
+    AtLeast(usize),
+}
+
+impl Times {
+    fn matches(self, calls: usize) -> bool {
+        match self {
+            Times::Exactly(n) => calls == n,
+            Times::AtLeast(n) => calls >= n,
+        }
+    }
+}
+
+impl Display for Times {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Times::Exactly(n) => write!(f, "exactly {n}"),
+            Times::AtLeast(n) => write!(f, "at least {n}"),
+        }
+    }
+}
+
+type RequestPredicate = Box<dyn Fn(&ReceivedRequest) -> bool + Send + Sync>;
+
+/// An expectation of a [`MockServer`]: requests with its method and path that match all of its
+/// predicates are responded to with its response.
+pub struct Mock {
+    method: Method,
+    path: String,
+    predicates: Vec<(String, RequestPredicate)>,
+    respond: Arc<dyn Fn() -> Response + Send + Sync>,
+    times: Times,
+}
+
+impl Debug for Mock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Mock")
+            .field("method", &self.method)
+            .field("path", &self.path)
+            .field(
+                "predicates",
+                &self.predicates.iter().map(|(describe, _)| describe).collect::<Vec<_>>(),
+            )
+            .field("times", &self.times)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Display for Mock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.method, self.path)?;
+        for (describe, _) in &self.predicates {
+            write!(f, ", {describe}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Mock {
+    /// Creates a [`Mock`] for requests with `method` to `path`, which is compared to the path of
+    /// the request without its query string. It responds with an empty `200 OK` response by default.
+    pub fn new<P: Into<String>>(method: Method, path: P) -> Mock {
+        Mock {
+            method,
+            path: path.into(),
+            predicates: Vec::new(),
+            respond: Arc::new(|| StatusCode::OK.into_response()),
+            times: Times::AtLeast(1),
+        }
+    }
+
+    /// Only matches requests with a header that satisfies `predicate`.
+    ///
+    /// ## Panics
+    /// This will panic if `name` isn't a valid header name.
+    pub fn header<K, P>(mut self, name: K, predicate: P) -> Self
+    where
+        K: TryInto<HeaderName>,
+        K::Error: Debug,
+        P: Predicate<str> + Send + Sync + 'static,
+    {
+        let name = name.try_into().expect("header name to be valid");
+        let describe = format!("header `{name}` {}", predicate.describe());
+
+        self.predicates.push((
+            describe,
+            Box::new(move |req| {
+                req.headers
+                    .get(&name)
+                    .and_then(|value| value.to_str().ok())
+                    .is_some_and(|value| predicate.test(value))
+            }),
+        ));
+
+        self
+    }
+
+    /// Only matches requests with a query parameter that satisfies `predicate`.
+    pub fn query<K: Into<String>, P: Predicate<str> + Send + Sync + 'static>(mut self, name: K, predicate: P) -> Self {
+        let name = name.into();
+        let describe = format!("query `{name}` {}", predicate.describe());
+
+        self.predicates.push((
+            describe,
+            Box::new(move |req| {
+                req.uri
+                    .query()
+                    .unwrap_or_default()
+                    .split('&')
+                    .filter_map(|pair| pair.split_once('=').or(Some((pair, ""))))
+                    .any(|(key, value)| key == name && predicate.test(value))
+            }),
+        ));
+
+        self
+    }
+
+    /// Only matches requests whose body, as a UTF-8 string, satisfies `predicate`.
+    pub fn body<P: Predicate<str> + Send + Sync + 'static>(mut self, predicate: P) -> Self {
+        let describe = format!("body {}", predicate.describe());
+        self.predicates.push((
+            describe,
+            Box::new(move |req| std::str::from_utf8(&req.body).is_ok_and(|body| predicate.test(body))),
+        ));
+
+        self
+    }
+
+    /// Only matches requests whose body is JSON that is equal to `value`.
+    ///
+    /// ## Panics
+    /// This will panic if `value` can't be serialized into JSON.
+    #[cfg(feature = "json")]
+    pub fn json_body<T: serde::Serialize>(mut self, value: T) -> Self {
+        let expected = serde_json::to_value(value).expect("value to be serializable into JSON");
+        let describe = format!("JSON body is {expected}");
+
+        self.predicates.push((
+            describe,
+            Box::new(move |req| {
+                serde_json::from_slice::<serde_json::Value>(&req.body).is_ok_and(|body| body == expected)
+            }),
+        ));
+
+        self
+    }
+
+    /// Sets the response of this mock, which is created for every request that it matches.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use charted_testkit::mock::Mock;
+    /// # use axum::http::{Method, StatusCode, header};
+    /// #
+    /// let mock = Mock::new(Method::GET, "/v1/users/1").respond_with(|| {
+    ///     (StatusCode::OK, [(header::CONTENT_TYPE, "application/json")], r#"{"id":1}"#)
+    /// });
+    /// ```
+    pub fn respond_with<F, R>(mut self, respond: F) -> Self
+    where
+        F: Fn() -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        self.respond = Arc::new(move || respond().into_response());
+        self
+    }
+
+    /// Expects this mock to be called exactly `n` times.
+    pub fn times(mut self, n: usize) -> Self {
+        self.times = Times::Exactly(n);
+        self
+    }
+
+    /// Expects this mock to be called at least `n` times, which is `1` by default.
+    pub fn at_least(mut self, n: usize) -> Self {
+        self.times = Times::AtLeast(n);
+        self
+    }
+
+    fn matches(&self, req: &ReceivedRequest) -> bool {
+        req.method == self.method
+            && req.uri.path() == self.path
+            && self.predicates.iter().all(|(_, predicate)| predicate(req))
+    }
+}
+
+#[derive(Default)]
+struct State {
+    mocks: Mutex<Vec<(Mock, usize)>>,
+    received: Mutex<Vec<ReceivedRequest>>,
+    unexpected: Mutex<Vec<ReceivedRequest>>,
+}
+
+impl State {
+    async fn handle(&self, req: Request) -> Response {
+        let (parts, body) = req.into_parts();
+        let body = match axum::body::to_bytes(body, usize::MAX).await {
+            Ok(body) => body,
+            Err(err) => return (StatusCode::BAD_REQUEST, format!("failed to read body: {err}")).into_response(),
+        };
+
+        let req = ReceivedRequest {
+            method: parts.method,
+            uri: parts.uri,
+            headers: parts.headers,
+            body,
+        };
+
+        lock(&self.received).push(req.clone());
+
+        let respond = lock(&self.mocks)
+            .iter_mut()
+            .find(|(mock, _)| mock.matches(&req))
+            .map(|(mock, calls)| {
+                *calls += 1;
+                mock.respond.clone()
+            });
+
+        match respond {
+            Some(respond) => respond(),
+            None => {
+                let message = format!("no mock matched `{req}`");
+                lock(&self.unexpected).push(req);
+
+                (StatusCode::NOT_FOUND, message).into_response()
+            }
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// An HTTP server that responds to requests with [`Mock`]s and verifies that they were called
+/// as expected once it is dropped.
+pub struct MockServer {
+    ctx: TestContext,
+    state: Arc<State>,
+}
+
+impl Debug for MockServer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MockServer")
+            .field("addr", &self.addr())
+            .field("mocks", &*lock(&self.state.mocks))
+            .finish_non_exhaustive()
+    }
+}
+
+impl MockServer {
+    /// Starts a [`MockServer`] on an ephemeral port.
+    pub async fn start() -> MockServer {
+        let state = Arc::new(State::default());
+        let handler = state.clone();
+
+        let mut ctx = TestContext::default();
+        ctx.serve(Router::new().fallback(move |req: Request<Body>| {
+            let state = handler.clone();
+            async move { state.handle(req).await }
+        }))
+        .await;
+
+        MockServer { ctx, state }
+    }
+
+    /// Returns the address that the server listens on.
+    pub fn addr(&self) -> SocketAddr {
+        *self.ctx.server_addr().expect("mock server to be served")
+    }
+
+    /// Returns the URL of the server (i.e, `http://127.0.0.1:34567`), which the service under test
+    /// should send its requests to.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr())
+    }
+
+    /// Adds an expectation, which is checked before the expectations that were added after it.
+    pub fn expect(&self, mock: Mock) {
+        lock(&self.state.mocks).push((mock, 0));
+    }
+
+    /// Returns every request that the server received so far.
+    pub fn received_requests(&self) -> Vec<ReceivedRequest> {
+        lock(&self.state.received).clone()
+    }
+
+    /// Fails the test if a [`Mock`] wasn't called as often as it expected or if a request didn't
+    /// match any mock, and resets the expectations and received requests.
+    #[track_caller]
+    pub fn verify(&self) {
+        let mocks = std::mem::take(&mut *lock(&self.state.mocks));
+        let unexpected = std::mem::take(&mut *lock(&self.state.unexpected));
+        lock(&self.state.received).clear();
+
+        let mut problems = Vec::new();
+        for (mock, calls) in &mocks {
+            if !mock.times.matches(*calls) {
+                problems.push(format!(
+                    "`{mock}` was called {calls} time{}, expected {} time{}",
+                    plural(*calls),
+                    mock.times,
+                    plural(match mock.times {
+                        Times::Exactly(n) | Times::AtLeast(n) => n,
+                    })
+                ));
+            }
+        }
+
+        problems.extend(unexpected.iter().map(|req| format!("unexpected request `{req}`")));
+        if problems.is_empty() {
+            return;
+        }
+
+        let mut message = format!("mock server at {} wasn't used as expected:", self.url());
+        for problem in problems {
+            let _ = write!(message, "\n    - {problem}");
+        }
+
+        crate::soft::__fail(message);
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        if !std::thread::panicking() {
+            self.verify();
+        }
+    }
+}
+
+fn plural(n: usize) -> &'static str {
+    match n {
+        1 => "",
+        _ => "s",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Mock, MockServer};
+    use crate::{consume_body, matchers::eq, TestContext};
+    use axum::{
+        body::Bytes,
+        http::{header, Method, StatusCode},
+    };
+
+    async fn send(upstream: &MockServer, method: Method, uri: &'static str, body: &'static str) -> (StatusCode, Bytes) {
+        let ctx = TestContext::from_base_url(upstream.url());
+        let res = ctx
+            .request(uri, method, Bytes::from_static(body.as_bytes()), |req| {
+                req.headers_mut()
+                    .insert(header::AUTHORIZATION, "Bearer sk_test".parse().unwrap());
+            })
+            .await
+            .expect("unable to send request");
+
+        (res.status(), consume_body!(res))
+    }
+
+    #[tokio::test]
+    #[cfg_attr(
+        windows,
+        ignore = "fails on Windows: hyper_util::client::legacy::Error(Connect, ConnectError(\"tcp connect error\", Os { code: 10049, kind: AddrNotAvailable, message: \"The requested address is not valid in its context.\" })))"
+    )]
+    async fn expectations() {
+        let upstream = MockServer::start().await;
+        upstream.expect(
+            Mock::new(Method::POST, "/v1/charges")
+                .header("authorization", eq("Bearer sk_test"))
+                .query("currency", eq("usd"))
+                .body(eq("amount=100"))
+                .respond_with(|| (StatusCode::CREATED, "ch_1"))
+                .times(1),
+        );
+
+        upstream.expect(Mock::new(Method::GET, "/v1/charges/ch_1").at_least(2));
+
+        let (status, body) = send(&upstream, Method::POST, "/v1/charges?currency=usd", "amount=100").await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(body, Bytes::from_static(b"ch_1"));
+
+        for _ in 0..3 {
+            let (status, _) = send(&upstream, Method::GET, "/v1/charges/ch_1", "").await;
+            assert_eq!(status, StatusCode::OK);
+        }
+
+        let received = upstream.received_requests();
+        assert_eq!(received.len(), 4);
+        assert_eq!(received[0].to_string(), "POST /v1/charges?currency=usd");
+        assert_eq!(received[0].body(), &Bytes::from_static(b"amount=100"));
+    }
+
+    #[cfg(feature = "json")]
+    #[tokio::test]
+    #[cfg_attr(
+        windows,
+        ignore = "fails on Windows: hyper_util::client::legacy::Error(Connect, ConnectError(\"tcp connect error\", Os { code: 10049, kind: AddrNotAvailable, message: \"The requested address is not valid in its context.\" })))"
+    )]
+    async fn json_body() {
+        let upstream = MockServer::start().await;
+        upstream.expect(Mock::new(Method::PUT, "/v1/users/1").json_body(serde_json::json!({ "name": "noel" })));
+
+        let (status, _) = send(&upstream, Method::PUT, "/v1/users/1", r#"{ "name": "noel" }"#).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    #[cfg_attr(
+        windows,
+        ignore = "fails on Windows: hyper_util::client::legacy::Error(Connect, ConnectError(\"tcp connect error\", Os { code: 10049, kind: AddrNotAvailable, message: \"The requested address is not valid in its context.\" })))"
+    )]
+    #[should_panic(
+        expected = "wasn't used as expected:\n    - `POST /v1/charges, header `authorization` is \"Bearer sk_live\"` was called 0 times, expected exactly 1 time\n    - unexpected request `POST /v1/charges`"
+    )]
+    async fn unmet_and_unexpected_calls_fail_on_drop() {
+        let upstream = MockServer::start().await;
+        upstream.expect(
+            Mock::new(Method::POST, "/v1/charges")
+                .header("authorization", eq("Bearer sk_live"))
+                .times(1),
+        );
+
+        let (status, body) = send(&upstream, Method::POST, "/v1/charges", "").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body, Bytes::from_static(b"no mock matched `POST /v1/charges`"));
+    }
+
+    #[tokio::test]
+    #[cfg_attr(
+        windows,
+        ignore = "fails on Windows: hyper_util::client::legacy::Error(Connect, ConnectError(\"tcp connect error\", Os { code: 10049, kind: AddrNotAvailable, message: \"The requested address is not valid in its context.\" })))"
+    )]
+    #[should_panic(expected = "`GET /v1/health` was called 2 times, expected exactly 1 time")]
+    async fn too_many_calls_fail() {
+        let upstream = MockServer::start().await;
+        upstream.expect(Mock::new(Method::GET, "/v1/health").times(1));
+
+        send(&upstream, Method::GET, "/v1/health", "").await;
+        send(&upstream, Method::GET, "/v1/health", "").await;
+        upstream.verify();
+    }
+}
@@ -0,0 +1,774 @@
+// 📦🦋 charted TestKit: testing library for Axum services with testcontainers support
+// Copyright (c) 2024 Noelware, LLC. <team@noelware.org>
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Contract validation of responses against an OpenAPI 3.x document.
+//!
+//! When an [`OpenApiSpec`] is set with [`TestContext::set_openapi`][crate::TestContext::set_openapi], every
+//! response that is received is validated against the operation that matches its request:
+//!
+//! * the request's path and method must be documented (unless [`OpenApiSpec::strict`] is disabled),
+//! * the status code must be documented, either exactly, as a range (`4XX`) or with `default`,
+//! * the `Content-Type` must be one of the documented media types, and
+//! * JSON bodies must be valid against the media type's schema.
+//!
+//! Violations fail the test with a report that lists every violation, and are recorded as soft
+//! assertion failures if a [`SoftAssertions`][crate::soft::SoftAssertions] guard is alive. Schemas
+//! of OpenAPI 3.1 documents are validated as JSON Schema draft 2020-12, and schemas of OpenAPI 3.0
+//! documents as draft 4 with support for `nullable`.
+//!
+//! ## Example
+//! ```rust,no_run
+//! # use charted_testkit::{TestContext, openapi::OpenApiSpec};
+//! # use axum::http::Method;
+//! #
+//! # #[tokio::main]
+//! # async fn main() {
+//! let ctx = TestContext::default();
+//! ctx.set_openapi(OpenApiSpec::from_file("assets/openapi.json").unwrap());
+//!
+//! // fails if the response doesn't match the `GET /repositories/{id}` operation
+//! let res = ctx.request("/repositories/1", Method::GET, None, charted_testkit::noop_request).await.unwrap();
+//! # }
+//! ```
+
+use crate::exchange::Exchange;
+use crate::schema::Schema;
+use jsonschema::Draft;
+use serde_json::{Map, Value};
+use std::{
+    collections::HashMap,
+    fmt::{Debug, Display, Write},
+    path::Path,
+    str::FromStr,
+    sync::Mutex,
+};
+
+/// Error type for loading an [`OpenApiSpec`].
+#[derive(Debug)]
+pub enum Error {
+    /// The document couldn't be read.
+    Io(std::io::Error),
+
+    /// The document isn't valid JSON or YAML.
+    Parse(String),
+
+    /// The document isn't an OpenAPI 3.x document.
+    UnsupportedVersion(Option<String>),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "failed to read OpenAPI document: {err}"),
+            Error::Parse(message) => write!(f, "failed to parse OpenAPI document: {message}"),
+            Error::UnsupportedVersion(Some(version)) => {
+                write!(f, "unsupported OpenAPI version `{version}`, expected 3.x")
+            }
+
+            Error::UnsupportedVersion(None) => f.write_str("document doesn't have an `openapi` version"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// An OpenAPI 3.x document that responses are validated against.
+pub struct OpenApiSpec {
+    document: Value,
+    draft: Draft,
+    strict: bool,
+    prefixes: Vec<String>,
+    validators: Mutex<HashMap<String, Schema>>,
+}
+
+impl Debug for OpenApiSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OpenApiSpec")
+            .field("version", &self.document.get("openapi"))
+            .field("strict", &self.strict)
+            .field("prefixes", &self.prefixes)
+            .finish_non_exhaustive()
+    }
+}
+
+impl OpenApiSpec {
+    /// Creates a [`OpenApiSpec`] from a parsed document.
+    pub fn from_value(document: Value) -> Result<OpenApiSpec, Error> {
+        let version = document.get("openapi").and_then(Value::as_str).map(str::to_owned);
+        let draft = match version.as_deref() {
+            Some(v) if v.starts_with("3.0") => Draft::Draft4,
+            Some(v) if v.starts_with("3.") => Draft::Draft202012,
+            _ => return Err(Error::UnsupportedVersion(version)),
+        };
+
+        // paths are relative to the path of the server URLs, i.e. `/api/v1`
+        let mut prefixes = document
+            .get("servers")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|server| server.get("url").and_then(Value::as_str))
+            .map(|url| {
+                let path = match url.find("://") {
+                    Some(idx) => url[idx + 3..].find('/').map_or("", |start| &url[idx + 3 + start..]),
+                    None => url,
+                };
+
+                path.trim_end_matches('/').to_owned()
+            })
+            .filter(|path| !path.is_empty())
+            .collect::<Vec<_>>();
+
+        // try the longest prefix first
+        prefixes.sort_by_key(|prefix| std::cmp::Reverse(prefix.len()));
+
+        Ok(OpenApiSpec {
+            document,
+            draft,
+            strict: true,
+            prefixes,
+            validators: Mutex::default(),
+        })
+    }
+
+    /// Loads a JSON or YAML document from a file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<OpenApiSpec, Error> {
+        std::fs::read_to_string(path).map_err(Error::Io)?.parse()
+    }
+
+    /// Creates a [`OpenApiSpec`] from a document that was generated with [`utoipa`].
+    #[cfg(feature = "utoipa")]
+    pub fn from_utoipa(openapi: &utoipa::openapi::OpenApi) -> Result<OpenApiSpec, Error> {
+        OpenApiSpec::from_value(serde_json::to_value(openapi).map_err(|e| Error::Parse(e.to_string()))?)
+    }
+
+    /// Fails responses to requests whose path or method isn't documented, which is enabled by
+    /// default. When disabled, they aren't validated at all.
+    pub fn strict(mut self, yes: bool) -> Self {
+        self.strict = yes;
+        self
+    }
+
+    /// Validates an exchange against the document.
+    pub fn validate(&self, exchange: &Exchange) -> Result<(), Report> {
+        let mut report = Report {
+            request: format!("{} {}", exchange.method(), exchange.uri().path()),
+            operation: None,
+            violations: Vec::new(),
+        };
+
+        let path = self.strip_prefix(exchange.uri().path());
+        let Some((template, item)) = self.find_path(path) else {
+            if self.strict {
+                report
+                    .violations
+                    .push(format!("no path in the document matches `{path}`"));
+                return Err(report);
+            }
+
+            return Ok(());
+        };
+
+        let method = exchange.method().as_str().to_ascii_lowercase();
+        let Some(operation) = item.get(&method).map(|op| self.resolve(op)) else {
+            if self.strict {
+                report
+                    .violations
+                    .push(format!("`{}` isn't documented for `{template}`", exchange.method()));
+
+                return Err(report);
+            }
+
+            return Ok(());
+        };
+
+        report.operation = Some(
+            operation
+                .get("operationId")
+                .and_then(Value::as_str)
+                .map(str::to_owned)
+                .unwrap_or_else(|| format!("{} {template}", exchange.method())),
+        );
+
+        let responses = operation
+            .get("responses")
+            .and_then(Value::as_object)
+            .cloned()
+            .unwrap_or_default();
+
+        let status = exchange.status().as_u16().to_string();
+        let range = format!("{}XX", &status[..1]);
+        let Some((status_key, response)) = responses
+            .iter()
+            .find(|(key, _)| **key == status)
+            .or_else(|| responses.iter().find(|(key, _)| key.eq_ignore_ascii_case(&range)))
+            .or_else(|| responses.iter().find(|(key, _)| *key == "default"))
+        else {
+            report.violations.push(format!(
+                "status code {} isn't documented (documented: {})",
+                exchange.status(),
+                list(responses.keys())
+            ));
+
+            return Err(report);
+        };
+
+        let response = self.resolve(response);
+        let content = response.get("content").and_then(Value::as_object);
+        let body = exchange.response_body();
+        let essence = crate::util::content_type_essence(exchange.response_headers());
+
+        let Some(content) = content.filter(|content| !content.is_empty()) else {
+            if body.is_some_and(|body| !body.is_empty()) {
+                report.violations.push(format!(
+                    "response has a body, but `{status_key}` doesn't document any content"
+                ));
+            }
+
+            return finish(report);
+        };
+
+        let Some(essence) = essence else {
+            report.violations.push(format!(
+                "response doesn't have a `Content-Type` header (documented: {})",
+                list(content.keys())
+            ));
+
+            return Err(report);
+        };
+
+        let Some((media_type, media)) = find_media_type(content, &essence) else {
+            report.violations.push(format!(
+                "content type `{essence}` isn't documented (documented: {})",
+                list(content.keys())
+            ));
+
+            return Err(report);
+        };
+
+        let is_json = essence == "application/json" || essence.ends_with("+json");
+        let (Some(schema), Some(body), true) = (media.get("schema"), body, is_json) else {
+            return finish(report);
+        };
+
+        let instance = match serde_json::from_slice::<Value>(body) {
+            Ok(instance) => instance,
+            Err(err) => {
+                report.violations.push(format!("response body isn't valid JSON: {err}"));
+                return Err(report);
+            }
+        };
+
+        let key = format!("{template} {method} {status_key} {media_type}");
+        match self.validator(&key, schema) {
+            Ok(validator) => {
+                if let Err(violations) = validator.validate(&instance) {
+                    report.violations.extend(violations.iter().map(ToString::to_string));
+                }
+            }
+
+            Err(err) => report.violations.push(format!("{err} (schema of `{media_type}`)")),
+        }
+
+        finish(report)
+    }
+
+    fn strip_prefix<'a>(&self, path: &'a str) -> &'a str {
+        self.prefixes
+            .iter()
+            .find_map(|prefix| {
+                path.strip_prefix(prefix.as_str())
+                    .filter(|rest| rest.is_empty() || rest.starts_with('/'))
+            })
+            .map(|rest| if rest.is_empty() { "/" } else { rest })
+            .unwrap_or(path)
+    }
+
+    /// Finds the path item that matches `path`, preferring templates with more literal segments.
+    fn find_path(&self, path: &str) -> Option<(&str, &Value)> {
+        let segments = path.trim_matches('/').split('/').collect::<Vec<_>>();
+        let paths = self.document.get("paths")?.as_object()?;
+
+        paths
+            .iter()
+            .filter_map(|(template, item)| {
+                let parts = template.trim_matches('/').split('/').collect::<Vec<_>>();
+                if parts.len() != segments.len() {
+                    return None;
+                }
+
+                let mut literals = 0;
+                for (part, segment) in parts.iter().zip(&segments) {
+                    if part.starts_with('{') && part.ends_with('}') {
+                        if segment.is_empty() {
+                            return None;
+                        }
+                    } else if part == segment {
+                        literals += 1;
+                    } else {
+                        return None;
+                    }
+                }
+
+                Some((literals, template.as_str(), item))
+            })
+            .max_by_key(|(literals, ..)| *literals)
+            .map(|(_, template, item)| (template, self.resolve(item)))
+    }
+
+    /// Resolves a `$ref` to a local component, i.e. `#/components/responses/NotFound`.
+    fn resolve<'a>(&'a self, value: &'a Value) -> &'a Value {
+        let mut value = value;
+
+        // references can point to other references, but not forever
+        for _ in 0..32 {
+            let Some(reference) = value.get("$ref").and_then(Value::as_str) else {
+                break;
+            };
+
+            match reference
+                .strip_prefix('#')
+                .and_then(|pointer| self.document.pointer(pointer))
+            {
+                Some(resolved) => value = resolved,
+                None => break,
+            }
+        }
+
+        value
+    }
+
+    fn validator(&self, key: &str, schema: &Value) -> Result<Schema, crate::schema::Error> {
+        let mut validators = self.validators.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(validator) = validators.get(key) {
+            return Ok(validator.clone());
+        }
+
+        // `$ref`s are resolved relative to the root of the schema, so the
+        // components are copied into it
+        let mut schema = schema.clone();
+        if let (Value::Object(map), Some(components)) = (&mut schema, self.document.get("components")) {
+            map.entry("components").or_insert_with(|| components.clone());
+        }
+
+        if self.draft == Draft::Draft4 {
+            convert_nullable(&mut schema);
+        }
+
+        let validator = Schema::compile(&schema, Some(self.draft))?;
+
+        validators.insert(key.to_owned(), validator.clone());
+        Ok(validator)
+    }
+}
+
+impl FromStr for OpenApiSpec {
+    type Err = Error;
+
+    /// Parses a JSON or YAML document.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let document = match serde_json::from_str::<Value>(s) {
+            Ok(document) => document,
+            Err(_) => serde_yaml::from_str::<Value>(s).map_err(|e| Error::Parse(e.to_string()))?,
+        };
+
+        OpenApiSpec::from_value(document)
+    }
+}
+
+/// Every violation of an exchange against an [`OpenApiSpec`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    request: String,
+    operation: Option<String>,
+    violations: Vec<String>,
+}
+
+impl Report {
+    /// Id of the operation that the exchange was validated against, or its method and path
+    /// if it doesn't have an id.
+    pub fn operation(&self) -> Option<&str> {
+        self.operation.as_deref()
+    }
+
+    /// Every violation, like `/name: 1 is not of type "string" (at schema path `/properties/name/type`)`.
+    pub fn violations(&self) -> &[String] {
+        &self.violations
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "response to `{}`", self.request)?;
+        if let Some(ref operation) = self.operation {
+            write!(f, " (operation `{operation}`)")?;
+        }
+
+        write!(f, " violates the OpenAPI document:")?;
+        for violation in &self.violations {
+            write!(f, "\n    - {violation}")?;
+        }
+
+        Ok(())
+    }
+}
+
+fn finish(report: Report) -> Result<(), Report> {
+    match report.violations.is_empty() {
+        true => Ok(()),
+        false => Err(report),
+    }
+}
+
+fn list<'a, I: Iterator<Item = &'a String>>(keys: I) -> String {
+    let mut rendered = String::new();
+    for (idx, key) in keys.enumerate() {
+        if idx > 0 {
+            rendered.push_str(", ");
+        }
+
+        let _ = write!(rendered, "{key}");
+    }
+
+    if rendered.is_empty() {
+        rendered.push_str("nothing");
+    }
+
+    rendered
+}
+
+/// Finds the media type that matches `essence`, preferring exact matches over `type/*` and `*/*`.
+fn find_media_type<'a>(content: &'a Map<String, Value>, essence: &str) -> Option<(&'a str, &'a Value)> {
+    let key_essence = |key: &str| key.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+    let wildcard = format!("{}/*", essence.split('/').next().unwrap_or_default());
+
+    let found = [essence, wildcard.as_str(), "*/*"].into_iter().find_map(|candidate| {
+        content
+            .iter()
+            .find(|(key, _)| key_essence(key) == candidate)
+            .map(|(key, value)| (key.as_str(), value))
+    });
+
+    found
+}
+
+/// Converts OpenAPI 3.0's `nullable: true` into a JSON Schema `null` type.
+fn convert_nullable(schema: &mut Value) {
+    match schema {
+        Value::Object(map) => {
+            if map.remove("nullable") == Some(Value::Bool(true)) {
+                match map.get_mut("type") {
+                    Some(Value::String(ty)) => {
+                        let ty = std::mem::take(ty);
+                        map.insert(String::from("type"), Value::from(vec![ty, String::from("null")]));
+                    }
+
+                    Some(_) => {}
+
+                    // schemas without a type (like `allOf` compositions) can be anything
+                    // already, but `enum`s have to allow `null` explicitly
+                    None => {
+                        if let Some(Value::Array(values)) = map.get_mut("enum") {
+                            values.push(Value::Null);
+                        }
+                    }
+                }
+            }
+
+            map.values_mut().for_each(convert_nullable);
+        }
+
+        Value::Array(values) => values.iter_mut().for_each(convert_nullable),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OpenApiSpec;
+    use crate::exchange::Exchange;
+    use axum::{
+        body::Bytes,
+        http::{header, HeaderMap, HeaderValue, Method, StatusCode, Uri, Version},
+    };
+    use serde_json::json;
+
+    fn spec(version: &str) -> OpenApiSpec {
+        OpenApiSpec::from_value(json!({
+            "openapi": version,
+            "info": { "title": "charted", "version": "0.1.0" },
+            "servers": [{ "url": "https://charts.noelware.org/api/v1" }],
+            "paths": {
+                "/repositories/{id}": {
+                    "get": {
+                        "operationId": "getRepository",
+                        "responses": {
+                            "200": {
+                                "description": "ok",
+                                "content": {
+                                    "application/json": {
+                                        "schema": { "$ref": "#/components/schemas/Repository" }
+                                    }
+                                }
+                            },
+                            "4XX": { "$ref": "#/components/responses/Error" }
+                        }
+                    }
+                },
+                "/repositories/latest": {
+                    "get": {
+                        "responses": { "204": { "description": "no content" } }
+                    }
+                }
+            },
+            "components": {
+                "schemas": {
+                    "Repository": {
+                        "type": "object",
+                        "required": ["id", "name"],
+                        "properties": {
+                            "id": { "type": "integer" },
+                            "name": { "type": "string" },
+                            "description": { "type": "string", "nullable": true }
+                        }
+                    }
+                },
+                "responses": {
+                    "Error": {
+                        "description": "error",
+                        "content": { "application/json": { "schema": { "type": "object" } } }
+                    }
+                }
+            }
+        }))
+        .unwrap()
+    }
+
+    fn exchange(
+        method: Method,
+        uri: &'static str,
+        status: StatusCode,
+        content_type: Option<&'static str>,
+        body: &'static [u8],
+    ) -> Exchange {
+        let mut response_headers = HeaderMap::new();
+        if let Some(content_type) = content_type {
+            response_headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+        }
+
+        Exchange {
+            method,
+            uri: Uri::from_static(uri),
+            version: Version::HTTP_11,
+            request_headers: HeaderMap::new(),
+            request_body: Bytes::new(),
+            status,
+            response_headers,
+            response_body: Some(Bytes::from_static(body)),
+        }
+    }
+
+    #[test]
+    fn valid_exchanges() {
+        let spec = spec("3.0.3");
+        let cases = [
+            exchange(
+                Method::GET,
+                "http://127.0.0.1:1/api/v1/repositories/1",
+                StatusCode::OK,
+                Some("application/json; charset=utf-8"),
+                br#"{"id":1,"name":"a","description":null}"#,
+            ),
+            exchange(
+                Method::GET,
+                "http://127.0.0.1:1/api/v1/repositories/1",
+                StatusCode::NOT_FOUND,
+                Some("application/json"),
+                br#"{"success":false}"#,
+            ),
+            exchange(
+                Method::GET,
+                "http://127.0.0.1:1/api/v1/repositories/latest",
+                StatusCode::NO_CONTENT,
+                None,
+                b"",
+            ),
+        ];
+
+        for exchange in cases {
+            if let Err(report) = spec.validate(&exchange) {
+                panic!("{report}");
+            }
+        }
+    }
+
+    #[test]
+    fn violations() {
+        let spec = spec("3.1.0");
+
+        let report = spec
+            .validate(&exchange(
+                Method::GET,
+                "http://127.0.0.1:1/api/v1/repositories/1",
+                StatusCode::OK,
+                Some("application/json"),
+                br#"{"id":"1"}"#,
+            ))
+            .unwrap_err();
+
+        assert_eq!(report.operation(), Some("getRepository"));
+        assert_eq!(report.violations().len(), 2);
+        assert!(report.violations()[0].starts_with(r#"/id: "1" is not of type "integer""#));
+        assert!(report.violations()[1].starts_with(r#"/: "name" is a required property"#));
+
+        let report = spec
+            .validate(&exchange(
+                Method::GET,
+                "http://127.0.0.1:1/api/v1/repositories/1",
+                StatusCode::INTERNAL_SERVER_ERROR,
+                None,
+                b"",
+            ))
+            .unwrap_err();
+
+        assert_eq!(
+            report.violations(),
+            ["status code 500 Internal Server Error isn't documented (documented: 200, 4XX)"]
+        );
+
+        let report = spec
+            .validate(&exchange(
+                Method::GET,
+                "http://127.0.0.1:1/api/v1/repositories/1",
+                StatusCode::OK,
+                Some("text/plain"),
+                b"hi",
+            ))
+            .unwrap_err();
+
+        assert_eq!(
+            report.violations(),
+            ["content type `text/plain` isn't documented (documented: application/json)"]
+        );
+
+        let report = spec
+            .validate(&exchange(
+                Method::DELETE,
+                "http://127.0.0.1:1/api/v1/repositories/1",
+                StatusCode::OK,
+                None,
+                b"",
+            ))
+            .unwrap_err();
+
+        assert_eq!(
+            report.violations(),
+            ["`DELETE` isn't documented for `/repositories/{id}`"]
+        );
+        assert_eq!(
+            report.to_string(),
+            "response to `DELETE /api/v1/repositories/1` violates the OpenAPI document:\n    - `DELETE` isn't documented for `/repositories/{id}`"
+        );
+
+        let exchange = exchange(Method::GET, "http://127.0.0.1:1/users", StatusCode::OK, None, b"");
+        assert_eq!(
+            spec.validate(&exchange).unwrap_err().violations(),
+            ["no path in the document matches `/users`"]
+        );
+        assert!(spec.strict(false).validate(&exchange).is_ok());
+    }
+
+    #[test]
+    fn nullable_is_only_supported_in_openapi_30() {
+        let exchange = exchange(
+            Method::GET,
+            "http://127.0.0.1:1/api/v1/repositories/1",
+            StatusCode::OK,
+            Some("application/json"),
+            br#"{"id":1,"name":"a","description":null}"#,
+        );
+
+        assert!(spec("3.0.3").validate(&exchange).is_ok());
+        assert!(spec("3.1.0").validate(&exchange).is_err());
+    }
+
+    #[test]
+    fn parsing() {
+        let spec = "openapi: 3.1.0\ninfo:\n  title: charted\n  version: 0.1.0\npaths: {}\n".parse::<OpenApiSpec>();
+        assert!(spec.is_ok());
+
+        assert!(matches!(
+            "{\"swagger\": \"2.0\"}".parse::<OpenApiSpec>(),
+            Err(super::Error::UnsupportedVersion(None))
+        ));
+
+        assert!(matches!(
+            "{\"openapi\": \"2.0\"}".parse::<OpenApiSpec>(),
+            Err(super::Error::UnsupportedVersion(Some(version))) if version == "2.0"
+        ));
+    }
+
+    #[cfg(feature = "utoipa")]
+    #[test]
+    fn from_utoipa() {
+        use utoipa::openapi::{
+            path::{OperationBuilder, PathItemBuilder},
+            response::ResponseBuilder,
+            OpenApiBuilder, PathItemType, PathsBuilder,
+        };
+
+        let openapi = OpenApiBuilder::new()
+            .paths(
+                PathsBuilder::new().path(
+                    "/",
+                    PathItemBuilder::new()
+                        .operation(
+                            PathItemType::Get,
+                            OperationBuilder::new().response("204", ResponseBuilder::new().description("no content")),
+                        )
+                        .build(),
+                ),
+            )
+            .build();
+
+        let spec = OpenApiSpec::from_utoipa(&openapi).unwrap();
+        assert!(spec
+            .validate(&exchange(
+                Method::GET,
+                "http://127.0.0.1:1/",
+                StatusCode::NO_CONTENT,
+                None,
+                b""
+            ))
+            .is_ok());
+
+        assert!(spec
+            .validate(&exchange(Method::GET, "http://127.0.0.1:1/", StatusCode::OK, None, b""))
+            .is_err());
+    }
+}
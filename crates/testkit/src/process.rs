@@ -0,0 +1,671 @@
+// 📦🦋 charted TestKit: testing library for Axum services with testcontainers support
+// Copyright (c) 2024 Noelware, LLC. <team@noelware.org>
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Managed child processes, for behaviour that can only be tested through the real binary of a
+//! service, like its CLI flags, signal handling and configuration loading.
+//!
+//! A [`Process`] is spawned as a [`Child`] that:
+//!
+//! * waits until it is ready, once its port is open or its health check responds successfully;
+//! * forwards its standard output and error into the output of the test, prefixed with its name,
+//!   so that it is captured like the test's own output;
+//! * is killed once it is dropped, which is usually when the test ends.
+//!
+//! ## Example
+//! ```rust,no_run
+//! # use charted_testkit::process::{self, Process};
+//! # use axum::http::Method;
+//! #
+//! # #[tokio::main]
+//! # async fn main() {
+//! let port = process::free_port();
+//! let server = Process::cargo_bin("charted")
+//!     .args(["server", "--config", "tests/config.toml"])
+//!     .env("CHARTED_SERVER_PORT", port.to_string())
+//!     .listen_on(([127, 0, 0, 1], port))
+//!     .health_check("/heartbeat")
+//!     .spawn()
+//!     .await
+//!     .expect("failed to spawn `charted`");
+//!
+//! let ctx = server.context();
+//! let res = ctx
+//!     .request("/", Method::GET, None, charted_testkit::noop_request)
+//!     .await
+//!     .expect("was unable to send request");
+//!
+//! charted_testkit::assert_successful!(res);
+//! # }
+//! ```
+
+use crate::TestContext;
+use axum::http::Method;
+use std::{
+    collections::HashMap,
+    ffi::{OsStr, OsString},
+    fmt::Display,
+    io::{BufRead, BufReader, Read},
+    net::{Ipv4Addr, SocketAddr, TcpListener},
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus, Stdio},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// How long [`Process::spawn`] waits for a process to be ready by default.
+pub const DEFAULT_READY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Error type for spawning and waiting on a [`Process`].
+#[derive(Debug)]
+pub enum Error {
+    /// The process couldn't be spawned or the binary couldn't be built.
+    Io(std::io::Error),
+
+    /// `cargo build` failed to build the binary.
+    Build { bin: String, stderr: String },
+
+    /// The process exited before it was ready.
+    Exited { status: ExitStatus, output: String },
+
+    /// The process wasn't ready, or didn't exit, in time.
+    Timeout { after: Duration, output: String },
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "failed to spawn process: {err}"),
+            Error::Build { bin, stderr } => write!(f, "failed to build binary `{bin}`:\n{stderr}"),
+            Error::Exited { status, output } => write!(f, "process exited with {status}:\n{output}"),
+            Error::Timeout { after, output } => write!(f, "process timed out after {after:?}:\n{output}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+/// Returns a port on `127.0.0.1` that is free right now, which can be passed to a [`Process`]
+/// to listen on.
+pub fn free_port() -> u16 {
+    TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+        .and_then(|listener| listener.local_addr())
+        .map(|addr| addr.port())
+        .expect("failed to find a free port")
+}
+
+#[derive(Debug, Clone)]
+enum Program {
+    Path(PathBuf),
+    Cargo { bin: String, package: Option<String> },
+}
+
+/// Builder for a [`Child`] process.
+#[derive(Debug, Clone)]
+pub struct Process {
+    program: Program,
+    args: Vec<OsString>,
+    envs: HashMap<OsString, OsString>,
+    current_dir: Option<PathBuf>,
+    addr: Option<SocketAddr>,
+    health_check: Option<String>,
+    ready_timeout: Duration,
+}
+
+impl Process {
+    /// Runs the program at `path`.
+    pub fn new<P: Into<PathBuf>>(path: P) -> Process {
+        Process::from_program(Program::Path(path.into()))
+    }
+
+    /// Runs the `bin` binary target of the workspace, which is built with `cargo build` in the
+    /// same profile as the test when the process is spawned, so that it is never stale.
+    pub fn cargo_bin<B: Into<String>>(bin: B) -> Process {
+        Process::from_program(Program::Cargo {
+            bin: bin.into(),
+            package: None,
+        })
+    }
+
+    fn from_program(program: Program) -> Process {
+        Process {
+            program,
+            args: Vec::new(),
+            envs: HashMap::new(),
+            current_dir: None,
+            addr: None,
+            health_check: None,
+            ready_timeout: DEFAULT_READY_TIMEOUT,
+        }
+    }
+
+    /// Sets the package that contains the binary target of [`Process::cargo_bin`], which is
+    /// only required if the name of the binary is ambiguous in the workspace.
+    pub fn package<P: Into<String>>(mut self, package: P) -> Self {
+        if let Program::Cargo {
+            package: ref mut pkg, ..
+        } = self.program
+        {
+            *pkg = Some(package.into());
+        }
+
+        self
+    }
+
+    /// Adds an argument that is passed to the process.
+    pub fn arg<A: AsRef<OsStr>>(mut self, arg: A) -> Self {
+        self.args.push(arg.as_ref().to_owned());
+        self
+    }
+
+    /// Adds multiple arguments that are passed to the process.
+    pub fn args<I: IntoIterator<Item = A>, A: AsRef<OsStr>>(mut self, args: I) -> Self {
+        self.args.extend(args.into_iter().map(|arg| arg.as_ref().to_owned()));
+        self
+    }
+
+    /// Sets an environment variable of the process, which otherwise inherits the environment of
+    /// the test.
+    pub fn env<K: AsRef<OsStr>, V: AsRef<OsStr>>(mut self, key: K, value: V) -> Self {
+        self.envs.insert(key.as_ref().to_owned(), value.as_ref().to_owned());
+        self
+    }
+
+    /// Sets the working directory of the process.
+    pub fn current_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.current_dir = Some(dir.into());
+        self
+    }
+
+    /// Sets the address that the process listens on. The process is ready once a TCP connection
+    /// can be made to it, unless a [health check][Process::health_check] is set.
+    pub fn listen_on<A: Into<SocketAddr>>(mut self, addr: A) -> Self {
+        self.addr = Some(addr.into());
+        self
+    }
+
+    /// Sets a path (i.e, `/heartbeat`) that must respond with a successful status code before the
+    /// process is ready. Requires [`Process::listen_on`].
+    pub fn health_check<P: Into<String>>(mut self, path: P) -> Self {
+        self.health_check = Some(path.into());
+        self
+    }
+
+    /// Sets how long [`Process::spawn`] waits for the process to be ready.
+    pub fn ready_timeout(mut self, timeout: Duration) -> Self {
+        self.ready_timeout = timeout;
+        self
+    }
+
+    /// Spawns the process, building it first if it is a [cargo binary][Process::cargo_bin], and
+    /// waits until it is ready if it [listens on an address][Process::listen_on].
+    pub async fn spawn(self) -> Result<Child, Error> {
+        let path = match self.program {
+            Program::Path(ref path) => path.clone(),
+            Program::Cargo { ref bin, ref package } => build(bin, package.as_deref()).await?,
+        };
+
+        let name = path
+            .file_stem()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.display().to_string());
+
+        let mut command = Command::new(&path);
+        command
+            .args(&self.args)
+            .envs(&self.envs)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        if let Some(ref dir) = self.current_dir {
+            command.current_dir(dir);
+        }
+
+        let mut process = command.spawn()?;
+        let output = Output::default();
+
+        let stdout = process.stdout.take().expect("stdout to be piped");
+        output.forward(format!("[{name}] "), stdout, false);
+
+        let stderr = process.stderr.take().expect("stderr to be piped");
+        output.forward(format!("[{name}] "), stderr, true);
+
+        let mut child = Child {
+            process,
+            addr: self.addr,
+            output,
+        };
+
+        if let Some(addr) = self.addr {
+            child
+                .wait_until_ready(addr, self.health_check.as_deref(), self.ready_timeout)
+                .await?;
+        }
+
+        Ok(child)
+    }
+}
+
+/// A process that was spawned with [`Process::spawn`], which is killed once it is dropped.
+#[derive(Debug)]
+pub struct Child {
+    process: std::process::Child,
+    addr: Option<SocketAddr>,
+    output: Output,
+}
+
+impl Child {
+    /// Returns the OS-assigned identifier of the process.
+    pub fn id(&self) -> u32 {
+        self.process.id()
+    }
+
+    /// Returns the address that the process listens on, if it was set with [`Process::listen_on`].
+    pub fn addr(&self) -> Option<SocketAddr> {
+        self.addr
+    }
+
+    /// Returns a [`TestContext`] that sends requests to the process.
+    ///
+    /// ## Panics
+    /// This will panic if the process doesn't [listen on an address][Process::listen_on].
+    pub fn context(&self) -> TestContext {
+        let addr = self.addr.expect("process doesn't listen on an address");
+        TestContext::from_base_url(format!("http://{addr}"))
+    }
+
+    /// Returns everything that the process wrote to its standard output so far.
+    pub fn stdout(&self) -> String {
+        self.output.stdout.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Returns everything that the process wrote to its standard error so far.
+    pub fn stderr(&self) -> String {
+        self.output.stderr.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Sends a signal (i.e, `libc::SIGTERM`) to the process.
+    #[cfg(unix)]
+    pub fn signal(&self, signal: i32) -> std::io::Result<()> {
+        // Safety: `kill` has no memory safety requirements, and the process wasn't reaped yet
+        //         since we own it, so its pid can't have been reused.
+        match unsafe { libc::kill(self.process.id() as libc::pid_t, signal) } {
+            0 => Ok(()),
+            _ => Err(std::io::Error::last_os_error()),
+        }
+    }
+
+    /// Kills the process and waits for it to exit.
+    pub fn kill(&mut self) -> std::io::Result<ExitStatus> {
+        self.process.kill()?;
+        self.process.wait()
+    }
+
+    /// Waits for the process to exit by itself for up to `timeout`.
+    pub async fn wait(&mut self, timeout: Duration) -> Result<ExitStatus, Error> {
+        let started = Instant::now();
+        loop {
+            if let Some(status) = self.process.try_wait()? {
+                // give the forwarding threads a chance to read the rest of the output
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                return Ok(status);
+            }
+
+            if started.elapsed() >= timeout {
+                return Err(Error::Timeout {
+                    after: timeout,
+                    output: self.output.to_string(),
+                });
+            }
+
+            tokio::time::sleep(Duration::from_millis(25)).await;
+        }
+    }
+
+    async fn wait_until_ready(
+        &mut self,
+        addr: SocketAddr,
+        health_check: Option<&str>,
+        timeout: Duration,
+    ) -> Result<(), Error> {
+        let ctx = health_check.map(|_| self.context());
+        let started = Instant::now();
+
+        loop {
+            if let Some(status) = self.process.try_wait()? {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                return Err(Error::Exited {
+                    status,
+                    output: self.output.to_string(),
+                });
+            }
+
+            // a process that accepts connections but never answers would otherwise hang forever
+            let remaining = timeout.saturating_sub(started.elapsed());
+            let ready = match (health_check, &ctx) {
+                (Some(path), Some(ctx)) => {
+                    let request = ctx.request(path.to_owned(), Method::GET, None, crate::noop_request);
+                    tokio::time::timeout(remaining, request)
+                        .await
+                        .is_ok_and(|res| res.is_ok_and(|res| res.status().is_success()))
+                }
+
+                _ => tokio::time::timeout(remaining, tokio::net::TcpStream::connect(addr))
+                    .await
+                    .is_ok_and(|stream| stream.is_ok()),
+            };
+
+            if ready {
+                return Ok(());
+            }
+
+            if started.elapsed() >= timeout {
+                return Err(Error::Timeout {
+                    after: timeout,
+                    output: self.output.to_string(),
+                });
+            }
+
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+}
+
+impl Drop for Child {
+    fn drop(&mut self) {
+        if let Ok(None) = self.process.try_wait() {
+            let _ = self.kill();
+        }
+    }
+}
+
+/// Everything that a [`Child`] wrote to its standard output and error.
+#[derive(Debug, Default, Clone)]
+struct Output {
+    stdout: Arc<Mutex<String>>,
+    stderr: Arc<Mutex<String>>,
+}
+
+impl Output {
+    /// Reads `reader` line by line on a separate thread, recording every line and printing it with
+    /// `prefix`. Threads inherit the output capturing of libtest, so the lines are only shown if the
+    /// test fails (or `--nocapture` is passed).
+    fn forward<R: Read + Send + 'static>(&self, prefix: String, reader: R, stderr: bool) {
+        let buf = match stderr {
+            true => self.stderr.clone(),
+            false => self.stdout.clone(),
+        };
+
+        std::thread::spawn(move || {
+            let mut reader = BufReader::new(reader);
+            let mut line = Vec::new();
+
+            while let Ok(n) = reader.read_until(b'\n', &mut line) {
+                if n == 0 {
+                    break;
+                }
+
+                let text = String::from_utf8_lossy(&line);
+                match stderr {
+                    true => eprint!("{prefix}{text}"),
+                    false => print!("{prefix}{text}"),
+                }
+
+                buf.lock().unwrap_or_else(|e| e.into_inner()).push_str(&text);
+                line.clear();
+            }
+        });
+    }
+}
+
+impl Display for Output {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let stdout = self.stdout.lock().unwrap_or_else(|e| e.into_inner());
+        let stderr = self.stderr.lock().unwrap_or_else(|e| e.into_inner());
+
+        write!(f, "--- stdout ---\n{stdout}\n--- stderr ---\n{stderr}")
+    }
+}
+
+/// Builds the `bin` binary target with `cargo build` and returns its path.
+async fn build(bin: &str, package: Option<&str>) -> Result<PathBuf, Error> {
+    let exe = std::env::current_exe()?;
+    let (profile_dir, profile) = profile_of(&exe);
+
+    let mut command = Command::new(std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into()));
+    command.args(["build", "--bin", bin]);
+
+    if let Some(package) = package {
+        command.args(["--package", package]);
+    }
+
+    match profile.as_str() {
+        "debug" => {}
+        "release" => {
+            command.arg("--release");
+        }
+
+        profile => {
+            command.args(["--profile", profile]);
+        }
+    }
+
+    if let Some(target_dir) = profile_dir.parent() {
+        command.arg("--target-dir").arg(target_dir);
+    }
+
+    // cargo can take a while, so don't block the runtime's worker thread while it builds
+    command.stdin(Stdio::null());
+    let output = tokio::process::Command::from(command).output().await?;
+    if !output.status.success() {
+        return Err(Error::Build {
+            bin: bin.to_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        });
+    }
+
+    Ok(profile_dir.join(format!("{bin}{}", std::env::consts::EXE_SUFFIX)))
+}
+
+/// Returns the directory that the binaries of the test's profile are in, and the name of that
+/// directory. Tests live in `{target}/{profile}/deps`, while binaries live in `{target}/{profile}`.
+fn profile_of(exe: &Path) -> (PathBuf, String) {
+    let mut dir = exe.parent().unwrap_or(exe).to_path_buf();
+    if dir.ends_with("deps") {
+        dir.pop();
+    }
+
+    let profile = dir
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "debug".into());
+
+    (dir, profile)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{free_port, profile_of, Error, Process};
+    use crate::{assert_successful, consume_body};
+    use axum::{body::Bytes, http::Method, routing, Router};
+    use std::{path::Path, time::Duration};
+
+    /// Not a test by itself: the tests below run this test executable as a child process, which
+    /// serves a router on `TESTKIT_CHILD_PORT` when it is set, or only accepts connections if
+    /// `TESTKIT_CHILD_UNRESPONSIVE` is set as well.
+    #[tokio::test]
+    #[ignore = "only runs as a child process of `tests::spawn`"]
+    async fn child_service() {
+        let Ok(port) = std::env::var("TESTKIT_CHILD_PORT") else {
+            return;
+        };
+
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", port.parse::<u16>().unwrap()))
+            .await
+            .unwrap();
+
+        eprintln!("listening on port {port}");
+        if std::env::var_os("TESTKIT_CHILD_UNRESPONSIVE").is_some() {
+            let mut connections = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                connections.push(stream);
+            }
+
+            return;
+        }
+
+        axum::serve(
+            listener,
+            Router::new().route("/health", routing::get(|| async { "ok" })),
+        )
+        .await
+        .unwrap();
+    }
+
+    fn child() -> Process {
+        Process::new(std::env::current_exe().unwrap()).args([
+            "process::tests::child_service",
+            "--exact",
+            "--ignored",
+            "--nocapture",
+        ])
+    }
+
+    #[tokio::test]
+    #[cfg_attr(
+        windows,
+        ignore = "fails on Windows: hyper_util::client::legacy::Error(Connect, ConnectError(\"tcp connect error\", Os { code: 10049, kind: AddrNotAvailable, message: \"The requested address is not valid in its context.\" })))"
+    )]
+    async fn spawn() {
+        let port = free_port();
+        let mut child = child()
+            .env("TESTKIT_CHILD_PORT", port.to_string())
+            .listen_on(([127, 0, 0, 1], port))
+            .health_check("/health")
+            .spawn()
+            .await
+            .expect("failed to spawn child process");
+
+        let ctx = child.context();
+        let res = ctx
+            .request("/health", Method::GET, None, crate::noop_request)
+            .await
+            .expect("unable to send request");
+
+        assert_successful!(res);
+        assert_eq!(consume_body!(res), Bytes::from_static(b"ok"));
+        assert!(child.stderr().contains(&format!("listening on port {port}")));
+
+        assert!(!child.kill().unwrap().success());
+    }
+
+    #[tokio::test]
+    #[cfg_attr(
+        windows,
+        ignore = "fails on Windows: hyper_util::client::legacy::Error(Connect, ConnectError(\"tcp connect error\", Os { code: 10049, kind: AddrNotAvailable, message: \"The requested address is not valid in its context.\" })))"
+    )]
+    async fn unresponsive_health_check() {
+        let port = free_port();
+        let err = child()
+            .env("TESTKIT_CHILD_PORT", port.to_string())
+            .env("TESTKIT_CHILD_UNRESPONSIVE", "1")
+            .listen_on(([127, 0, 0, 1], port))
+            .health_check("/health")
+            .ready_timeout(Duration::from_secs(2))
+            .spawn()
+            .await
+            .expect_err("child process should never respond to the health check");
+
+        let Error::Timeout { after, output } = err else {
+            panic!("expected `Error::Timeout`, got {err:?}");
+        };
+
+        assert_eq!(after, Duration::from_secs(2));
+        assert!(output.contains(&format!("listening on port {port}")), "{output}");
+    }
+
+    #[tokio::test]
+    async fn exits_before_ready() {
+        let err = child()
+            .listen_on(([127, 0, 0, 1], free_port()))
+            .ready_timeout(Duration::from_secs(10))
+            .spawn()
+            .await
+            .expect_err("child process should exit without `TESTKIT_CHILD_PORT`");
+
+        let Error::Exited { status, output } = err else {
+            panic!("expected `Error::Exited`, got {err:?}");
+        };
+
+        assert!(status.success());
+        assert!(output.contains("1 passed"), "{output}");
+    }
+
+    #[tokio::test]
+    async fn wait_for_exit() {
+        let mut child = child().spawn().await.expect("failed to spawn child process");
+        assert!(child.wait(Duration::from_secs(10)).await.unwrap().success());
+        assert!(child.stdout().contains("running 1 test"));
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn signals() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let port = free_port();
+        let mut child = child()
+            .env("TESTKIT_CHILD_PORT", port.to_string())
+            .listen_on(([127, 0, 0, 1], port))
+            .spawn()
+            .await
+            .expect("failed to spawn child process");
+
+        child.signal(libc::SIGTERM).unwrap();
+
+        let status = child.wait(Duration::from_secs(10)).await.unwrap();
+        assert_eq!(status.signal(), Some(libc::SIGTERM));
+    }
+
+    #[test]
+    fn profiles() {
+        let (dir, profile) = profile_of(Path::new("/work/target/debug/deps/charted_testkit-0123456789abcdef"));
+        assert_eq!(dir, Path::new("/work/target/debug"));
+        assert_eq!(profile, "debug");
+
+        let (dir, profile) = profile_of(Path::new("/work/target/x86_64-unknown-linux-gnu/release/deps/it-0123"));
+        assert_eq!(dir, Path::new("/work/target/x86_64-unknown-linux-gnu/release"));
+        assert_eq!(profile, "release");
+    }
+}
@@ -0,0 +1,445 @@
+// 📦🦋 charted TestKit: testing library for Axum services with testcontainers support
+// Copyright (c) 2024 Noelware, LLC. <team@noelware.org>
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! A TCP proxy that impairs the network between a client and a server, to test how clients
+//! handle slow and unreliable networks.
+//!
+//! Unlike [faults][crate::faults], which are injected into the responses of the ephemeral
+//! server, a [`TcpProxy`] works on raw TCP connections, so it can sit in front of the ephemeral
+//! server or any other address, like the port of a container. Impairments can be changed while
+//! connections are open, and apply to all data that is proxied after that.
+//!
+//! ## Example
+//! ```rust,no_run
+//! # use charted_testkit::{TestContext, proxy::TcpProxy};
+//! # use std::time::Duration;
+//! #
+//! # #[tokio::main]
+//! # async fn main() {
+//! let mut server = TestContext::default();
+//! server.serve(axum::Router::new()).await;
+//!
+//! let proxy = TcpProxy::start(*server.server_addr().unwrap())
+//!     .await
+//!     .expect("failed to start proxy");
+//!
+//! proxy.set_latency(Duration::from_millis(100), Duration::from_millis(20));
+//! proxy.set_bandwidth(Some(64 * 1024));
+//!
+//! let ctx = TestContext::from_base_url(proxy.url());
+//! // ...
+//!
+//! proxy.cut();
+//! # }
+//! ```
+
+use crate::util::Rng;
+use std::{
+    io,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpListener, TcpStream, ToSocketAddrs,
+    },
+    task::{AbortHandle, JoinHandle},
+};
+
+/// Size of the buffer that data is proxied with.
+const BUFFER_SIZE: usize = 16 * 1024;
+
+/// Impairments that a [`TcpProxy`] applies to every chunk of data, in both directions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Impairments {
+    /// Delay that is added before every chunk of data is forwarded.
+    pub latency: Duration,
+
+    /// Random variation of the latency; every chunk is delayed by `latency ± jitter`.
+    pub jitter: Duration,
+
+    /// Maximum throughput of every connection in each direction, in bytes per second.
+    pub bandwidth: Option<u64>,
+}
+
+struct State {
+    impairments: Mutex<Impairments>,
+    enabled: AtomicBool,
+    connections: Mutex<Vec<[AbortHandle; 2]>>,
+    rng: Mutex<Rng>,
+}
+
+impl State {
+    fn impairments(&self) -> Impairments {
+        *lock(&self.impairments)
+    }
+
+    /// Returns how long to wait before forwarding `len` bytes.
+    fn delay_for(&self, len: usize, impairments: &Impairments) -> Duration {
+        let mut delay = impairments.latency;
+        if !impairments.jitter.is_zero() {
+            // uniformly distributed in `[-jitter, jitter)`
+            let offset = lock(&self.rng).next_f64() * 2.0 - 1.0;
+            let jitter = impairments.jitter.mul_f64(offset.abs());
+
+            delay = match offset < 0.0 {
+                true => delay.saturating_sub(jitter),
+                false => delay + jitter,
+            };
+        }
+
+        if let Some(rate) = impairments.bandwidth.filter(|rate| *rate > 0) {
+            delay += Duration::from_secs_f64(len as f64 / rate as f64);
+        }
+
+        delay
+    }
+
+    fn cut(&self) {
+        for handles in lock(&self.connections).drain(..) {
+            for handle in handles {
+                handle.abort();
+            }
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// A TCP proxy in front of an upstream address, which is stopped once it is dropped.
+pub struct TcpProxy {
+    addr: SocketAddr,
+    upstream: SocketAddr,
+    state: Arc<State>,
+    accept: JoinHandle<()>,
+}
+
+impl std::fmt::Debug for TcpProxy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TcpProxy")
+            .field("addr", &self.addr)
+            .field("upstream", &self.upstream)
+            .field("impairments", &self.impairments())
+            .field("enabled", &self.is_enabled())
+            .finish()
+    }
+}
+
+impl TcpProxy {
+    /// Starts a proxy on an ephemeral port of `127.0.0.1` that forwards connections to `upstream`,
+    /// which is resolved once.
+    pub async fn start<A: ToSocketAddrs>(upstream: A) -> io::Result<TcpProxy> {
+        let upstream = tokio::net::lookup_host(upstream)
+            .await?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "upstream didn't resolve to any address"))?;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(State {
+            impairments: Mutex::default(),
+            enabled: AtomicBool::new(true),
+            connections: Mutex::default(),
+            rng: Mutex::new(Rng::from_time()),
+        });
+
+        let accept = tokio::spawn(accept(listener, upstream, state.clone()));
+        Ok(TcpProxy {
+            addr,
+            upstream,
+            state,
+            accept,
+        })
+    }
+
+    /// Returns the address that the proxy listens on.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Returns the address that connections are forwarded to.
+    pub fn upstream(&self) -> SocketAddr {
+        self.upstream
+    }
+
+    /// Returns the URL of the proxy (i.e, `http://127.0.0.1:34567`), which can be passed to
+    /// [`TestContext::from_base_url`][crate::TestContext::from_base_url].
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Returns the current impairments.
+    pub fn impairments(&self) -> Impairments {
+        self.state.impairments()
+    }
+
+    /// Replaces all impairments.
+    pub fn set_impairments(&self, impairments: Impairments) {
+        *lock(&self.state.impairments) = impairments;
+    }
+
+    /// Delays every chunk of data by `latency ± jitter`. Passing [`Duration::ZERO`] for both
+    /// removes the latency.
+    pub fn set_latency(&self, latency: Duration, jitter: Duration) {
+        let mut impairments = lock(&self.state.impairments);
+        impairments.latency = latency;
+        impairments.jitter = jitter;
+    }
+
+    /// Limits the throughput of every connection in each direction to `bytes_per_second`, or
+    /// removes the limit if it is `None`.
+    pub fn set_bandwidth(&self, bytes_per_second: Option<u64>) {
+        lock(&self.state.impairments).bandwidth = bytes_per_second;
+    }
+
+    /// Removes all impairments and enables the proxy again.
+    pub fn reset(&self) {
+        self.set_impairments(Impairments::default());
+        self.set_enabled(true);
+    }
+
+    /// Returns whenever if the proxy accepts connections.
+    pub fn is_enabled(&self) -> bool {
+        self.state.enabled.load(Ordering::SeqCst)
+    }
+
+    /// Enables or disables the proxy. A disabled proxy [cuts][TcpProxy::cut] all open connections
+    /// and closes new connections right after they were accepted, like a service that is down.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.state.enabled.store(enabled, Ordering::SeqCst);
+        if !enabled {
+            self.cut();
+        }
+    }
+
+    /// Closes every open connection, in both directions.
+    pub fn cut(&self) {
+        self.state.cut();
+    }
+
+    /// Returns how many connections are open.
+    pub fn connections(&self) -> usize {
+        let mut connections = lock(&self.state.connections);
+        connections.retain(|handles| handles.iter().any(|handle| !handle.is_finished()));
+
+        connections.len()
+    }
+}
+
+impl Drop for TcpProxy {
+    fn drop(&mut self) {
+        self.accept.abort();
+        self.state.cut();
+    }
+}
+
+async fn accept(listener: TcpListener, upstream: SocketAddr, state: Arc<State>) {
+    loop {
+        let Ok((client, _)) = listener.accept().await else {
+            continue;
+        };
+
+        if !state.enabled.load(Ordering::SeqCst) {
+            drop(client);
+            continue;
+        }
+
+        let state = state.clone();
+        tokio::spawn(async move {
+            let Ok(server) = TcpStream::connect(upstream).await else {
+                return;
+            };
+
+            let _ = client.set_nodelay(true);
+            let _ = server.set_nodelay(true);
+
+            let (client_read, client_write) = client.into_split();
+            let (server_read, server_write) = server.into_split();
+
+            let mut connections = lock(&state.connections);
+
+            // the proxy could have been disabled while connecting to the upstream
+            if !state.enabled.load(Ordering::SeqCst) {
+                return;
+            }
+
+            connections.push([
+                tokio::spawn(pipe(client_read, server_write, state.clone())).abort_handle(),
+                tokio::spawn(pipe(server_read, client_write, state.clone())).abort_handle(),
+            ]);
+        });
+    }
+}
+
+/// Forwards data from `from` to `to` until `from` is closed, then closes `to` for writing.
+async fn pipe(mut from: OwnedReadHalf, mut to: OwnedWriteHalf, state: Arc<State>) {
+    let mut buf = vec![0; BUFFER_SIZE];
+    loop {
+        // smaller chunks make a limited bandwidth smoother
+        let max = state
+            .impairments()
+            .bandwidth
+            .map_or(BUFFER_SIZE, |rate| (rate as usize / 10).clamp(1, BUFFER_SIZE));
+
+        let len = match from.read(&mut buf[..max]).await {
+            Ok(0) | Err(_) => break,
+            Ok(len) => len,
+        };
+
+        // impairments could've changed while waiting for data
+        let impairments = state.impairments();
+        let delay = state.delay_for(len, &impairments);
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+
+        if to.write_all(&buf[..len]).await.is_err() {
+            break;
+        }
+    }
+
+    let _ = to.shutdown().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Impairments, State, TcpProxy};
+    use crate::{assert_successful, consume_body, faults::Fault, util::Rng, TestContext};
+    use axum::{body::Bytes, http::Method, routing, Router};
+    use std::{
+        sync::{atomic::AtomicBool, Mutex},
+        time::{Duration, Instant},
+    };
+
+    #[test]
+    fn delays() {
+        let state = State {
+            impairments: Mutex::default(),
+            enabled: AtomicBool::new(true),
+            connections: Mutex::default(),
+            rng: Mutex::new(Rng::new(42)),
+        };
+
+        let impairments = Impairments {
+            latency: Duration::from_millis(100),
+            jitter: Duration::from_millis(20),
+            bandwidth: Some(1000),
+        };
+
+        for _ in 0..100 {
+            let delay = state.delay_for(500, &impairments);
+            assert!(
+                (Duration::from_millis(580)..=Duration::from_millis(620)).contains(&delay),
+                "{delay:?}"
+            );
+        }
+
+        assert_eq!(state.delay_for(500, &Impairments::default()), Duration::ZERO);
+    }
+
+    async fn server() -> TestContext {
+        let mut ctx = TestContext::default();
+        ctx.serve(
+            Router::new()
+                .route("/", routing::get(|| async { "Hello, world!" }))
+                .route("/large", routing::get(|| async { "a".repeat(10_000) })),
+        )
+        .await;
+
+        ctx
+    }
+
+    #[tokio::test]
+    #[cfg_attr(
+        windows,
+        ignore = "fails on Windows: hyper_util::client::legacy::Error(Connect, ConnectError(\"tcp connect error\", Os { code: 10049, kind: AddrNotAvailable, message: \"The requested address is not valid in its context.\" })))"
+    )]
+    async fn impairments() {
+        let server = server().await;
+        let proxy = TcpProxy::start(*server.server_addr().unwrap()).await.unwrap();
+        let ctx = TestContext::from_base_url(proxy.url());
+
+        let res = ctx.request("/", Method::GET, None, crate::noop_request).await.unwrap();
+        assert_successful!(res);
+        assert_eq!(consume_body!(res), Bytes::from_static(b"Hello, world!"));
+        assert_eq!(proxy.connections(), 1);
+
+        proxy.set_latency(Duration::from_millis(100), Duration::ZERO);
+        let started = Instant::now();
+        ctx.request("/", Method::GET, None, crate::noop_request).await.unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(200));
+
+        proxy.reset();
+        proxy.set_bandwidth(Some(20_000));
+
+        let started = Instant::now();
+        let res = ctx
+            .request("/large", Method::GET, None, crate::noop_request)
+            .await
+            .unwrap();
+        assert_eq!(consume_body!(res).len(), 10_000);
+        assert!(started.elapsed() >= Duration::from_millis(400));
+    }
+
+    #[tokio::test]
+    #[cfg_attr(
+        windows,
+        ignore = "fails on Windows: hyper_util::client::legacy::Error(Connect, ConnectError(\"tcp connect error\", Os { code: 10049, kind: AddrNotAvailable, message: \"The requested address is not valid in its context.\" })))"
+    )]
+    async fn cuts_and_disabling() {
+        let server = server().await;
+        server
+            .faults()
+            .add(Fault::slow_write(Duration::from_millis(10)).on("/large"));
+
+        let proxy = TcpProxy::start(*server.server_addr().unwrap()).await.unwrap();
+        let ctx = TestContext::from_base_url(proxy.url());
+        ctx.set_buffer_responses(true);
+
+        let request = tokio::spawn(ctx.request("/large", Method::GET, None, crate::noop_request));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        proxy.cut();
+        request
+            .await
+            .unwrap()
+            .expect_err("request should fail once its connection was cut");
+
+        assert_eq!(proxy.connections(), 0);
+
+        proxy.set_enabled(false);
+        ctx.request("/", Method::GET, None, crate::noop_request)
+            .await
+            .expect_err("disabled proxy shouldn't accept connections");
+
+        proxy.set_enabled(true);
+        let res = ctx.request("/", Method::GET, None, crate::noop_request).await.unwrap();
+        assert_successful!(res);
+    }
+}
@@ -0,0 +1,279 @@
+// 📦🦋 charted TestKit: testing library for Axum services with testcontainers support
+// Copyright (c) 2024 Noelware, LLC. <team@noelware.org>
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Opt-in redirect following for requests sent with a [`TestContext`][crate::TestContext].
+//!
+//! When a [`RedirectPolicy`] is set with [`TestContext::set_redirect_policy`][crate::TestContext::set_redirect_policy],
+//! `301`, `302`, `303`, `307` and `308` responses with a `Location` header are followed and the final response
+//! will have a [`RedirectChain`] extension with every intermediate response.
+//!
+//! Methods are rewritten as described in [RFC 9110 § 15.4]: `303` responses are followed with `GET` (unless
+//! the request was a `HEAD` request), `301` and `302` responses to `POST` requests are followed with `GET`, and
+//! `307` and `308` responses keep the method and body. The `Authorization` and `Cookie` headers are never
+//! forwarded to a different origin.
+//!
+//! [RFC 9110 § 15.4]: https://www.rfc-editor.org/rfc/rfc9110#section-15.4
+
+use axum::http::{HeaderMap, Method, Response, StatusCode, Uri};
+
+/// Policy that decides how redirects are followed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedirectPolicy {
+    max_hops: usize,
+    same_origin_only: bool,
+}
+
+impl Default for RedirectPolicy {
+    fn default() -> Self {
+        RedirectPolicy::limited(10)
+    }
+}
+
+impl RedirectPolicy {
+    /// Creates a policy that follows up to `max_hops` redirects before the request fails
+    /// with [`Error::TooManyRedirects`][crate::Error::TooManyRedirects].
+    pub fn limited(max_hops: usize) -> Self {
+        RedirectPolicy {
+            max_hops,
+            same_origin_only: false,
+        }
+    }
+
+    /// Fails the request with [`Error::CrossOriginRedirect`][crate::Error::CrossOriginRedirect]
+    /// instead of following a redirect to a different origin.
+    pub fn same_origin_only(mut self, yes: bool) -> Self {
+        self.same_origin_only = yes;
+        self
+    }
+
+    /// Returns the maximum amount of redirects that will be followed.
+    pub fn max_hops(&self) -> usize {
+        self.max_hops
+    }
+
+    /// Checks whenever if redirects to a different origin are rejected.
+    pub fn is_same_origin_only(&self) -> bool {
+        self.same_origin_only
+    }
+}
+
+/// A redirect response that was followed.
+#[derive(Debug, Clone)]
+pub struct Hop {
+    pub(crate) method: Method,
+    pub(crate) uri: Uri,
+    pub(crate) status: StatusCode,
+    pub(crate) headers: HeaderMap,
+}
+
+impl Hop {
+    /// Method of the request that resulted in this redirect.
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+
+    /// URI of the request that resulted in this redirect.
+    pub fn uri(&self) -> &Uri {
+        &self.uri
+    }
+
+    /// Status code of the redirect response.
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Headers of the redirect response.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Value of the redirect response's `Location` header.
+    pub fn location(&self) -> Option<&str> {
+        self.headers
+            .get(axum::http::header::LOCATION)
+            .and_then(|value| value.to_str().ok())
+    }
+}
+
+/// Every redirect that was followed to get to a response, in order. This is available as
+/// a response extension when a [`RedirectPolicy`] is set.
+///
+/// ## Example
+/// ```no_run
+/// # use charted_testkit::{TestContext, redirect::{RedirectChain, RedirectPolicy}};
+/// # use axum::http::{Method, StatusCode};
+/// #
+/// # #[tokio::main]
+/// # async fn main() {
+/// let ctx = TestContext::default();
+/// ctx.set_redirect_policy(RedirectPolicy::limited(5));
+///
+/// let res = ctx.request("/download", Method::GET, None, charted_testkit::noop_request).await.unwrap();
+/// let chain = RedirectChain::of(&res).unwrap();
+///
+/// assert_eq!(chain.len(), 1);
+/// assert_eq!(chain.hops()[0].status(), StatusCode::FOUND);
+/// assert_eq!(chain.hops()[0].location(), Some("/files/1"));
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RedirectChain(pub(crate) Vec<Hop>);
+
+impl RedirectChain {
+    /// Returns the redirect chain of a response, if redirects were followed.
+    pub fn of<B>(res: &Response<B>) -> Option<&RedirectChain> {
+        res.extensions().get()
+    }
+
+    /// Returns all the redirects that were followed.
+    pub fn hops(&self) -> &[Hop] {
+        &self.0
+    }
+
+    /// Returns how many redirects were followed.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Checks whenever if no redirects were followed.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Checks whenever if `status` is a redirect that can be followed.
+pub(crate) fn is_redirect(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::MOVED_PERMANENTLY
+            | StatusCode::FOUND
+            | StatusCode::SEE_OTHER
+            | StatusCode::TEMPORARY_REDIRECT
+            | StatusCode::PERMANENT_REDIRECT
+    )
+}
+
+/// Returns whenever if a redirect with `status` should be followed with a `GET` request
+/// without a body.
+pub(crate) fn rewrites_to_get(status: StatusCode, method: &Method) -> bool {
+    match status {
+        StatusCode::SEE_OTHER => method != Method::HEAD && method != Method::GET,
+        StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND => method == Method::POST,
+        _ => false,
+    }
+}
+
+/// Resolves a `Location` header value relative to the URI of the request that was redirected.
+pub(crate) fn resolve(base: &Uri, location: &str) -> Option<Uri> {
+    let scheme = base.scheme_str().unwrap_or("http");
+    let authority = base.authority()?.as_str();
+
+    // a relative location can contain `://` too (i.e, `/login?next=https://charts.dev/`)
+    let is_absolute = location.parse::<Uri>().is_ok_and(|uri| uri.scheme().is_some());
+    let resolved = if is_absolute {
+        location.to_owned()
+    } else if let Some(rest) = location.strip_prefix("//") {
+        format!("{scheme}://{rest}")
+    } else if location.starts_with('/') {
+        format!("{scheme}://{authority}{location}")
+    } else if location.starts_with('?') {
+        format!("{scheme}://{authority}{}{location}", base.path())
+    } else {
+        let path = base.path();
+        let dir = &path[..path.rfind('/').map(|idx| idx + 1).unwrap_or(0)];
+        format!(
+            "{scheme}://{authority}{}{location}",
+            if dir.is_empty() { "/" } else { dir }
+        )
+    };
+
+    resolved.parse().ok()
+}
+
+/// Checks whenever if both URIs have the same scheme, host and port.
+pub(crate) fn same_origin(a: &Uri, b: &Uri) -> bool {
+    fn port(uri: &Uri) -> Option<u16> {
+        uri.port_u16().or(match uri.scheme_str() {
+            Some("https") => Some(443),
+            _ => Some(80),
+        })
+    }
+
+    a.scheme_str()
+        .unwrap_or("http")
+        .eq_ignore_ascii_case(b.scheme_str().unwrap_or("http"))
+        && a.host().map(str::to_ascii_lowercase) == b.host().map(str::to_ascii_lowercase)
+        && port(a) == port(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{resolve, rewrites_to_get, same_origin};
+    use axum::http::{Method, StatusCode, Uri};
+
+    #[test]
+    fn resolve_locations() {
+        let base = Uri::from_static("http://127.0.0.1:3651/oauth/authorize?state=1");
+        let cases = [
+            ("/callback", "http://127.0.0.1:3651/callback"),
+            ("callback?code=2", "http://127.0.0.1:3651/oauth/callback?code=2"),
+            ("?page=2", "http://127.0.0.1:3651/oauth/authorize?page=2"),
+            ("//charts.dev/x", "http://charts.dev/x"),
+            ("https://charts.dev/", "https://charts.dev/"),
+            (
+                "/login?next=https://charts.dev/",
+                "http://127.0.0.1:3651/login?next=https://charts.dev/",
+            ),
+        ];
+
+        for (location, expected) in cases {
+            assert_eq!(resolve(&base, location).unwrap(), expected, "resolving {location}");
+        }
+    }
+
+    #[test]
+    fn method_rewriting() {
+        assert!(rewrites_to_get(StatusCode::SEE_OTHER, &Method::PUT));
+        assert!(!rewrites_to_get(StatusCode::SEE_OTHER, &Method::HEAD));
+        assert!(rewrites_to_get(StatusCode::FOUND, &Method::POST));
+        assert!(!rewrites_to_get(StatusCode::FOUND, &Method::DELETE));
+        assert!(!rewrites_to_get(StatusCode::TEMPORARY_REDIRECT, &Method::POST));
+        assert!(!rewrites_to_get(StatusCode::PERMANENT_REDIRECT, &Method::POST));
+    }
+
+    #[test]
+    fn origins() {
+        assert!(same_origin(
+            &Uri::from_static("http://charts.dev/a"),
+            &Uri::from_static("http://charts.dev:80/b")
+        ));
+
+        assert!(!same_origin(
+            &Uri::from_static("http://charts.dev/a"),
+            &Uri::from_static("https://charts.dev/a")
+        ));
+
+        assert!(!same_origin(
+            &Uri::from_static("http://127.0.0.1:1234/"),
+            &Uri::from_static("http://localhost:1234/")
+        ));
+    }
+}
@@ -0,0 +1,1134 @@
+// 📦🦋 charted TestKit: testing library for Axum services with testcontainers support
+// Copyright (c) 2024 Noelware, LLC. <team@noelware.org>
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Plain-text request scenarios that are run against a [`TestContext`].
+//!
+//! A scenario is a list of requests, each optionally followed by what its response should look
+//! like. Values can be captured from a response into variables, which can be used in later requests
+//! and asserts as `{{name}}`:
+//!
+//! ```text
+//! # create a repository and fetch it
+//! POST /repositories
+//! Content-Type: application/json
+//!
+//! {"name": "hello-world"}
+//!
+//! HTTP 201
+//! Content-Type: application/json
+//! [Captures]
+//! id: jsonpath "$.data.id"
+//! [Asserts]
+//! jsonpath "$.data.name" == "hello-world"
+//!
+//! GET /repositories/{{id}}
+//!
+//! HTTP 200
+//! [Asserts]
+//! jsonpath "$.data.id" == {{id}}
+//! header "x-request-id" isUuid
+//! body not contains "error"
+//! ```
+//!
+//! * A request starts with `METHOD /path` and is followed by its headers. Its body starts after
+//!   the first blank line.
+//! * `HTTP <status>` starts the expected response, where `*` accepts any status. Headers that are
+//!   listed after it must be equal to the response's headers.
+//! * `[Captures]` lines are `name: query`, and `[Asserts]` lines are `query [not] predicate`.
+//! * Queries are `status`, `header "name"`, `jsonpath "path"` (see the [`json`][crate::json] module
+//!   for the path syntax) and `body`.
+//! * Predicates are `== value`, `!= value`, `contains value`, `startsWith "text"`, `endsWith "text"`,
+//!   `matches "regex"` (or `matches /regex/`, which doesn't need backslashes to be escaped), `exists`
+//!   and `isUuid`, where values are JSON literals.
+//! * Lines that start with `#` outside of request bodies are comments. Comments after a request body
+//!   must be separated from it by a blank line.
+//!
+//! Every file in a directory can be run as its own test with [`include_scenarios!`][crate::include_scenarios].
+//!
+//! ## Example
+//! ```rust,no_run
+//! # use charted_testkit::{TestContext, scenario::Scenario};
+//! #
+//! # #[tokio::main]
+//! # async fn main() {
+//! let ctx = TestContext::default();
+//! let scenario = Scenario::from_file("tests/scenarios/repositories.http").unwrap();
+//!
+//! if let Err(err) = scenario.run(&ctx).await {
+//!     panic!("{err}");
+//! }
+//! # }
+//! ```
+
+use crate::{exchange::Exchange, TestContext};
+use axum::http::{HeaderName, HeaderValue, Method};
+use http_body_util::BodyExt;
+use regex::Regex;
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    fmt::{Display, Write},
+    path::Path,
+    str::FromStr,
+};
+
+/// Error type for parsing and running a [`Scenario`].
+#[derive(Debug)]
+pub enum Error {
+    /// The scenario file couldn't be read.
+    Io(std::io::Error),
+
+    /// The scenario has a syntax error.
+    Parse { line: usize, message: String },
+
+    /// A value of the scenario is invalid once its variables were substituted, or uses a variable
+    /// that isn't defined.
+    Invalid { line: usize, message: String },
+
+    /// The request of an entry couldn't be sent.
+    Request { line: usize, error: crate::Error },
+
+    /// The response of an entry didn't match what was expected.
+    Assertion {
+        line: usize,
+        message: String,
+        exchange: Option<Box<Exchange>>,
+    },
+}
+
+impl Error {
+    /// Line of the scenario that the error happened on, if it is known.
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            Error::Io(_) => None,
+            Error::Parse { line, .. }
+            | Error::Invalid { line, .. }
+            | Error::Request { line, .. }
+            | Error::Assertion { line, .. } => Some(*line),
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "failed to read scenario: {err}"),
+            Error::Parse { line, message } => write!(f, "line {line}: {message}"),
+            Error::Invalid { line, message } => write!(f, "line {line}: {message}"),
+            Error::Request { line, error } => write!(f, "line {line}: {error}"),
+            Error::Assertion {
+                line,
+                message,
+                exchange,
+            } => {
+                write!(f, "request on line {line} failed:\n{message}")?;
+                if let Some(exchange) = exchange {
+                    write!(f, "\n\n{exchange}")?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::Request { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// A parsed scenario.
+#[derive(Debug, Clone)]
+pub struct Scenario {
+    entries: Vec<Entry>,
+    variables: HashMap<String, Value>,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    line: usize,
+    method: Method,
+    target: String,
+    headers: Vec<(usize, HeaderName, String)>,
+    body: Option<String>,
+    response: Option<Expected>,
+}
+
+#[derive(Debug, Clone)]
+struct Expected {
+    line: usize,
+    status: Option<u16>,
+    headers: Vec<(usize, HeaderName, String)>,
+    captures: Vec<(usize, String, Query)>,
+    asserts: Vec<Assert>,
+}
+
+#[derive(Debug, Clone)]
+enum Query {
+    Status,
+    Header(HeaderName),
+    JsonPath(String),
+    Body,
+}
+
+#[derive(Debug, Clone)]
+struct Assert {
+    line: usize,
+    source: String,
+    query: Query,
+    negated: bool,
+    predicate: Predicate,
+}
+
+#[derive(Debug, Clone)]
+enum Predicate {
+    Eq(String),
+    Ne(String),
+    Contains(String),
+    StartsWith(String),
+    EndsWith(String),
+    Matches(String),
+    Exists,
+    IsUuid,
+}
+
+impl Scenario {
+    /// Loads a scenario from a file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Scenario, Error> {
+        std::fs::read_to_string(path).map_err(Error::Io)?.parse()
+    }
+
+    /// Defines a variable before the scenario is run, which can be used as `{{name}}`.
+    pub fn variable<N: Into<String>, V: Into<Value>>(mut self, name: N, value: V) -> Self {
+        self.variables.insert(name.into(), value.into());
+        self
+    }
+
+    /// Returns how many requests the scenario sends.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Checks whenever if the scenario doesn't have any requests.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Runs every request of the scenario with the default session of `ctx`, in order, and returns
+    /// every variable once all of them succeeded. The scenario stops at the first request whose
+    /// response doesn't match, reporting every assert of it that failed.
+    pub async fn run(&self, ctx: &TestContext) -> Result<HashMap<String, Value>, Error> {
+        let mut variables = self.variables.clone();
+        for entry in &self.entries {
+            run_entry(ctx, entry, &mut variables).await?;
+        }
+
+        Ok(variables)
+    }
+}
+
+impl FromStr for Scenario {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Parser::new(s).parse()
+    }
+}
+
+async fn run_entry(ctx: &TestContext, entry: &Entry, variables: &mut HashMap<String, Value>) -> Result<(), Error> {
+    let target = interpolate(&entry.target, variables, entry.line, false)?;
+    if !target.starts_with('/') {
+        return Err(Error::Invalid {
+            line: entry.line,
+            message: format!("request target `{target}` must be a path that starts with `/`"),
+        });
+    }
+
+    let mut headers = Vec::with_capacity(entry.headers.len());
+    for (line, name, value) in &entry.headers {
+        let value = interpolate(value, variables, *line, false)?;
+        let value = HeaderValue::from_str(&value).map_err(|_| Error::Invalid {
+            line: *line,
+            message: format!("`{value}` isn't a valid value for header `{name}`"),
+        })?;
+
+        headers.push((name.clone(), value));
+    }
+
+    let body = match entry.body {
+        Some(ref body) => Some(interpolate(body, variables, entry.line, false)?),
+        None => None,
+    };
+
+    let res = ctx
+        .request(target, entry.method.clone(), body.map(axum::body::Bytes::from), |req| {
+            for (name, value) in &headers {
+                req.headers_mut().insert(name.clone(), value.clone());
+            }
+        })
+        .await
+        .map_err(|error| Error::Request {
+            line: entry.line,
+            error,
+        })?;
+
+    let Some(ref expected) = entry.response else {
+        return Ok(());
+    };
+
+    let mut exchange = Exchange::of(&res).cloned();
+    let (parts, body) = res.into_parts();
+    let body = body
+        .collect()
+        .await
+        .map_err(|e| Error::Request {
+            line: entry.line,
+            error: crate::Error::Body(e),
+        })?
+        .to_bytes();
+
+    if let Some(exchange) = exchange.as_mut() {
+        exchange.response_body.get_or_insert_with(|| body.clone());
+    }
+
+    let response = Response {
+        status: parts.status.as_u16(),
+        headers: &parts.headers,
+        body: &body,
+        json: serde_json::from_slice(&body).ok(),
+    };
+
+    let mut failures = Vec::new();
+    if let Some(status) = expected.status {
+        if status != response.status {
+            failures.push(format!(
+                "line {}: expected status {status}, got {}",
+                expected.line, parts.status
+            ));
+        }
+    }
+
+    for (line, name, value) in &expected.headers {
+        let value = interpolate(value, variables, *line, false)?;
+        let actual = response
+            .headers
+            .get(name)
+            .map(|v| String::from_utf8_lossy(v.as_bytes()));
+        if actual.as_deref() != Some(value.as_str()) {
+            failures.push(format!(
+                "line {line}: expected header `{name}` to be `{value}`, got {}",
+                actual.map_or_else(|| String::from("<missing>"), |v| format!("`{v}`"))
+            ));
+        }
+    }
+
+    // captures are evaluated first so asserts of the same entry can use them
+    for (line, name, query) in &expected.captures {
+        match response.query(query) {
+            Some(value) => {
+                variables.insert(name.clone(), value);
+            }
+
+            None => failures.push(format!("line {line}: couldn't capture `{name}`, the value is missing")),
+        }
+    }
+
+    for assert in &expected.asserts {
+        let actual = response.query(&assert.query);
+        let passed = assert.predicate.evaluate(actual.as_ref(), variables, assert.line)? != assert.negated;
+        if !passed {
+            failures.push(format!(
+                "line {}: `{}` failed, actual value: {}",
+                assert.line,
+                assert.source,
+                actual.map_or_else(|| String::from("<missing>"), |v| v.to_string())
+            ));
+        }
+    }
+
+    if failures.is_empty() {
+        return Ok(());
+    }
+
+    Err(Error::Assertion {
+        line: entry.line,
+        message: failures.iter().fold(String::new(), |mut message, failure| {
+            let _ = write!(message, "\n    {failure}");
+            message
+        })[1..]
+            .to_owned(),
+        exchange: exchange.map(Box::new),
+    })
+}
+
+struct Response<'a> {
+    status: u16,
+    headers: &'a axum::http::HeaderMap,
+    body: &'a [u8],
+    json: Option<Value>,
+}
+
+impl Response<'_> {
+    fn query(&self, query: &Query) -> Option<Value> {
+        match query {
+            Query::Status => Some(Value::from(self.status)),
+            Query::Header(name) => self
+                .headers
+                .get(name)
+                .map(|value| Value::from(String::from_utf8_lossy(value.as_bytes()).into_owned())),
+
+            Query::Body => Some(Value::from(String::from_utf8_lossy(self.body).into_owned())),
+            Query::JsonPath(path) => {
+                let selected = crate::json::select(self.json.as_ref()?, path)?;
+                match path.contains("[*]") {
+                    true => Some(Value::Array(selected.into_iter().cloned().collect())),
+                    false => selected.first().map(|value| (*value).clone()),
+                }
+            }
+        }
+    }
+}
+
+impl Predicate {
+    fn evaluate(&self, actual: Option<&Value>, variables: &HashMap<String, Value>, line: usize) -> Result<bool, Error> {
+        let literal = |raw: &str| literal(raw, variables, line);
+        let text = |raw: &str| -> Result<String, Error> {
+            match literal(raw)? {
+                Value::String(s) => Ok(s),
+                value => Ok(value.to_string()),
+            }
+        };
+
+        let actual_str = actual.and_then(Value::as_str);
+        Ok(match self {
+            Predicate::Exists => actual.is_some(),
+            Predicate::IsUuid => actual_str.is_some_and(crate::util::is_uuid),
+            Predicate::Eq(raw) => {
+                let expected = literal(raw)?;
+                actual.is_some_and(|actual| equals(actual, &expected))
+            }
+
+            Predicate::Ne(raw) => {
+                let expected = literal(raw)?;
+                !actual.is_some_and(|actual| equals(actual, &expected))
+            }
+
+            Predicate::StartsWith(raw) => {
+                let prefix = text(raw)?;
+                actual_str.is_some_and(|s| s.starts_with(&prefix))
+            }
+
+            Predicate::EndsWith(raw) => {
+                let suffix = text(raw)?;
+                actual_str.is_some_and(|s| s.ends_with(&suffix))
+            }
+
+            Predicate::Contains(raw) => {
+                let expected = literal(raw)?;
+                match (actual, &expected) {
+                    (Some(Value::String(s)), Value::String(needle)) => s.contains(needle.as_str()),
+                    (Some(Value::Array(values)), expected) => values.iter().any(|value| equals(value, expected)),
+                    _ => false,
+                }
+            }
+
+            Predicate::Matches(raw) => {
+                let pattern = match regex_literal(raw) {
+                    Some(pattern) => interpolate(&pattern, variables, line, false)?,
+                    None => text(raw)?,
+                };
+
+                let regex = Regex::new(&pattern).map_err(|e| Error::Invalid {
+                    line,
+                    message: format!("invalid regex `{pattern}`: {e}"),
+                })?;
+
+                actual_str.is_some_and(|s| regex.is_match(s))
+            }
+        })
+    }
+}
+
+/// Returns the pattern of a `/regex/` literal, in which `/` is escaped as `\/`.
+fn regex_literal(raw: &str) -> Option<String> {
+    raw.strip_prefix('/')
+        .and_then(|s| s.strip_suffix('/'))
+        .map(|pattern| pattern.replace("\\/", "/"))
+}
+
+/// Checks whenever if two values are equal. Numbers are compared by their value, and strings
+/// (like header values) are parsed as JSON when they're compared with other values.
+fn equals(actual: &Value, expected: &Value) -> bool {
+    match (actual, expected) {
+        (Value::Number(a), Value::Number(b)) => crate::json::numbers_equal(a, b),
+        (Value::String(a), Value::String(b)) => a == b,
+        (Value::String(a), b) => serde_json::from_str::<Value>(a).is_ok_and(|a| equals(&a, b)),
+        (a, b) => a == b,
+    }
+}
+
+/// Parses a JSON literal after substituting its variables. A literal that only consists of a
+/// variable (`{{id}}`) is the variable's value itself.
+fn literal(raw: &str, variables: &HashMap<String, Value>, line: usize) -> Result<Value, Error> {
+    if let Some(name) = raw.strip_prefix("{{").and_then(|s| s.strip_suffix("}}")) {
+        if !name.contains("{{") {
+            return variable(variables, name.trim(), line).cloned();
+        }
+    }
+
+    let substituted = interpolate(raw, variables, line, true)?;
+    serde_json::from_str(&substituted).map_err(|e| Error::Invalid {
+        line,
+        message: format!("`{substituted}` isn't a valid value: {e}"),
+    })
+}
+
+fn variable<'a>(variables: &'a HashMap<String, Value>, name: &str, line: usize) -> Result<&'a Value, Error> {
+    variables.get(name).ok_or_else(|| Error::Invalid {
+        line,
+        message: format!("variable `{name}` isn't defined"),
+    })
+}
+
+/// Substitutes every `{{name}}` in `text`. If `escape` is true, then strings are escaped to be
+/// inserted into a JSON string.
+fn interpolate(text: &str, variables: &HashMap<String, Value>, line: usize, escape: bool) -> Result<String, Error> {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+
+        result.push_str(&rest[..start]);
+        match variable(variables, rest[start + 2..start + end].trim(), line)? {
+            Value::String(s) if escape => {
+                let quoted = Value::from(s.as_str()).to_string();
+                result.push_str(&quoted[1..quoted.len() - 1]);
+            }
+
+            Value::String(s) => result.push_str(s),
+            value => result.push_str(&value.to_string()),
+        }
+
+        rest = &rest[start + end + 2..];
+    }
+
+    result.push_str(rest);
+    Ok(result)
+}
+
+const METHODS: &[&str] = &[
+    "GET", "HEAD", "POST", "PUT", "DELETE", "CONNECT", "OPTIONS", "TRACE", "PATCH",
+];
+
+fn is_request_line(line: &str) -> bool {
+    line.split_once(' ')
+        .is_some_and(|(method, rest)| METHODS.contains(&method) && !rest.trim().is_empty())
+}
+
+fn is_response_line(line: &str) -> bool {
+    line == "HTTP" || line.starts_with("HTTP ") || line.starts_with("HTTP/")
+}
+
+fn is_comment(line: &str) -> bool {
+    line.trim_start().starts_with('#')
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Section {
+    Headers,
+    Captures,
+    Asserts,
+}
+
+struct Parser<'a> {
+    lines: Vec<&'a str>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(source: &'a str) -> Parser<'a> {
+        Parser {
+            lines: source.lines().collect(),
+            pos: 0,
+        }
+    }
+
+    /// Line number (1-based) of the current line.
+    fn line(&self) -> usize {
+        self.pos + 1
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.lines.get(self.pos).map(|line| line.trim_end())
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, Error> {
+        Err(Error::Parse {
+            line: self.line(),
+            message: message.into(),
+        })
+    }
+
+    fn skip_blank_and_comments(&mut self) {
+        while self
+            .peek()
+            .is_some_and(|line| line.trim().is_empty() || is_comment(line))
+        {
+            self.pos += 1;
+        }
+    }
+
+    fn parse(mut self) -> Result<Scenario, Error> {
+        let mut entries = Vec::new();
+        loop {
+            self.skip_blank_and_comments();
+            let Some(line) = self.peek() else {
+                break;
+            };
+
+            if !is_request_line(line) {
+                return self.error(format!("expected a request like `GET /path`, got `{line}`"));
+            }
+
+            entries.push(self.entry()?);
+        }
+
+        Ok(Scenario {
+            entries,
+            variables: HashMap::new(),
+        })
+    }
+
+    fn entry(&mut self) -> Result<Entry, Error> {
+        let line = self.line();
+        let (method, target) = self.peek().unwrap_or_default().split_once(' ').unwrap_or_default();
+
+        // an optional version after the target (`GET /path HTTP/1.1`) is ignored
+        let target = target.split_whitespace().next().unwrap_or_default().to_owned();
+        let method = Method::from_bytes(method.as_bytes()).expect("method to be valid");
+        self.pos += 1;
+
+        let mut headers = Vec::new();
+        while let Some(current) = self.peek() {
+            if current.trim().is_empty() || is_request_line(current) || is_response_line(current) {
+                break;
+            }
+
+            self.pos += 1;
+            if !is_comment(current) {
+                headers.push(self.header(current)?);
+            }
+        }
+
+        let mut body = Vec::new();
+        while let Some(current) = self.peek() {
+            if is_request_line(current) || is_response_line(current) {
+                break;
+            }
+
+            body.push(current);
+            self.pos += 1;
+        }
+
+        // comments before the next request aren't part of the body if a blank line separates them
+        let trailing = body
+            .iter()
+            .rposition(|line| !line.trim().is_empty() && !is_comment(line))
+            .map_or(0, |idx| idx + 1);
+
+        if let Some(blank) = (trailing..body.len()).find(|idx| body[*idx].trim().is_empty()) {
+            body.truncate(blank);
+        }
+
+        let body = body.join("\n").trim().to_owned();
+        let response = match self.peek() {
+            Some(current) if is_response_line(current) => Some(self.expected()?),
+            _ => None,
+        };
+
+        Ok(Entry {
+            line,
+            method,
+            target,
+            headers,
+            body: (!body.is_empty()).then_some(body),
+            response,
+        })
+    }
+
+    fn header(&self, line: &str) -> Result<(usize, HeaderName, String), Error> {
+        let Some((name, value)) = line.split_once(':') else {
+            return self.error_at(self.pos, format!("expected a header like `Name: value`, got `{line}`"));
+        };
+
+        match HeaderName::from_bytes(name.trim().as_bytes()) {
+            Ok(name) => Ok((self.pos, name, value.trim().to_owned())),
+            Err(_) => self.error_at(self.pos, format!("`{}` isn't a valid header name", name.trim())),
+        }
+    }
+
+    fn error_at<T>(&self, line: usize, message: String) -> Result<T, Error> {
+        Err(Error::Parse { line, message })
+    }
+
+    fn expected(&mut self) -> Result<Expected, Error> {
+        let line = self.line();
+        let status = match self.peek().unwrap_or_default().split_whitespace().nth(1) {
+            Some("*") => None,
+            Some(status) => match status.parse::<u16>() {
+                Ok(status) if (100..=999).contains(&status) => Some(status),
+                _ => return self.error(format!("`{status}` isn't a valid status code")),
+            },
+
+            None => return self.error("expected a status code like `HTTP 200`"),
+        };
+
+        self.pos += 1;
+
+        let mut expected = Expected {
+            line,
+            status,
+            headers: Vec::new(),
+            captures: Vec::new(),
+            asserts: Vec::new(),
+        };
+
+        let mut section = Section::Headers;
+        while let Some(current) = self.peek() {
+            if is_request_line(current) {
+                break;
+            }
+
+            self.pos += 1;
+
+            let current = current.trim();
+            if current.is_empty() || is_comment(current) {
+                continue;
+            }
+
+            match current {
+                "[Captures]" => section = Section::Captures,
+                "[Asserts]" => section = Section::Asserts,
+                _ if current.starts_with('[') => {
+                    return self.error_at(self.pos, format!("unknown section `{current}`"));
+                }
+
+                _ => match section {
+                    Section::Headers => expected.headers.push(self.header(current)?),
+                    Section::Captures => {
+                        let Some((name, query)) = current.split_once(':') else {
+                            return self.error_at(
+                                self.pos,
+                                format!("expected a capture like `name: query`, got `{current}`"),
+                            );
+                        };
+
+                        let mut rest = query.trim();
+                        let query = self.query(&mut rest)?;
+                        if !rest.is_empty() {
+                            return self.error_at(self.pos, format!("unexpected `{rest}` after the query"));
+                        }
+
+                        expected.captures.push((self.pos, name.trim().to_owned(), query));
+                    }
+
+                    Section::Asserts => {
+                        let assert = self.assert(current)?;
+                        expected.asserts.push(assert);
+                    }
+                },
+            }
+        }
+
+        Ok(expected)
+    }
+
+    fn query(&self, rest: &mut &str) -> Result<Query, Error> {
+        let (keyword, after) = rest.split_once(' ').unwrap_or((rest, ""));
+        *rest = after.trim_start();
+
+        match keyword {
+            "status" => Ok(Query::Status),
+            "body" => Ok(Query::Body),
+            "header" => {
+                let name = self.quoted(rest)?;
+                HeaderName::from_bytes(name.as_bytes())
+                    .map(Query::Header)
+                    .or_else(|_| self.error_at(self.pos, format!("`{name}` isn't a valid header name")))
+            }
+
+            "jsonpath" => {
+                let path = self.quoted(rest)?;
+                if crate::json::select(&Value::Null, &path).is_none() {
+                    return self.error_at(self.pos, format!("invalid JSON path `{path}`"));
+                }
+
+                Ok(Query::JsonPath(path))
+            }
+
+            _ => self.error_at(
+                self.pos,
+                format!("unknown query `{keyword}`, expected `status`, `header`, `jsonpath` or `body`"),
+            ),
+        }
+    }
+
+    /// Parses a quoted string at the start of `rest`.
+    fn quoted(&self, rest: &mut &str) -> Result<String, Error> {
+        let Some(after) = rest.strip_prefix('"') else {
+            return self.error_at(self.pos, format!("expected a quoted string, got `{rest}`"));
+        };
+
+        let mut value = String::new();
+        let mut chars = after.char_indices();
+        while let Some((idx, c)) = chars.next() {
+            match c {
+                '"' => {
+                    *rest = after[idx + 1..].trim_start();
+                    return Ok(value);
+                }
+
+                '\\' => match chars.next() {
+                    Some((_, c)) => value.push(c),
+                    None => break,
+                },
+
+                c => value.push(c),
+            }
+        }
+
+        self.error_at(self.pos, "unterminated string".to_owned())
+    }
+
+    fn assert(&self, source: &str) -> Result<Assert, Error> {
+        let mut rest = source;
+        let query = self.query(&mut rest)?;
+
+        let negated = match rest.strip_prefix("not ") {
+            Some(after) => {
+                rest = after.trim_start();
+                true
+            }
+
+            None => false,
+        };
+
+        let (operator, argument) = rest.split_once(' ').unwrap_or((rest, ""));
+        let argument = argument.trim().to_owned();
+        let predicate = match operator {
+            "exists" => Predicate::Exists,
+            "isUuid" => Predicate::IsUuid,
+            "==" => Predicate::Eq(argument.clone()),
+            "!=" => Predicate::Ne(argument.clone()),
+            "contains" => Predicate::Contains(argument.clone()),
+            "startsWith" => Predicate::StartsWith(argument.clone()),
+            "endsWith" => Predicate::EndsWith(argument.clone()),
+            "matches" => Predicate::Matches(argument.clone()),
+            "" => return self.error_at(self.pos, format!("expected a predicate after the query in `{source}`")),
+            _ => return self.error_at(self.pos, format!("unknown predicate `{operator}`")),
+        };
+
+        let needs_argument = !matches!(predicate, Predicate::Exists | Predicate::IsUuid);
+        if needs_argument == argument.is_empty() {
+            return self.error_at(
+                self.pos,
+                match needs_argument {
+                    true => format!("predicate `{operator}` expects a value"),
+                    false => format!("predicate `{operator}` doesn't take a value"),
+                },
+            );
+        }
+
+        // literals without variables can be checked right away
+        if needs_argument && !argument.contains("{{") {
+            let pattern = match (&predicate, regex_literal(&argument)) {
+                (Predicate::Matches(_), Some(pattern)) => Some(pattern),
+                _ => {
+                    let value = literal(&argument, &HashMap::new(), self.pos)
+                        .or_else(|_| self.error_at(self.pos, format!("`{argument}` isn't a valid JSON value")))?;
+
+                    match (&predicate, value) {
+                        (Predicate::Matches(_), Value::String(pattern)) => Some(pattern),
+                        _ => None,
+                    }
+                }
+            };
+
+            if let Some(Err(err)) = pattern.as_deref().map(Regex::new) {
+                return self.error_at(
+                    self.pos,
+                    format!("invalid regex `{}`: {err}", pattern.unwrap_or_default()),
+                );
+            }
+        }
+
+        Ok(Assert {
+            line: self.pos,
+            source: source.to_owned(),
+            query,
+            negated,
+            predicate,
+        })
+    }
+}
+
+#[doc(hidden)]
+pub async fn __run(source: &str, path: &str, ctx: &TestContext) {
+    let scenario = match source.parse::<Scenario>() {
+        Ok(scenario) => scenario,
+        Err(err) => panic!("failed to parse scenario `{path}`: {err}"),
+    };
+
+    if let Err(err) = scenario.run(ctx).await {
+        panic!("scenario `{path}` failed: {err}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{equals, interpolate, Error, Scenario};
+    use crate::TestContext;
+    use axum::{
+        extract::Path,
+        http::{header, StatusCode},
+        routing, Json, Router,
+    };
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+
+    const SCENARIO: &str = r#"
+# create a repository
+POST /repositories
+Content-Type: application/json
+X-Name: {{name}}
+
+{"name": "{{name}}"}
+
+HTTP 201
+content-type: application/json
+[Captures]
+id: jsonpath "$.data.id"
+[Asserts]
+jsonpath "$.data.name" == "{{name}}"
+header "x-request-id" isUuid
+header "content-length" exists
+
+GET /repositories/{{id}}
+
+HTTP 200
+[Asserts]
+status == 200
+jsonpath "$.data.id" == {{id}}
+jsonpath "$.data.tags" contains "helm"
+jsonpath "$.data.tags[*]" not contains "docker"
+body matches /^\{.*\}$/
+body not contains "error"
+"#;
+
+    fn router() -> Router {
+        Router::new()
+            .route(
+                "/repositories",
+                routing::post(|Json(body): Json<Value>| async move {
+                    (
+                        StatusCode::CREATED,
+                        [("x-request-id", "9f0b6a5e-2d8c-4f6e-a8f7-5d1c6c1a2b3c")],
+                        Json(json!({ "data": { "id": 1, "name": body["name"] } })),
+                    )
+                }),
+            )
+            .route(
+                "/repositories/:id",
+                routing::get(
+                    |Path(id): Path<u64>| async move { Json(json!({ "data": { "id": id, "tags": ["helm"] } })) },
+                ),
+            )
+    }
+
+    #[test]
+    fn parsing() {
+        let scenario = SCENARIO.parse::<Scenario>().unwrap();
+        assert_eq!(scenario.len(), 2);
+
+        let entry = &scenario.entries[0];
+        assert_eq!(entry.line, 3);
+        assert_eq!(entry.target, "/repositories");
+        assert_eq!(entry.headers.len(), 2);
+        assert_eq!(entry.body.as_deref(), Some(r#"{"name": "{{name}}"}"#));
+
+        let expected = entry.response.as_ref().unwrap();
+        assert_eq!(expected.line, 9);
+        assert_eq!(expected.status, Some(201));
+        assert_eq!(expected.headers[0].1, header::CONTENT_TYPE);
+        assert_eq!(expected.captures.len(), 1);
+        assert_eq!(expected.asserts.len(), 3);
+        assert_eq!(expected.asserts[1].line, 15);
+
+        let entry = &scenario.entries[1];
+        assert!(entry.body.is_none());
+        assert_eq!(entry.response.as_ref().unwrap().asserts.len(), 6);
+    }
+
+    #[test]
+    fn comments_between_entries() {
+        let scenario = "GET /a\n\n# fetch b\nGET /b\n\n# create c\n\nPOST /c\n\n{}\n# not a comment\n\n# done\n"
+            .parse::<Scenario>()
+            .unwrap();
+
+        assert_eq!(scenario.len(), 3);
+        assert!(scenario.entries[0].body.is_none());
+        assert!(scenario.entries[1].body.is_none());
+        assert_eq!(scenario.entries[2].body.as_deref(), Some("{}\n# not a comment"));
+    }
+
+    #[test]
+    fn parse_errors() {
+        let cases = [
+            ("hello", 1, "expected a request like `GET /path`, got `hello`"),
+            (
+                "GET /\nnot a header",
+                2,
+                "expected a header like `Name: value`, got `not a header`",
+            ),
+            ("GET /\n\nHTTP abc", 3, "`abc` isn't a valid status code"),
+            (
+                "GET /\nHTTP 200\n[Asserts]\nstatus",
+                4,
+                "expected a predicate after the query in `status`",
+            ),
+            (
+                "GET /\nHTTP 200\n[Asserts]\ncookie \"a\" exists",
+                4,
+                "unknown query `cookie`, expected `status`, `header`, `jsonpath` or `body`",
+            ),
+            (
+                "GET /\nHTTP 200\n[Asserts]\nbody matches \"(\"",
+                4,
+                "invalid regex `(`: regex parse error:",
+            ),
+            (
+                "GET /\nHTTP 200\n[Asserts]\nstatus == nope",
+                4,
+                "`nope` isn't a valid JSON value",
+            ),
+            (
+                "GET /\nHTTP 200\n[Asserts]\nstatus exists 1",
+                4,
+                "predicate `exists` doesn't take a value",
+            ),
+            ("GET /\nHTTP 200\n[Foo]", 3, "unknown section `[Foo]`"),
+            (
+                "GET /\nHTTP 200\n[Captures]\nid: header \"x-id",
+                4,
+                "unterminated string",
+            ),
+        ];
+
+        for (source, line, message) in cases {
+            match source.parse::<Scenario>() {
+                Err(Error::Parse {
+                    line: actual,
+                    message: m,
+                }) => {
+                    assert_eq!(actual, line, "line of {source:?}");
+                    assert!(m.starts_with(message), "message of {source:?}: {m}");
+                }
+
+                other => panic!("expected {source:?} to fail to parse, got {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn equality() {
+        assert!(equals(&json!(1), &json!(1.0)));
+        assert!(equals(&json!("2"), &json!(2)));
+        assert!(!equals(
+            &json!(9_007_199_254_740_993_u64),
+            &json!(9_007_199_254_740_992_u64)
+        ));
+    }
+
+    #[test]
+    fn interpolation() {
+        let variables = HashMap::from([
+            (String::from("id"), json!(1)),
+            (String::from("name"), json!("say \"hi\"")),
+        ]);
+
+        assert_eq!(interpolate("/repos/{{id}}", &variables, 1, false).unwrap(), "/repos/1");
+        assert_eq!(interpolate("{{ name }}", &variables, 1, false).unwrap(), "say \"hi\"");
+        assert_eq!(
+            interpolate("\"{{name}}\"", &variables, 1, true).unwrap(),
+            r#""say \"hi\"""#
+        );
+        assert_eq!(interpolate("{{", &variables, 1, false).unwrap(), "{{");
+        assert!(matches!(
+            interpolate("{{missing}}", &variables, 7, false),
+            Err(Error::Invalid { line: 7, .. })
+        ));
+    }
+
+    #[tokio::test]
+    #[cfg_attr(
+        windows,
+        ignore = "fails on Windows: hyper_util::client::legacy::Error(Connect, ConnectError(\"tcp connect error\", Os { code: 10049, kind: AddrNotAvailable, message: \"The requested address is not valid in its context.\" })))"
+    )]
+    async fn running() {
+        let mut ctx = TestContext::default();
+        ctx.serve(router()).await;
+
+        let scenario = SCENARIO.parse::<Scenario>().unwrap().variable("name", "charted");
+        let variables = scenario.run(&ctx).await.unwrap();
+
+        assert_eq!(variables["id"], json!(1));
+        assert_eq!(ctx.history().len(), 2);
+
+        let scenario = r#"
+GET /repositories/2
+
+HTTP 201
+content-type: text/plain
+[Asserts]
+jsonpath "$.data.id" == 3
+jsonpath "$.data.id" == 2
+jsonpath "$.data.missing" exists
+"#
+        .parse::<Scenario>()
+        .unwrap();
+
+        let err = scenario.run(&ctx).await.unwrap_err();
+        let message = err.to_string();
+
+        assert_eq!(err.line(), Some(2));
+        assert!(message.starts_with(
+            "request on line 2 failed:\n    line 4: expected status 201, got 200 OK\n    line 5: expected header `content-type` to be `text/plain`, got `application/json`\n    line 7: `jsonpath \"$.data.id\" == 3` failed, actual value: 2\n    line 9: `jsonpath \"$.data.missing\" exists` failed, actual value: <missing>\n\n--- request ---\n"
+        ));
+    }
+}
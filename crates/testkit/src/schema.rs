@@ -0,0 +1,335 @@
+// 📦🦋 charted TestKit: testing library for Axum services with testcontainers support
+// Copyright (c) 2024 Noelware, LLC. <team@noelware.org>
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Validation of response bodies against a [JSON Schema](https://json-schema.org).
+//!
+//! A [`Schema`] can be created from an inline JSON value, from a file, or derived from a type that
+//! implements [`schemars::JsonSchema`] with the `schemars` crate feature. Schemas are validated as
+//! draft 2020-12 unless they declare a different draft with `$schema`.
+//!
+//! ## Example
+//! ```rust
+//! # use axum::http::{response::Response, StatusCode};
+//! use serde_json::json;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let res = Response::builder()
+//!     .status(StatusCode::OK)
+//!     .body(String::from(r#"{"id":1,"name":"noel"}"#))
+//!     .expect("response to be constructed");
+//!
+//! charted_testkit::assert_json_schema!(res, json!({
+//!     "type": "object",
+//!     "required": ["id", "name"],
+//!     "properties": {
+//!         "id": { "type": "integer" },
+//!         "name": { "type": "string" }
+//!     }
+//! }));
+//! # }
+//! ```
+
+use axum::body::Bytes;
+use jsonschema::{Draft, JSONSchema};
+use serde_json::Value;
+use std::{
+    fmt::{Debug, Display, Write},
+    path::Path,
+    sync::Arc,
+};
+
+/// Error type for creating a [`Schema`].
+#[derive(Debug)]
+pub enum Error {
+    /// The schema file couldn't be read.
+    Io(std::io::Error),
+
+    /// The schema file isn't valid JSON.
+    Parse(serde_json::Error),
+
+    /// The schema isn't a valid JSON Schema.
+    Invalid(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "failed to read JSON schema: {err}"),
+            Error::Parse(err) => write!(f, "failed to parse JSON schema: {err}"),
+            Error::Invalid(message) => write!(f, "invalid JSON schema: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::Parse(err) => Some(err),
+            Error::Invalid(_) => None,
+        }
+    }
+}
+
+/// A compiled JSON Schema. This is cheap to clone.
+#[derive(Clone)]
+pub struct Schema(Arc<JSONSchema>);
+
+impl Debug for Schema {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Schema").finish_non_exhaustive()
+    }
+}
+
+impl Schema {
+    /// Compiles a schema.
+    pub fn new(schema: &Value) -> Result<Schema, Error> {
+        let draft = match schema.get("$schema") {
+            // the draft will be detected from `$schema`
+            Some(_) => None,
+            None => Some(Draft::Draft202012),
+        };
+
+        Schema::compile(schema, draft)
+    }
+
+    /// Loads and compiles a schema from a JSON file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Schema, Error> {
+        let contents = std::fs::read(path).map_err(Error::Io)?;
+        Schema::new(&serde_json::from_slice(&contents).map_err(Error::Parse)?)
+    }
+
+    /// Derives a schema from a type.
+    ///
+    /// ## Example
+    /// ```rust
+    /// use charted_testkit::schema::Schema;
+    /// use serde_json::json;
+    ///
+    /// #[derive(schemars::JsonSchema)]
+    /// struct User {
+    ///     id: u64,
+    ///     name: String,
+    /// }
+    ///
+    /// let schema = Schema::of::<User>();
+    /// assert!(schema.validate(&json!({ "id": 1, "name": "noel" })).is_ok());
+    /// assert!(schema.validate(&json!({ "id": "1" })).is_err());
+    /// ```
+    #[cfg(feature = "schemars")]
+    pub fn of<T: schemars::JsonSchema>() -> Schema {
+        let schema = schemars::schema_for!(T);
+        Schema::new(schema.as_value()).expect("schemas generated by `schemars` to be valid")
+    }
+
+    pub(crate) fn compile(schema: &Value, draft: Option<Draft>) -> Result<Schema, Error> {
+        let mut options = JSONSchema::options();
+        if let Some(draft) = draft {
+            options.with_draft(draft);
+        }
+
+        options
+            .compile(schema)
+            .map(|schema| Schema(Arc::new(schema)))
+            .map_err(|e| Error::Invalid(e.to_string()))
+    }
+
+    /// Validates `instance`, returning every violation if it isn't valid.
+    pub fn validate(&self, instance: &Value) -> Result<(), Vec<Violation>> {
+        self.0.validate(instance).map_err(|errors| {
+            errors
+                .map(|error| Violation {
+                    instance_path: error.instance_path.to_string(),
+                    schema_path: error.schema_path.to_string(),
+                    message: error.to_string(),
+                })
+                .collect()
+        })
+    }
+}
+
+impl TryFrom<Value> for Schema {
+    type Error = Error;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        Schema::new(&value)
+    }
+}
+
+impl TryFrom<&Value> for Schema {
+    type Error = Error;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        Schema::new(value)
+    }
+}
+
+impl From<&Schema> for Schema {
+    fn from(value: &Schema) -> Self {
+        value.clone()
+    }
+}
+
+/// A part of an instance that isn't valid against a [`Schema`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    instance_path: String,
+    schema_path: String,
+    message: String,
+}
+
+impl Violation {
+    /// JSON pointer to the invalid value, like `/items/0/name`. This is empty for the root value.
+    pub fn instance_path(&self) -> &str {
+        &self.instance_path
+    }
+
+    /// JSON pointer to the keyword of the schema that the value violates, like `/properties/name/type`.
+    pub fn schema_path(&self) -> &str {
+        &self.schema_path
+    }
+
+    /// Message of the violation, like `1 is not of type "string"`.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} (at schema path `{}`)",
+            if self.instance_path.is_empty() {
+                "/"
+            } else {
+                &self.instance_path
+            },
+            self.message,
+            self.schema_path
+        )
+    }
+}
+
+#[doc(hidden)]
+#[track_caller]
+pub fn __assert_json_schema<S>(body: Bytes, schema: S, exchange: Option<&crate::exchange::Exchange>)
+where
+    S: TryInto<Schema>,
+    S::Error: Display,
+{
+    let schema = match schema.try_into() {
+        Ok(schema) => schema,
+        Err(err) => panic!("{err}"),
+    };
+
+    let describe = || match exchange {
+        Some(exchange) => exchange.with_body(&body).to_string(),
+        None => format!("body: {}", crate::util::display_body(&body)),
+    };
+
+    let instance: Value = match serde_json::from_slice(&body) {
+        Ok(value) => value,
+        Err(err) => {
+            return crate::soft::__fail(format!("response body is not valid JSON: {err}\n\n{}", describe()));
+        }
+    };
+
+    let Err(violations) = schema.validate(&instance) else {
+        return;
+    };
+
+    let mut message = String::from("JSON body doesn't match the schema:");
+    for violation in &violations {
+        let _ = write!(message, "\n    {violation}");
+    }
+
+    let _ = write!(message, "\n\n{}", describe());
+    crate::soft::__fail(message);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Schema;
+    use serde_json::json;
+
+    #[test]
+    fn violations() {
+        let schema = Schema::new(&json!({
+            "type": "object",
+            "required": ["name"],
+            "properties": {
+                "items": { "type": "array", "items": { "type": "integer" } },
+                "name": { "type": "string" }
+            }
+        }))
+        .unwrap();
+
+        assert!(schema.validate(&json!({ "name": "noel", "items": [1, 2] })).is_ok());
+
+        let violations = schema.validate(&json!({ "items": [1, "2"] })).unwrap_err();
+        let rendered = violations.iter().map(ToString::to_string).collect::<Vec<_>>();
+
+        assert_eq!(
+            rendered,
+            [
+                r#"/items/1: "2" is not of type "integer" (at schema path `/properties/items/items/type`)"#,
+                r#"/: "name" is a required property (at schema path `/required`)"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn drafts() {
+        // `exclusiveMaximum` was a boolean in draft 4, but it is a number since draft 6
+        let schema = json!({ "maximum": 5, "exclusiveMaximum": true });
+        assert!(matches!(Schema::new(&schema), Err(super::Error::Invalid(_))));
+
+        let mut draft4 = schema.clone();
+        draft4["$schema"] = json!("http://json-schema.org/draft-04/schema#");
+
+        let schema = Schema::new(&draft4).unwrap();
+        assert!(schema.validate(&json!(4)).is_ok());
+        assert!(schema.validate(&json!(5)).is_err());
+    }
+
+    #[cfg(feature = "schemars")]
+    #[test]
+    fn derived() {
+        #[derive(schemars::JsonSchema)]
+        #[allow(dead_code)]
+        struct Repository {
+            id: u64,
+            name: String,
+            description: Option<String>,
+        }
+
+        let schema = Schema::of::<Repository>();
+        assert!(schema
+            .validate(&json!({ "id": 1, "name": "charted", "description": null }))
+            .is_ok());
+
+        let violations = schema.validate(&json!({ "id": -1, "name": "charted" })).unwrap_err();
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].instance_path(), "/id");
+    }
+}
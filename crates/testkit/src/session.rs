@@ -0,0 +1,576 @@
+// 📦🦋 charted TestKit: testing library for Axum services with testcontainers support
+// Copyright (c) 2024 Noelware, LLC. <team@noelware.org>
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Client sessions that send requests to the ephemeral server.
+//!
+//! Every [`TestContext`][crate::TestContext] has a default session that is used by
+//! [`TestContext::request`][crate::TestContext::request], and named sessions can be
+//! created with [`TestContext::client`][crate::TestContext::client]. Each named session
+//! has its own default headers, authentication and cookie jar, which makes it easy to
+//! test interactions between multiple users.
+//!
+//! Defaults that are configured on the [`TestContext`][crate::TestContext] apply to every
+//! session. Per request, values are resolved in the following order, where the first one
+//! wins:
+//!
+//! 1. whatever the `build` function of a request sets
+//! 2. the defaults of the session that sends the request
+//! 3. the defaults of the [`TestContext`][crate::TestContext]
+
+use crate::{
+    compression,
+    cookies::CookieJar,
+    exchange::{self, Exchange},
+    history::{History, Record, Timings},
+    redirect::{self, Hop, RedirectChain, RedirectPolicy},
+    Error,
+};
+use axum::{
+    body::{Body, Bytes},
+    extract::Request,
+    http::{header, HeaderMap, HeaderName, HeaderValue},
+    response::Response,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use http_body_util::{BodyExt, Full};
+use hyper::{body::Incoming, Method};
+use hyper_util::client::legacy::{connect::HttpConnector, Client};
+use std::{
+    fmt::Debug,
+    future::Future,
+    net::SocketAddr,
+    sync::{Arc, OnceLock, RwLock},
+    time::{Instant, SystemTime},
+};
+
+/// State that is shared between the [`TestContext`][crate::TestContext] and all of its sessions.
+pub(crate) struct Shared {
+    pub(crate) client: Client<HttpConnector, Full<Bytes>>,
+    pub(crate) addr: OnceLock<SocketAddr>,
+    /// Scheme and authority (i.e, `http://127.0.0.1:3000`) that requests are sent to, which is
+    /// either the ephemeral server or the base URL the context was created with.
+    pub(crate) origin: OnceLock<String>,
+    pub(crate) defaults: RwLock<Defaults>,
+    pub(crate) history: History,
+}
+
+/// Context-level defaults that are applied to the requests of every session.
+#[derive(Debug)]
+pub(crate) struct Defaults {
+    pub(crate) headers: HeaderMap,
+    pub(crate) base_path: Option<String>,
+    pub(crate) auth: Option<Auth>,
+    pub(crate) redirect: Option<RedirectPolicy>,
+    pub(crate) decompress: bool,
+    pub(crate) buffer: bool,
+    #[cfg(feature = "openapi")]
+    pub(crate) openapi: Option<std::sync::Arc<crate::openapi::OpenApiSpec>>,
+}
+
+impl Default for Defaults {
+    fn default() -> Self {
+        Defaults {
+            headers: HeaderMap::new(),
+            base_path: None,
+            auth: None,
+            redirect: None,
+            decompress: false,
+            buffer: false,
+            #[cfg(feature = "openapi")]
+            openapi: None,
+        }
+    }
+}
+
+/// Authentication scheme that is sent in the `Authorization` header.
+#[derive(Clone, PartialEq, Eq)]
+pub enum Auth {
+    /// `Authorization: Basic base64(username:password)`
+    Basic { username: String, password: Option<String> },
+
+    /// `Authorization: Bearer <token>`
+    Bearer(String),
+}
+
+impl Auth {
+    /// Creates a new [`Auth::Basic`] scheme.
+    pub fn basic<U: Into<String>, P: Into<String>>(username: U, password: Option<P>) -> Auth {
+        Auth::Basic {
+            username: username.into(),
+            password: password.map(Into::into),
+        }
+    }
+
+    /// Creates a new [`Auth::Bearer`] scheme.
+    pub fn bearer<T: Into<String>>(token: T) -> Auth {
+        Auth::Bearer(token.into())
+    }
+
+    /// Returns the value of the `Authorization` header for this scheme.
+    ///
+    /// ## Example
+    /// ```rust
+    /// use charted_testkit::session::Auth;
+    ///
+    /// let auth = Auth::basic("noel", Some("weow"));
+    /// assert_eq!(auth.header_value(), "Basic bm9lbDp3ZW93");
+    /// ```
+    pub fn header_value(&self) -> HeaderValue {
+        let value = match self {
+            Auth::Basic { username, password } => {
+                let credentials = format!("{username}:{}", password.as_deref().unwrap_or_default());
+                format!("Basic {}", STANDARD.encode(credentials))
+            }
+
+            Auth::Bearer(token) => format!("Bearer {token}"),
+        };
+
+        let mut value = HeaderValue::from_str(&value).expect("`Authorization` header to be a valid header value");
+        value.set_sensitive(true);
+
+        value
+    }
+}
+
+// credentials shouldn't be leaked in test output
+impl Debug for Auth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Auth::Basic { username, .. } => f
+                .debug_struct("Basic")
+                .field("username", username)
+                .field("password", &"<redacted>")
+                .finish(),
+
+            Auth::Bearer(_) => f.debug_tuple("Bearer").field(&"<redacted>").finish(),
+        }
+    }
+}
+
+struct Inner {
+    name: Option<String>,
+    shared: Arc<Shared>,
+    headers: RwLock<HeaderMap>,
+    auth: RwLock<Option<Auth>>,
+    base_path: RwLock<Option<String>>,
+    cookies: Option<CookieJar>,
+}
+
+/// A client identity that sends requests to the ephemeral server with its own default
+/// headers, authentication and cookie jar.
+///
+/// [`Session`] is cheap to clone and all clones share the same state.
+#[derive(Clone)]
+pub struct Session(Arc<Inner>);
+
+impl Debug for Session {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Session")
+            .field("name", &self.0.name)
+            .field("auth", &*self.0.auth.read().unwrap_or_else(|e| e.into_inner()))
+            .field("cookies", &self.0.cookies.is_some())
+            .finish()
+    }
+}
+
+impl Session {
+    pub(crate) fn new(name: Option<String>, shared: Arc<Shared>, cookies: Option<CookieJar>) -> Session {
+        Session(Arc::new(Inner {
+            name,
+            shared,
+            headers: RwLock::default(),
+            auth: RwLock::default(),
+            base_path: RwLock::default(),
+            cookies,
+        }))
+    }
+
+    /// Returns the name of this session, or `None` if this is the default session of a
+    /// [`TestContext`][crate::TestContext].
+    pub fn name(&self) -> Option<&str> {
+        self.0.name.as_deref()
+    }
+
+    /// Returns the cookie jar of this session, if it has one. Named sessions always have
+    /// their own cookie jar.
+    pub fn cookies(&self) -> Option<&CookieJar> {
+        self.0.cookies.as_ref()
+    }
+
+    /// Returns a copy of the default headers that are sent on every request of this session.
+    pub fn headers(&self) -> HeaderMap {
+        self.0.headers.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Sets a default header that is sent on every request of this session, replacing any
+    /// existing value.
+    pub fn set_header(&self, name: HeaderName, value: HeaderValue) {
+        self.0
+            .headers
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(name, value);
+    }
+
+    /// Removes a default header from this session.
+    pub fn remove_header(&self, name: HeaderName) {
+        self.0.headers.write().unwrap_or_else(|e| e.into_inner()).remove(name);
+    }
+
+    /// Returns the authentication scheme of this session.
+    pub fn auth(&self) -> Option<Auth> {
+        self.0.auth.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Sets the authentication scheme that is sent in the `Authorization` header of every
+    /// request of this session, overriding the context's scheme. Passing `None` removes it
+    /// and falls back to the context's scheme.
+    pub fn set_auth<A: Into<Option<Auth>>>(&self, auth: A) {
+        *self.0.auth.write().unwrap_or_else(|e| e.into_inner()) = auth.into();
+    }
+
+    /// Returns the base path of this session.
+    pub fn base_path(&self) -> Option<String> {
+        self.0.base_path.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Sets the base path that is prefixed to the path of every request of this session,
+    /// overriding the context's base path. Passing `None` removes it and falls back to the
+    /// context's base path, while passing an empty string disables the context's base path.
+    pub fn set_base_path<P: Into<String>>(&self, path: Option<P>) {
+        *self.0.base_path.write().unwrap_or_else(|e| e.into_inner()) = path.map(Into::into);
+    }
+
+    /// Sends a request to the ephemeral server (or the [base URL][crate::TestContext::from_base_url])
+    /// as this session.
+    ///
+    /// The base path is prefixed to `uri`, and the default headers, `Authorization` header and
+    /// `Cookie` header are set before `build` is called, so all of them can be overwritten
+    /// per request.
+    pub fn request<U: AsRef<str> + 'static, B: Into<Option<Bytes>>, F: Fn(&mut Request<Full<Bytes>>)>(
+        &self,
+        uri: U,
+        method: Method,
+        body: B,
+        build: F,
+    ) -> impl Future<Output = Result<Response<Body>, Error>> + Send + 'static {
+        let origin = self
+            .0
+            .shared
+            .origin
+            .get()
+            .expect("no server to send requests to, call `TestContext::serve` first");
+        let defaults = self.0.shared.defaults.read().unwrap_or_else(|e| e.into_inner());
+        let base_path = self
+            .base_path()
+            .or_else(|| defaults.base_path.clone())
+            .unwrap_or_default();
+
+        let mut req = Request::<Full<Bytes>>::new(Full::new(body.into().unwrap_or_default()));
+        *req.method_mut() = method;
+        *req.uri_mut() = format!("{origin}{}", join_path(&base_path, uri.as_ref()))
+            .parse()
+            .expect("failed to parse into `hyper::Uri`");
+
+        *req.headers_mut() = defaults.headers.clone();
+        merge_headers(req.headers_mut(), self.headers());
+
+        if let Some(auth) = self.auth().or_else(|| defaults.auth.clone()) {
+            req.headers_mut().insert(header::AUTHORIZATION, auth.header_value());
+        }
+
+        let policy = defaults.redirect.clone();
+        let decompress = defaults.decompress;
+        #[cfg(feature = "openapi")]
+        let openapi = defaults.openapi.clone();
+
+        // response bodies can only be validated if they were buffered
+        #[cfg(feature = "openapi")]
+        let buffer = defaults.buffer || openapi.is_some();
+        #[cfg(not(feature = "openapi"))]
+        let buffer = defaults.buffer;
+        drop(defaults);
+
+        if let Some(value) = self.cookies().and_then(|jar| jar.header_for(req.uri())) {
+            req.headers_mut().insert(header::COOKIE, value);
+        }
+
+        build(&mut req);
+
+        let session = self.clone();
+        async move {
+            let started_at = SystemTime::now();
+            let start = Instant::now();
+            let res = match policy {
+                Some(policy) => session.send_following_redirects(req, policy).await?,
+                None => session.send(req).await?,
+            };
+
+            let wait = start.elapsed();
+            let res = compression::finish(res, decompress).await?;
+            let res = exchange::buffer(res, buffer).await?;
+            if let Some(exchange) = Exchange::of(&res) {
+                let timings = Timings {
+                    wait,
+                    receive: start.elapsed() - wait,
+                };
+
+                session.0.shared.history.push(Record::new(
+                    session.name().map(str::to_owned),
+                    started_at,
+                    timings,
+                    exchange,
+                ));
+            }
+
+            #[cfg(feature = "openapi")]
+            if let (Some(spec), Some(exchange)) = (openapi, Exchange::of(&res)) {
+                if let Err(report) = spec.validate(exchange) {
+                    crate::soft::__fail(format!("{report}\n\n{exchange}"));
+                }
+            }
+
+            Ok(res)
+        }
+    }
+
+    async fn send(&self, req: Request<Full<Bytes>>) -> Result<Response<Incoming>, Error> {
+        let (parts, body) = req.into_parts();
+
+        // collecting a `Full` body never fails and is immediate
+        let body = body
+            .collect()
+            .await
+            .map(|collected| collected.to_bytes())
+            .unwrap_or_default();
+
+        let req = Request::from_parts(parts.clone(), Full::new(body.clone()));
+        let mut res = self.0.shared.client.request(req).await?;
+        if let Some(jar) = self.cookies() {
+            jar.store(&parts.uri, res.headers());
+        }
+
+        let exchange = Exchange {
+            method: parts.method,
+            uri: parts.uri,
+            version: res.version(),
+            request_headers: parts.headers,
+            request_body: body,
+            status: res.status(),
+            response_headers: res.headers().clone(),
+            response_body: None,
+        };
+
+        res.extensions_mut().insert(exchange);
+        Ok(res)
+    }
+
+    async fn send_following_redirects(
+        &self,
+        req: Request<Full<Bytes>>,
+        policy: RedirectPolicy,
+    ) -> Result<Response<Incoming>, Error> {
+        let (parts, body) = req.into_parts();
+
+        // collecting a `Full` body never fails and is immediate
+        let mut body = body
+            .collect()
+            .await
+            .map(|collected| collected.to_bytes())
+            .unwrap_or_default();
+        let mut method = parts.method;
+        let mut uri = parts.uri;
+        let mut headers = parts.headers;
+        let mut hops = Vec::new();
+
+        loop {
+            let mut req = Request::new(Full::new(body.clone()));
+            *req.method_mut() = method.clone();
+            *req.uri_mut() = uri.clone();
+            *req.version_mut() = parts.version;
+            *req.headers_mut() = headers.clone();
+
+            let started_at = SystemTime::now();
+            let start = Instant::now();
+            let mut res = self.send(req).await?;
+            let location = match res.headers().get(header::LOCATION) {
+                Some(location) if redirect::is_redirect(res.status()) => {
+                    String::from_utf8_lossy(location.as_bytes()).into_owned()
+                }
+
+                _ => {
+                    res.extensions_mut().insert(RedirectChain(hops));
+                    return Ok(res);
+                }
+            };
+
+            // drain the body before following the redirect and record the hop, so that the history
+            // shows the whole chain rather than only the last response
+            let wait = start.elapsed();
+            let (parts, hop_body) = res.into_parts();
+            let hop_body = hop_body
+                .collect()
+                .await
+                .map_err(|e| Error::Body(axum::Error::new(e)))?
+                .to_bytes();
+
+            if let Some(exchange) = parts.extensions.get::<Exchange>() {
+                let exchange = Exchange {
+                    response_body: Some(hop_body),
+                    ..exchange.clone()
+                };
+
+                let timings = Timings {
+                    wait,
+                    receive: start.elapsed() - wait,
+                };
+
+                self.0.shared.history.push(Record::new(
+                    self.name().map(str::to_owned),
+                    started_at,
+                    timings,
+                    &exchange,
+                ));
+            }
+
+            let status = parts.status;
+            let next = redirect::resolve(&uri, &location).ok_or(Error::InvalidRedirect { location })?;
+            let cross_origin = !redirect::same_origin(&uri, &next);
+
+            hops.push(Hop {
+                method: method.clone(),
+                uri: uri.clone(),
+                status,
+                headers: parts.headers,
+            });
+
+            if hops.len() > policy.max_hops() {
+                return Err(Error::TooManyRedirects {
+                    max_hops: policy.max_hops(),
+                    chain: RedirectChain(hops),
+                });
+            }
+
+            if cross_origin && policy.is_same_origin_only() {
+                return Err(Error::CrossOriginRedirect { from: uri, to: next });
+            }
+
+            if redirect::rewrites_to_get(status, &method) {
+                method = Method::GET;
+                body = Bytes::new();
+
+                for name in [
+                    header::CONTENT_TYPE,
+                    header::CONTENT_LENGTH,
+                    header::CONTENT_ENCODING,
+                    header::TRANSFER_ENCODING,
+                ] {
+                    headers.remove(name);
+                }
+            }
+
+            if cross_origin {
+                headers.remove(header::AUTHORIZATION);
+                headers.remove(header::COOKIE);
+            }
+
+            if let Some(value) = self.cookies().and_then(|jar| jar.header_for(&next)) {
+                headers.insert(header::COOKIE, value);
+            }
+
+            uri = next;
+        }
+    }
+}
+
+/// Prefixes `path` with `base`, making sure that there is exactly one `/` in between.
+fn join_path(base: &str, path: &str) -> String {
+    let base = base.trim_end_matches('/');
+    match (base.is_empty(), path.starts_with('/')) {
+        (true, true) => path.to_owned(),
+        (true, false) | (false, false) => format!("{base}/{path}"),
+        (false, true) => format!("{base}{path}"),
+    }
+}
+
+/// Replaces all values of every header in `from` into `into`.
+fn merge_headers(into: &mut HeaderMap, from: HeaderMap) {
+    let mut current = None;
+    for (name, value) in from {
+        if let Some(name) = name {
+            into.remove(&name);
+            current = Some(name);
+        }
+
+        // `HeaderMap::into_iter` only yields the name of the first value of each header
+        if let Some(ref name) = current {
+            into.append(name.clone(), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{join_path, merge_headers, Auth};
+    use axum::http::{header, HeaderMap, HeaderValue};
+
+    #[test]
+    fn join_paths() {
+        assert_eq!(join_path("", "/repos"), "/repos");
+        assert_eq!(join_path("", "repos"), "/repos");
+        assert_eq!(join_path("/api/v1", "/repos"), "/api/v1/repos");
+        assert_eq!(join_path("/api/v1/", "repos?page=2"), "/api/v1/repos?page=2");
+    }
+
+    #[test]
+    fn merged_headers_replace_all_values() {
+        let mut into = HeaderMap::new();
+        into.append(header::ACCEPT, HeaderValue::from_static("text/plain"));
+        into.append(header::ACCEPT, HeaderValue::from_static("text/html"));
+        into.insert(header::USER_AGENT, HeaderValue::from_static("charted-testkit"));
+
+        let mut from = HeaderMap::new();
+        from.append(header::ACCEPT, HeaderValue::from_static("application/json"));
+        from.append(header::ACCEPT, HeaderValue::from_static("application/msgpack"));
+
+        merge_headers(&mut into, from);
+        assert_eq!(
+            into.get_all(header::ACCEPT).iter().collect::<Vec<_>>(),
+            ["application/json", "application/msgpack"]
+        );
+
+        assert_eq!(into.get(header::USER_AGENT).unwrap(), "charted-testkit");
+    }
+
+    #[test]
+    fn auth_header_values() {
+        assert_eq!(Auth::bearer("weow").header_value(), "Bearer weow");
+        assert_eq!(Auth::basic("noel", None::<String>).header_value(), "Basic bm9lbDo=");
+        assert!(Auth::bearer("weow").header_value().is_sensitive());
+    }
+
+    #[test]
+    fn auth_debug_is_redacted() {
+        let debug = format!("{:?}", Auth::basic("noel", Some("hunter2")));
+        assert!(debug.contains("noel"));
+        assert!(!debug.contains("hunter2"));
+    }
+}
@@ -0,0 +1,480 @@
+// 📦🦋 charted TestKit: testing library for Axum services with testcontainers support
+// Copyright (c) 2024 Noelware, LLC. <team@noelware.org>
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Snapshot testing of responses with [`assert_response_snapshot!`][crate::assert_response_snapshot].
+//!
+//! A snapshot is a plain text rendering of a response's status code, a selection of its headers and
+//! its body (JSON bodies are pretty-printed). Snapshots are stored in the `tests/snapshots` directory
+//! of the crate that is being tested and are named after the test that created them, prefixed with its
+//! crate and module path (`<crate>__<module>__<test>.snap`).
+//!
+//! * If a snapshot doesn't exist yet, the assertion fails, so that a typo in a snapshot's name or
+//!   a snapshot that wasn't committed doesn't go unnoticed.
+//! * If it exists, the rendered response is compared with it and the assertion fails with a line
+//!   diff on mismatch.
+//! * If the `TESTKIT_UPDATE` environment variable is set to `1`, snapshots are created or rewritten
+//!   and the assertion passes.
+//!
+//! Values that change between runs, like timestamps, UUIDs or the `Date` header, can be redacted with
+//! [`Settings`]:
+//!
+//! ```rust,no_run
+//! # use charted_testkit::{TestContext, snapshot::Settings};
+//! # use axum::http::Method;
+//! #
+//! # #[tokio::main]
+//! # async fn main() {
+//! # let ctx = TestContext::default();
+//! let res = ctx.request("/users/@me", Method::GET, None, charted_testkit::noop_request).await.unwrap();
+//!
+//! # #[cfg(feature = "json")]
+//! charted_testkit::assert_response_snapshot!(
+//!     res,
+//!     Settings::new()
+//!         .header("date")
+//!         .redact_header("date")
+//!         .redact_json(".data.created_at")
+//!         .redact_uuids()
+//! );
+//! # }
+//! ```
+
+use crate::{exchange::Exchange, util::display_body};
+use axum::{
+    body::Bytes,
+    http::{header, response::Parts, HeaderName},
+};
+use regex::Regex;
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Settings for [`assert_response_snapshot!`][crate::assert_response_snapshot].
+#[derive(Debug, Clone)]
+pub struct Settings {
+    name: Option<String>,
+    headers: Vec<HeaderName>,
+    redactions: Vec<Redaction>,
+}
+
+#[derive(Debug, Clone)]
+enum Redaction {
+    Header(HeaderName),
+    #[cfg_attr(not(feature = "json"), allow(dead_code))]
+    Json(String),
+    Regex(Regex, String),
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            name: None,
+            headers: vec![header::CONTENT_TYPE],
+            redactions: Vec::new(),
+        }
+    }
+}
+
+impl Settings {
+    /// Creates new [`Settings`] which only include the `Content-Type` header.
+    pub fn new() -> Settings {
+        Settings::default()
+    }
+
+    /// Names the snapshot instead of naming it after the test that created it.
+    pub fn name<N: Into<String>>(mut self, name: N) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Includes a header in the snapshot.
+    ///
+    /// ## Panics
+    /// This will panic if `name` isn't a valid header name.
+    pub fn header<K>(mut self, name: K) -> Self
+    where
+        K: TryInto<HeaderName>,
+        K::Error: std::fmt::Debug,
+    {
+        self.headers.push(name.try_into().expect("header name to be valid"));
+        self
+    }
+
+    /// Replaces the value of a header with `[redacted]`.
+    ///
+    /// ## Panics
+    /// This will panic if `name` isn't a valid header name.
+    pub fn redact_header<K>(mut self, name: K) -> Self
+    where
+        K: TryInto<HeaderName>,
+        K::Error: std::fmt::Debug,
+    {
+        self.redactions
+            .push(Redaction::Header(name.try_into().expect("header name to be valid")));
+
+        self
+    }
+
+    /// Replaces every value at a JSON path (like `.items[*].created_at`) with `"[redacted]"`. See the
+    /// [`json`][crate::json] module for the path syntax.
+    ///
+    /// ## Panics
+    /// This will panic if `path` is an invalid path.
+    #[cfg(feature = "json")]
+    pub fn redact_json<P: Into<String>>(mut self, path: P) -> Self {
+        let path = path.into();
+        assert!(
+            crate::json::select(&serde_json::Value::Null, &path).is_some(),
+            "invalid JSON path: `{path}`"
+        );
+
+        self.redactions.push(Redaction::Json(path));
+        self
+    }
+
+    /// Replaces every match of a regular expression in the body with `replacement`.
+    ///
+    /// ## Panics
+    /// This will panic if `pattern` isn't a valid regular expression.
+    pub fn redact_regex<R: Into<String>>(mut self, pattern: &str, replacement: R) -> Self {
+        self.redactions.push(Redaction::Regex(
+            Regex::new(pattern).expect("pattern to be a valid regular expression"),
+            replacement.into(),
+        ));
+
+        self
+    }
+
+    /// Replaces every hyphenated UUID in the body with `[uuid]`.
+    pub fn redact_uuids(self) -> Self {
+        self.redact_regex(
+            "[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}",
+            "[uuid]",
+        )
+    }
+
+    /// Replaces every RFC 3339 timestamp (like `2024-08-19T04:20:00.000Z`) in the body with `[timestamp]`.
+    pub fn redact_timestamps(self) -> Self {
+        self.redact_regex(
+            r"\d{4}-\d{2}-\d{2}[Tt ]\d{2}:\d{2}:\d{2}(\.\d+)?([Zz]|[+-]\d{2}:\d{2})",
+            "[timestamp]",
+        )
+    }
+}
+
+/// Renders a response into its snapshot representation.
+pub(crate) fn render(parts: &Parts, body: &[u8], settings: &Settings) -> String {
+    let mut rendered = format!("status: {}\n", parts.status);
+
+    let mut wrote_header = false;
+    for (idx, name) in settings.headers.iter().enumerate() {
+        if settings.headers[..idx].contains(name) {
+            continue;
+        }
+
+        let redacted = settings
+            .redactions
+            .iter()
+            .any(|redaction| matches!(redaction, Redaction::Header(header) if header == name));
+
+        for value in parts.headers.get_all(name) {
+            if !wrote_header {
+                rendered.push_str("headers:\n");
+                wrote_header = true;
+            }
+
+            let value = match redacted {
+                true => String::from("[redacted]"),
+                false => String::from_utf8_lossy(value.as_bytes()).into_owned(),
+            };
+
+            let _ = writeln!(rendered, "  {name}: {value}");
+        }
+    }
+
+    rendered.push_str("body:\n");
+    let mut body = render_body(parts, body, settings);
+    for redaction in &settings.redactions {
+        if let Redaction::Regex(pattern, replacement) = redaction {
+            body = pattern.replace_all(&body, replacement.as_str()).into_owned();
+        }
+    }
+
+    rendered.push_str(&body);
+    if !rendered.ends_with('\n') {
+        rendered.push('\n');
+    }
+
+    rendered
+}
+
+fn render_body(parts: &Parts, body: &[u8], settings: &Settings) -> String {
+    #[cfg(feature = "json")]
+    {
+        let is_json = crate::util::content_type_essence(&parts.headers)
+            .is_some_and(|essence| essence == "application/json" || essence.ends_with("+json"));
+
+        if let Some(mut value) = is_json
+            .then(|| serde_json::from_slice::<serde_json::Value>(body).ok())
+            .flatten()
+        {
+            for redaction in &settings.redactions {
+                if let Redaction::Json(path) = redaction {
+                    for value in crate::json::select_mut(&mut value, path).unwrap_or_default() {
+                        *value = serde_json::Value::String(String::from("[redacted]"));
+                    }
+                }
+            }
+
+            return serde_json::to_string_pretty(&value).expect("JSON value to be serializable");
+        }
+    }
+
+    #[cfg(not(feature = "json"))]
+    let _ = (parts, settings);
+
+    match std::str::from_utf8(body) {
+        Ok(body) => body.to_owned(),
+        Err(_) => body.escape_ascii().to_string(),
+    }
+}
+
+/// Returns a line diff between `expected` and `actual`, where removed lines are prefixed
+/// with `-` and added lines with `+`.
+pub(crate) fn diff(expected: &str, actual: &str) -> String {
+    let expected = expected.lines().collect::<Vec<_>>();
+    let actual = actual.lines().collect::<Vec<_>>();
+
+    // longest common subsequence of lines; snapshots are small enough for this
+    let mut lcs = vec![vec![0usize; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            lcs[i][j] = match expected[i] == actual[j] {
+                true => lcs[i + 1][j + 1] + 1,
+                false => lcs[i + 1][j].max(lcs[i][j + 1]),
+            };
+        }
+    }
+
+    let mut rendered = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            let _ = writeln!(rendered, "  {}", expected[i]);
+            i += 1;
+            j += 1;
+        } else if i < expected.len() && (j == actual.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            let _ = writeln!(rendered, "- {}", expected[i]);
+            i += 1;
+        } else {
+            let _ = writeln!(rendered, "+ {}", actual[j]);
+            j += 1;
+        }
+    }
+
+    rendered
+}
+
+thread_local! {
+    static COUNTERS: RefCell<HashMap<String, usize>> = RefCell::new(HashMap::new());
+}
+
+/// Turns the type name of a function that was defined in a test (`krate::tests::my_test::{{closure}}::f`)
+/// into the name of the snapshot (`krate__tests__my_test`).
+pub(crate) fn snapshot_name(function: &str) -> String {
+    let mut segments = function
+        .split("::")
+        .filter(|segment| *segment != "{{closure}}")
+        .collect::<Vec<_>>();
+
+    // drop the function that was used to get the type name
+    segments.pop();
+
+    // `#[charted_testkit::test]` nests the test in a function with the same name. Other repeated
+    // segments (`api::api::get`) are kept, so that different tests don't share a snapshot.
+    if let [.., outer, inner] = segments[..] {
+        if outer == inner {
+            segments.pop();
+        }
+    }
+
+    // the crate is kept, as each integration test (`tests/users.rs`) is its own crate and their
+    // tests would otherwise share snapshots if they have the same name
+    segments.join("__")
+}
+
+#[doc(hidden)]
+#[track_caller]
+pub fn __assert_snapshot(manifest_dir: &str, function: &str, parts: &Parts, body: &Bytes, settings: &Settings) {
+    let name = settings.name.clone().unwrap_or_else(|| snapshot_name(function));
+    let name = COUNTERS.with_borrow_mut(|counters| {
+        let count = counters.entry(name.clone()).or_default();
+        *count += 1;
+
+        match *count {
+            1 => name,
+            n => format!("{name}-{n}"),
+        }
+    });
+
+    let path = Path::new(manifest_dir)
+        .join("tests")
+        .join("snapshots")
+        .join(format!("{name}.snap"));
+
+    let actual = render(parts, body, settings);
+    let update = std::env::var("TESTKIT_UPDATE").is_ok_and(|value| value == "1");
+
+    let expected = match fs::read_to_string(&path) {
+        Ok(expected) if !update => expected,
+        Ok(_) => return write(&path, &actual),
+        Err(_) if update => return write(&path, &actual),
+        Err(_) => {
+            return crate::soft::__fail(format!(
+                "snapshot `{}` doesn't exist; run the tests with `TESTKIT_UPDATE=1` to create it\n\nresponse:\n{actual}",
+                path.display()
+            ));
+        }
+    };
+
+    // snapshots could've been checked out with CRLF line endings
+    if expected.replace("\r\n", "\n") == actual {
+        return;
+    }
+
+    let mut message = format!(
+        "response doesn't match snapshot `{}`; run the tests with `TESTKIT_UPDATE=1` to update it\n\n{}",
+        path.display(),
+        diff(&expected, &actual),
+    );
+
+    match parts.extensions.get::<Exchange>() {
+        Some(exchange) => {
+            let _ = write!(message, "\n{}", exchange.with_body(body));
+        }
+
+        None => {
+            let _ = write!(message, "\nbody: {}", display_body(body));
+        }
+    }
+
+    crate::soft::__fail(message);
+}
+
+#[track_caller]
+fn write(path: &PathBuf, contents: &str) {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).unwrap_or_else(|e| panic!("failed to create `{}`: {e}", parent.display()));
+    }
+
+    fs::write(path, contents).unwrap_or_else(|e| panic!("failed to write snapshot `{}`: {e}", path.display()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff, render, snapshot_name, Settings};
+    use axum::http::{header, Response, StatusCode};
+
+    #[test]
+    fn names() {
+        assert_eq!(
+            snapshot_name("charted_testkit::tests::test_snapshots::{{closure}}::__f"),
+            "charted_testkit__tests__test_snapshots"
+        );
+        assert_eq!(
+            snapshot_name("usage::snapshots::snapshots::{{closure}}::__f"),
+            "usage__snapshots"
+        );
+        assert_eq!(snapshot_name("usage::snapshots::__f"), "usage__snapshots");
+        assert_eq!(
+            snapshot_name("usage::api::api::get::{{closure}}::__f"),
+            "usage__api__api__get"
+        );
+        assert_eq!(
+            snapshot_name("usage::api::api::get::get::{{closure}}::__f"),
+            "usage__api__api__get"
+        );
+
+        // integration tests are separate crates, so tests with the same name don't share a snapshot
+        assert_ne!(
+            snapshot_name("repos::create::create::{{closure}}::__f"),
+            snapshot_name("users::create::create::{{closure}}::__f")
+        );
+    }
+
+    #[test]
+    fn rendering() {
+        let (parts, _) = Response::builder()
+            .status(StatusCode::CREATED)
+            .header(header::CONTENT_TYPE, "text/plain")
+            .header(header::DATE, "Mon, 19 Aug 2024 04:20:00 GMT")
+            .header(header::SERVER, "charted")
+            .body(())
+            .unwrap()
+            .into_parts();
+
+        let settings = Settings::new()
+            .header("date")
+            .redact_header(header::DATE)
+            .redact_uuids()
+            .redact_timestamps();
+
+        assert_eq!(
+            render(
+                &parts,
+                b"created 9f0b6a5e-2d8c-4f6e-a8f7-5d1c6c1a2b3c at 2024-08-19T04:20:00.123Z",
+                &settings
+            ),
+            "status: 201 Created\nheaders:\n  content-type: text/plain\n  date: [redacted]\nbody:\ncreated [uuid] at [timestamp]\n"
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn rendering_json() {
+        let (parts, _) = Response::builder()
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(())
+            .unwrap()
+            .into_parts();
+
+        assert_eq!(
+            render(
+                &parts,
+                br#"{"items":[{"id":1,"at":1724000000},{"id":2,"at":1724000001}]}"#,
+                &Settings::new().redact_json(".items[*].at")
+            ),
+            "status: 200 OK\nheaders:\n  content-type: application/json\nbody:\n{\n  \"items\": [\n    {\n      \"at\": \"[redacted]\",\n      \"id\": 1\n    },\n    {\n      \"at\": \"[redacted]\",\n      \"id\": 2\n    }\n  ]\n}\n"
+        );
+    }
+
+    #[test]
+    fn diffs() {
+        assert_eq!(
+            diff("status: 200 OK\nbody:\na\nb\n", "status: 404 Not Found\nbody:\na\nc\n"),
+            "- status: 200 OK\n+ status: 404 Not Found\n  body:\n  a\n- b\n+ c\n"
+        );
+    }
+}
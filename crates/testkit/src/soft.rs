@@ -0,0 +1,260 @@
+// 📦🦋 charted TestKit: testing library for Axum services with testcontainers support
+// Copyright (c) 2024 Noelware, LLC. <team@noelware.org>
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Soft assertions, which record failures instead of panicking on the first one.
+//!
+//! While a [`SoftAssertions`] guard is alive, every TestKit assertion macro that fails on the same
+//! thread records its failure instead of panicking. When the guard is dropped (or
+//! [`SoftAssertions::finish`] is called), it panics with every recorded failure. If the thread is
+//! already panicking, like when a regular `assert!` failed, the recorded failures are printed to
+//! stderr instead. Use `#[charted_testkit::test(soft)]` to make a whole test use soft assertions.
+//!
+//! Assertions are tracked per thread, so the guard should be created on the same thread that the
+//! assertions run on. This is always the case with `#[charted_testkit::test]` and `#[tokio::test]`,
+//! which both use a current-thread runtime.
+//!
+//! ## Example
+//! ```rust,should_panic
+//! # use axum::http::{Response, StatusCode};
+//! use charted_testkit::soft::SoftAssertions;
+//!
+//! let res = Response::builder().status(StatusCode::NOT_FOUND).body(()).unwrap();
+//!
+//! let soft = SoftAssertions::new();
+//! charted_testkit::assert_status_code!(res, StatusCode::OK);
+//! charted_testkit::assert_has_header!(res, CONTENT_TYPE);
+//!
+//! assert_eq!(soft.len(), 2);
+//! soft.finish(); // panics with both failures
+//! ```
+
+use std::{
+    cell::RefCell,
+    fmt::{Display, Write},
+    panic::Location,
+};
+
+thread_local! {
+    static FRAMES: RefCell<Vec<Vec<Failure>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// A failed assertion that was recorded by a [`SoftAssertions`] guard.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failure {
+    message: String,
+    location: &'static Location<'static>,
+}
+
+impl Failure {
+    /// Failure message of the assertion.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Source location of the assertion that failed.
+    pub fn location(&self) -> &'static Location<'static> {
+        self.location
+    }
+}
+
+impl Display for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.location, self.message)
+    }
+}
+
+/// Guard that records failed assertions on the current thread until it is dropped. Guards can be
+/// nested, in which case failures are recorded by the innermost guard.
+#[derive(Debug)]
+#[must_use = "assertions are only soft while the guard is alive"]
+pub struct SoftAssertions {
+    depth: usize,
+    finished: bool,
+}
+
+impl Default for SoftAssertions {
+    fn default() -> Self {
+        SoftAssertions::new()
+    }
+}
+
+impl SoftAssertions {
+    /// Starts recording failed assertions on the current thread.
+    pub fn new() -> SoftAssertions {
+        let depth = FRAMES.with_borrow_mut(|frames| {
+            frames.push(Vec::new());
+            frames.len()
+        });
+
+        SoftAssertions { depth, finished: false }
+    }
+
+    /// Returns a copy of every failure that was recorded so far.
+    pub fn failures(&self) -> Vec<Failure> {
+        FRAMES.with_borrow(|frames| frames.get(self.depth - 1).cloned().unwrap_or_default())
+    }
+
+    /// Returns how many failures were recorded so far.
+    pub fn len(&self) -> usize {
+        FRAMES.with_borrow(|frames| frames.get(self.depth - 1).map_or(0, Vec::len))
+    }
+
+    /// Checks whenever if no failures were recorded so far.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Stops recording failed assertions and panics if any were recorded.
+    #[track_caller]
+    pub fn finish(mut self) {
+        self.finished = true;
+
+        let failures = self.take();
+        if !failures.is_empty() {
+            panic!("{}", report(&failures));
+        }
+    }
+
+    fn take(&mut self) -> Vec<Failure> {
+        FRAMES.with_borrow_mut(|frames| {
+            // guards should be dropped in reverse order, but frames that outlived
+            // their guard are merged so that their failures aren't lost
+            let mut failures = Vec::new();
+            while frames.len() >= self.depth {
+                let mut frame = frames.pop().unwrap_or_default();
+                frame.append(&mut failures);
+                failures = frame;
+            }
+
+            failures
+        })
+    }
+}
+
+impl Drop for SoftAssertions {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+
+        let failures = self.take();
+        if failures.is_empty() {
+            return;
+        }
+
+        // panicking again would abort, but the failures before the panic shouldn't be lost
+        if std::thread::panicking() {
+            eprintln!("charted-testkit: the test panicked after {}", report(&failures));
+            return;
+        }
+
+        panic!("{}", report(&failures));
+    }
+}
+
+fn report(failures: &[Failure]) -> String {
+    let mut report = match failures.len() {
+        1 => String::from("1 soft assertion failed:"),
+        n => format!("{n} soft assertions failed:"),
+    };
+
+    for (idx, failure) in failures.iter().enumerate() {
+        let _ = write!(report, "\n  {}) {}", idx + 1, failure.location);
+        for line in failure.message.lines() {
+            let _ = write!(report, "\n     {line}");
+        }
+    }
+
+    report
+}
+
+/// Reports a failed assertion: it is recorded if a [`SoftAssertions`] guard is alive on the
+/// current thread, otherwise this panics with `message`.
+#[doc(hidden)]
+#[track_caller]
+pub fn __fail(message: String) {
+    let location = Location::caller();
+    let message = FRAMES.with_borrow_mut(|frames| match frames.last_mut() {
+        Some(frame) => {
+            frame.push(Failure { message, location });
+            None
+        }
+
+        None => Some(message),
+    });
+
+    if let Some(message) = message {
+        panic!("{message}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SoftAssertions, __fail};
+    use std::panic::catch_unwind;
+
+    #[test]
+    fn records_failures() {
+        let soft = SoftAssertions::new();
+        __fail(String::from("first"));
+        __fail(String::from("second\nwith more details"));
+
+        let failures = soft.failures();
+        assert_eq!(failures.len(), 2);
+        assert_eq!(failures[0].message(), "first");
+        assert_eq!(failures[0].location().file(), file!());
+
+        let err = catch_unwind(move || soft.finish()).unwrap_err();
+        let message = err.downcast_ref::<String>().unwrap();
+
+        assert!(message.starts_with("2 soft assertions failed:\n  1) "));
+        assert!(message.contains("\n     first\n  2) "));
+        assert!(message.ends_with("\n     second\n     with more details"));
+    }
+
+    #[test]
+    fn nested_guards() {
+        let outer = SoftAssertions::new();
+        {
+            let inner = SoftAssertions::new();
+            __fail(String::from("inner"));
+            assert_eq!(inner.len(), 1);
+
+            let err = catch_unwind(move || drop(inner)).unwrap_err();
+            assert!(err.downcast_ref::<String>().unwrap().contains("inner"));
+        }
+
+        assert!(outer.is_empty());
+        outer.finish();
+    }
+
+    #[test]
+    #[should_panic(expected = "not soft")]
+    fn panics_without_guard() {
+        __fail(String::from("not soft"));
+    }
+
+    #[test]
+    fn finishing_without_failures() {
+        SoftAssertions::new().finish();
+        drop(SoftAssertions::new());
+    }
+}
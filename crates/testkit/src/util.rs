@@ -0,0 +1,137 @@
+// 📦🦋 charted TestKit: testing library for Axum services with testcontainers support
+// Copyright (c) 2024 Noelware, LLC. <team@noelware.org>
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Internal helpers that are shared between modules.
+
+use axum::http::{header, HeaderMap};
+use std::borrow::Cow;
+
+/// Maximum amount of bytes of a body that will be displayed in failure messages.
+const MAX_DISPLAYED_BODY: usize = 4096;
+
+/// Renders a raw body for debugging purposes. Bodies that aren't valid UTF-8 are
+/// escaped and long bodies are truncated.
+pub(crate) fn display_body(body: &[u8]) -> Cow<'_, str> {
+    if body.is_empty() {
+        return Cow::Borrowed("<empty>");
+    }
+
+    let (mut slice, truncated) = match body.len() > MAX_DISPLAYED_BODY {
+        true => (&body[..MAX_DISPLAYED_BODY], true),
+        false => (body, false),
+    };
+
+    // truncating can cut a multi-byte character in half, which doesn't make the body binary
+    if let Err(err) = std::str::from_utf8(slice) {
+        if truncated && err.error_len().is_none() {
+            slice = &slice[..err.valid_up_to()];
+        }
+    }
+
+    let mut rendered = match std::str::from_utf8(slice) {
+        Ok(s) => s.to_owned(),
+        Err(_) => slice.escape_ascii().to_string(),
+    };
+
+    if truncated {
+        rendered.push_str(&format!("... ({} more bytes)", body.len() - slice.len()));
+    }
+
+    Cow::Owned(rendered)
+}
+
+/// Returns the essence (`type/subtype`, lowercased and without parameters) of the `Content-Type` header.
+pub(crate) fn content_type_essence(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.split(';').next().unwrap_or_default().trim().to_ascii_lowercase())
+}
+
+/// Checks whenever if `value` is a hyphenated UUID (`xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx`).
+pub(crate) fn is_uuid(value: &str) -> bool {
+    let bytes = value.as_bytes();
+    bytes.len() == 36
+        && bytes.iter().enumerate().all(|(idx, b)| match idx {
+            8 | 13 | 18 | 23 => *b == b'-',
+            _ => b.is_ascii_hexdigit(),
+        })
+}
+
+/// Small xorshift64 random number generator, for faults and impairments that only need to be
+/// unpredictable and not secure.
+#[derive(Debug, Clone)]
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    /// Creates a generator from `seed`, which can be any number.
+    pub(crate) fn new(seed: u64) -> Rng {
+        // xorshift never leaves the all-zero state
+        Rng(seed | 1)
+    }
+
+    /// Creates a generator that is seeded from the current time.
+    pub(crate) fn from_time() -> Rng {
+        let seed = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos() as u64)
+            .unwrap_or_default();
+
+        Rng::new(seed)
+    }
+
+    /// Returns a random number in `[0, 1)`.
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{display_body, is_uuid};
+
+    #[test]
+    fn display_body_escapes_and_truncates() {
+        assert_eq!(display_body(b""), "<empty>");
+        assert_eq!(display_body(b"\xff\x00ab"), "\\xff\\x00ab");
+
+        let long = vec![b'a'; 5000];
+        assert!(display_body(&long).ends_with("... (904 more bytes)"));
+
+        // the limit falls in the middle of `é`, which is two bytes long
+        let text = format!("a{}", "é".repeat(2500));
+        let rendered = display_body(text.as_bytes());
+        assert!(rendered.starts_with("aéé"));
+        assert!(rendered.ends_with("é... (906 more bytes)"));
+    }
+
+    #[test]
+    fn uuids() {
+        assert!(is_uuid("9f0b6a5e-2d8c-4f6e-a8f7-5d1c6c1a2b3c"));
+        assert!(is_uuid("9F0B6A5E-2D8C-4F6E-A8F7-5D1C6C1A2B3C"));
+        assert!(!is_uuid("9f0b6a5e2d8c4f6ea8f75d1c6c1a2b3c"));
+        assert!(!is_uuid("9f0b6a5e-2d8c-4f6e-a8f7-5d1c6c1a2b3g"));
+    }
+}